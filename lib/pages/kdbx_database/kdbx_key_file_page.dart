@@ -115,6 +115,24 @@ class KdbxKeyFilePage extends StatelessWidget {
     if (config == null) return;
     final data = await SyncDriverFactory.getFile(config);
     final keyHash = openKdbxKeyFile(keyFile: data.toList());
+    if (keyHash.isEmpty) {
+      if (!context.mounted) return;
+      await showCupertinoDialog<void>(
+        barrierDismissible: true,
+        context: context,
+        builder: (context) => CupertinoAlertDialog(
+          title: Text('无法读取密钥文件'),
+          content: Text('密钥文件已损坏或格式不受支持'),
+          actions: [
+            CupertinoDialogAction(
+              child: Text('确定'),
+              onPressed: () => Navigator.of(context).pop(),
+            ),
+          ],
+        ),
+      );
+      return;
+    }
     if (isSave) {
       getIt.get<AppDatabase>().kdbxKeyFileDao.createKdbxKeyFile(
         KdbxKeyFileCompanion.insert(
@@ -7,5 +7,8 @@ pub fn init_app() {
 /// Open a KDBX key file and return the key
 #[flutter_rust_bridge::frb(sync)]
 pub fn open_kdbx_key_file(key_file: Vec<u8>) -> Vec<u8> {
+    // 损坏或版本不受支持的密钥文件返回空列表, 保持与生成的绑定一致
     crate::kdbx::keys::parse_keyfile(&key_file)
+        .map(|key_file| key_file.key.to_vec())
+        .unwrap_or_default()
 }
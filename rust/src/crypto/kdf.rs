@@ -1,19 +1,91 @@
 use aes::cipher::{BlockEncrypt, KeyInit};
 use aes::Aes256;
-use generic_array::typenum::U32;
 use generic_array::GenericArray;
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::crypto::memory_crypt::SecureDataError;
+use crate::crypto::secure_data::SecureData;
 
 #[derive(Debug, Error)]
 pub enum KdfError {
     #[error("Hash raw error")]
     HashRawError(#[from] argon2::Error),
+
+    #[error("Secure data error")]
+    SecureDataError(#[from] SecureDataError),
+
+    #[error("Invalid key length: {0}")]
+    InvalidKeyLength(usize),
+
+    #[error("Key derivation was cancelled")]
+    Cancelled,
+
+    #[error("Key derivation worker failed")]
+    WorkerFailed,
+
+    #[error("A cancelled key derivation is still running")]
+    Busy,
+}
+
+/// 取消密钥变换的令牌, 克隆后可在其他线程调用`cancel`
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// 密钥变换的进度回调与取消令牌
+#[derive(Default)]
+pub struct KdfOptions<'a> {
+    /// 在调用线程上以0.0~1.0报告进度
+    pub progress: Option<&'a dyn Fn(f64)>,
+    pub cancel: Option<CancellationToken>,
+}
+
+impl KdfOptions<'_> {
+    fn report(&self, progress: f64) {
+        if let Some(callback) = self.progress {
+            callback(progress);
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<(), KdfError> {
+        match &self.cancel {
+            Some(token) if token.is_cancelled() => Err(KdfError::Cancelled),
+            _ => Ok(()),
+        }
+    }
 }
 
 pub trait Kdf {
-    fn transform_key(&self, key: &GenericArray<u8, U32>)
-        -> Result<GenericArray<u8, U32>, KdfError>;
+    /// 变换32字节的复合密钥, 结果保存在`SecureData`中
+    fn transform_key(&self, key: &SecureData) -> Result<SecureData, KdfError> {
+        self.transform_key_with(key, &KdfOptions::default())
+    }
+
+    /// 带进度与取消的密钥变换, 取消时返回[`KdfError::Cancelled`]
+    fn transform_key_with(
+        &self,
+        key: &SecureData,
+        options: &KdfOptions,
+    ) -> Result<SecureData, KdfError>;
 }
 
 pub struct AesKdf {
@@ -21,30 +93,94 @@ pub struct AesKdf {
     pub rounds: u64,
 }
 
+/// 基准测试时每次变换的AES轮数
+const AES_BENCHMARK_CHUNK: u64 = 10_000;
+/// 每完成这么多轮检查一次取消并报告进度
+const AES_PROGRESS_CHUNK: u64 = 100_000;
+/// 等待Argon2工作线程时检查取消的间隔
+const ARGON2_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const WORKER_RUNNING: u8 = 0;
+const WORKER_DONE: u8 = 1;
+const WORKER_ABANDONED: u8 = 2;
+
+/// 按参数指纹统计已取消但仍在计算的Argon2工作线程
+static ABANDONED_ARGON2_WORKERS: Mutex<BTreeMap<[u8; 32], usize>> = Mutex::new(BTreeMap::new());
+
+fn lock_abandoned_workers() -> MutexGuard<'static, BTreeMap<[u8; 32], usize>> {
+    ABANDONED_ARGON2_WORKERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// 已取消但仍占用内存的Argon2工作线程数量
+pub fn abandoned_argon2_workers() -> usize {
+    lock_abandoned_workers().values().sum()
+}
+
+impl AesKdf {
+    /// 在`duration`内反复变换, 测量本机每秒可完成的轮数
+    pub fn benchmark(duration: Duration) -> Result<u64, KdfError> {
+        let kdf = AesKdf {
+            seed: vec![0; 32],
+            rounds: AES_BENCHMARK_CHUNK,
+        };
+        let key = SecureData::new(&[0; 32]);
+        let start = Instant::now();
+        let mut rounds = 0u64;
+        while rounds == 0 || start.elapsed() < duration {
+            kdf.transform_key(&key)?;
+            rounds += AES_BENCHMARK_CHUNK;
+        }
+        let per_second = rounds as f64 / start.elapsed().as_secs_f64();
+        Ok((per_second as u64).max(1))
+    }
+}
+
 impl Kdf for AesKdf {
-    fn transform_key(
+    fn transform_key_with(
         &self,
-        key: &GenericArray<u8, U32>,
-    ) -> Result<GenericArray<u8, U32>, KdfError> {
+        key: &SecureData,
+        options: &KdfOptions,
+    ) -> Result<SecureData, KdfError> {
+        let key = key.unsecure()?;
+        if key.len() != 32 {
+            return Err(KdfError::InvalidKeyLength(key.len()));
+        }
         let seed_array = GenericArray::from_slice(&self.seed);
         let cipher = Aes256::new(seed_array);
 
         let mut block1 = GenericArray::clone_from_slice(&key[..16]);
         let mut block2 = GenericArray::clone_from_slice(&key[16..]);
-        for _ in 0..self.rounds {
-            cipher.encrypt_block(&mut block1);
-            cipher.encrypt_block(&mut block2);
+        let mut done = 0;
+        options.report(0.0);
+        while done < self.rounds {
+            if let Err(e) = options.check_cancelled() {
+                block1.as_mut_slice().zeroize();
+                block2.as_mut_slice().zeroize();
+                return Err(e);
+            }
+            let chunk = AES_PROGRESS_CHUNK.min(self.rounds - done);
+            for _ in 0..chunk {
+                cipher.encrypt_block(&mut block1);
+                cipher.encrypt_block(&mut block2);
+            }
+            done += chunk;
+            options.report(done as f64 / self.rounds as f64);
         }
 
         let mut digest = Sha256::new();
 
         digest.update(block1);
         digest.update(block2);
+        block1.as_mut_slice().zeroize();
+        block2.as_mut_slice().zeroize();
 
-        Ok(digest.finalize())
+        Ok(SecureData::take(digest.finalize().as_mut_slice()))
     }
 }
 
+#[derive(Clone)]
 pub struct Argon2Kdf {
     pub memory: u64,
     pub salt: Vec<u8>,
@@ -52,26 +188,244 @@ pub struct Argon2Kdf {
     pub parallelism: u32,
     pub version: argon2::Version,
     pub variant: argon2::Variant,
+    /// 可选的密钥参数K, 快速解锁以此混入平台密钥
+    pub secret: Zeroizing<Vec<u8>>,
 }
 
-impl Kdf for Argon2Kdf {
-    fn transform_key(
-        &self,
-        key: &GenericArray<u8, U32>,
-    ) -> Result<GenericArray<u8, U32>, KdfError> {
-        let config = argon2::Config {
+impl Argon2Kdf {
+    /// 以当前参数执行一次变换, 返回本机的耗时
+    pub fn benchmark(&self) -> Result<Duration, KdfError> {
+        let key = SecureData::new(&[0; 32]);
+        let start = Instant::now();
+        self.transform_key(&key)?;
+        Ok(start.elapsed())
+    }
+}
+
+impl Argon2Kdf {
+    fn config(&self) -> argon2::Config<'_> {
+        argon2::Config {
             thread_mode: argon2::ThreadMode::Parallel,
             ad: &[],
             hash_length: 32,
             lanes: self.parallelism,
             mem_cost: (self.memory / 1024) as u32,
-            secret: &[],
+            secret: &self.secret,
             time_cost: self.iterations as u32,
             variant: self.variant,
             version: self.version,
+        }
+    }
+
+    /// 区分数据库与参数的指纹, 已取消的计算只阻止相同参数的重试
+    fn worker_id(&self) -> [u8; 32] {
+        let mut digest = Sha256::new();
+        digest.update(&self.salt);
+        digest.update(self.memory.to_le_bytes());
+        digest.update(self.iterations.to_le_bytes());
+        digest.update(self.parallelism.to_le_bytes());
+        digest.update(self.version.as_u32().to_le_bytes());
+        digest.update(self.variant.as_u32().to_le_bytes());
+        digest.finalize().into()
+    }
+
+    /// 相同参数下已取消但仍在计算的工作线程数量
+    fn abandoned_workers(&self) -> usize {
+        lock_abandoned_workers()
+            .get(&self.worker_id())
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+impl Kdf for Argon2Kdf {
+    /// Argon2无法按轮报告进度, 提供取消令牌时在工作线程上计算, 调用线程轮询取消
+    ///
+    /// 取消后立即返回, 但工作线程仍会完成整个计算, 其占用的内存在计算结束后才释放.
+    /// 为避免反复取消重试时内存叠加, 相同参数(同一数据库)已取消的计算结束前,
+    /// 带取消令牌的调用返回[`KdfError::Busy`], 其他参数的调用不受影响.
+    fn transform_key_with(
+        &self,
+        key: &SecureData,
+        options: &KdfOptions,
+    ) -> Result<SecureData, KdfError> {
+        options.check_cancelled()?;
+        options.report(0.0);
+        let key = key.unsecure()?;
+        let mut result = match &options.cancel {
+            None => Zeroizing::new(argon2::hash_raw(&key, &self.salt, &self.config())?),
+            Some(token) => {
+                if self.abandoned_workers() > 0 {
+                    return Err(KdfError::Busy);
+                }
+                let (sender, receiver) = mpsc::channel();
+                let kdf = self.clone();
+                let id = self.worker_id();
+                let state = Arc::new(AtomicU8::new(WORKER_RUNNING));
+                let worker_state = state.clone();
+                std::thread::spawn(move || {
+                    let result =
+                        argon2::hash_raw(&key, &kdf.salt, &kdf.config()).map(Zeroizing::new);
+                    drop(kdf);
+                    // 持锁修改状态, 与取消时的计数保持一致
+                    let mut abandoned = lock_abandoned_workers();
+                    if worker_state.swap(WORKER_DONE, Ordering::SeqCst) == WORKER_ABANDONED {
+                        if let Some(count) = abandoned.get_mut(&id) {
+                            *count -= 1;
+                            if *count == 0 {
+                                abandoned.remove(&id);
+                            }
+                        }
+                    }
+                    drop(abandoned);
+                    // 已取消时接收端已关闭, 结果随之清零释放
+                    let _ = sender.send(result);
+                });
+                loop {
+                    match receiver.recv_timeout(ARGON2_POLL_INTERVAL) {
+                        Ok(result) => break result?,
+                        Err(mpsc::RecvTimeoutError::Timeout) if token.is_cancelled() => {
+                            let mut abandoned = lock_abandoned_workers();
+                            if state
+                                .compare_exchange(
+                                    WORKER_RUNNING,
+                                    WORKER_ABANDONED,
+                                    Ordering::SeqCst,
+                                    Ordering::SeqCst,
+                                )
+                                .is_ok()
+                            {
+                                *abandoned.entry(id).or_default() += 1;
+                            }
+                            return Err(KdfError::Cancelled);
+                        }
+                        Err(mpsc::RecvTimeoutError::Timeout) => {}
+                        Err(mpsc::RecvTimeoutError::Disconnected) => {
+                            return Err(KdfError::WorkerFailed)
+                        }
+                    }
+                }
+            }
+        };
+        options.report(1.0);
+        Ok(SecureData::take(&mut result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// 工作线程按参数计数, 各测试使用不同的盐互不影响
+    fn argon2_kdf(salt: u8) -> Argon2Kdf {
+        Argon2Kdf {
+            memory: 1024 * 1024,
+            salt: vec![salt; 32],
+            iterations: 1,
+            parallelism: 2,
+            version: argon2::Version::Version13,
+            variant: argon2::Variant::Argon2id,
+            secret: Zeroizing::new(vec![]),
+        }
+    }
+
+    #[test]
+    fn test_aes_kdf_progress() {
+        let kdf = AesKdf {
+            seed: vec![0; 32],
+            rounds: AES_PROGRESS_CHUNK * 2 + 1,
+        };
+        let key = SecureData::new(&[7; 32]);
+        let reports = RefCell::new(vec![]);
+        let report = |p: f64| reports.borrow_mut().push(p);
+        let options = KdfOptions {
+            progress: Some(&report),
+            cancel: None,
+        };
+        let result = kdf.transform_key_with(&key, &options).unwrap();
+        assert_eq!(
+            result.unsecure().unwrap(),
+            kdf.transform_key(&key).unwrap().unsecure().unwrap()
+        );
+
+        let reports = reports.into_inner();
+        assert_eq!(reports.len(), 4);
+        assert!(reports.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(reports.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn test_aes_kdf_cancel() {
+        let kdf = AesKdf {
+            seed: vec![0; 32],
+            rounds: u64::MAX,
+        };
+        let token = CancellationToken::new();
+        token.cancel();
+        let options = KdfOptions {
+            progress: None,
+            cancel: Some(token),
+        };
+        assert!(matches!(
+            kdf.transform_key_with(&SecureData::new(&[7; 32]), &options),
+            Err(KdfError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_argon2_kdf_worker_thread() {
+        let kdf = argon2_kdf(1);
+        let key = SecureData::new(&[7; 32]);
+        let options = KdfOptions {
+            progress: None,
+            cancel: Some(CancellationToken::new()),
+        };
+        assert_eq!(
+            kdf.transform_key_with(&key, &options)
+                .unwrap()
+                .unsecure()
+                .unwrap(),
+            kdf.transform_key(&key).unwrap().unsecure().unwrap()
+        );
+        assert_eq!(kdf.abandoned_workers(), 0);
+    }
+
+    #[test]
+    fn test_argon2_kdf_cancel() {
+        let kdf = argon2_kdf(2);
+        let token = CancellationToken::new();
+        token.cancel();
+        let options = KdfOptions {
+            progress: None,
+            cancel: Some(token),
+        };
+        assert!(matches!(
+            kdf.transform_key_with(&SecureData::new(&[7; 32]), &options),
+            Err(KdfError::Cancelled)
+        ));
+        // 开始前已取消时不会启动工作线程
+        assert_eq!(kdf.abandoned_workers(), 0);
+    }
+
+    #[test]
+    fn test_argon2_kdf_busy_per_parameters() {
+        let busy = argon2_kdf(3);
+        let other = argon2_kdf(4);
+        lock_abandoned_workers().insert(busy.worker_id(), 1);
+
+        let key = SecureData::new(&[7; 32]);
+        let options = KdfOptions {
+            progress: None,
+            cancel: Some(CancellationToken::new()),
         };
+        let busy_result = busy.transform_key_with(&key, &options);
+        let other_result = other.transform_key_with(&key, &options);
+        lock_abandoned_workers().remove(&busy.worker_id());
 
-        let key = argon2::hash_raw(key, &self.salt, &config).map_err(KdfError::HashRawError)?;
-        Ok(*GenericArray::from_slice(&key))
+        assert!(matches!(busy_result, Err(KdfError::Busy)));
+        assert!(other_result.is_ok());
+        // 不带取消令牌时在调用线程上计算, 不受影响
+        assert!(busy.transform_key(&key).is_ok());
     }
 }
@@ -119,14 +119,153 @@ pub mod memory_crypt {
 
 #[cfg(unix)]
 pub mod memory_crypt {
+    //! 以进程随机密钥和XChaCha20-Poly1305加密内存, 每份数据使用独立的随机nonce
+    //!
+    //! 加密后的布局为 nonce(24) || 密文 || tag(16). 进程密钥存放在单独mmap的页中,
+    //! 该页被mlock并排除出core dump, 在进程生命周期内不会释放.
+
+    use std::sync::OnceLock;
+
+    use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+    use chacha20poly1305::{Key, Tag, XChaCha20Poly1305, XNonce};
+
     use crate::crypto::memory_crypt::SecureDataError;
 
+    const KEY_SIZE: usize = 32;
+    const NONCE_SIZE: usize = 24;
+    const TAG_SIZE: usize = 16;
+
+    struct ProcessKey {
+        page: *mut u8,
+    }
+
+    // 页在初始化后只读, 且永不释放
+    unsafe impl Send for ProcessKey {}
+    unsafe impl Sync for ProcessKey {}
+
+    static PROCESS_KEY: OnceLock<Result<ProcessKey, String>> = OnceLock::new();
+
+    impl ProcessKey {
+        fn generate() -> Result<Self, String> {
+            unsafe {
+                let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+                let page = libc::mmap(
+                    std::ptr::null_mut(),
+                    page_size,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANON,
+                    -1,
+                    0,
+                );
+                if page == libc::MAP_FAILED {
+                    return Err(format!("mmap failed: {}", std::io::Error::last_os_error()));
+                }
+
+                // RLIMIT_MEMLOCK不足时仍可加密, 只是密钥页可能被换出
+                if libc::mlock(page, page_size) != 0 {
+                    eprintln!(
+                        "Warning: mlock of memory key page failed: {}",
+                        std::io::Error::last_os_error()
+                    );
+                }
+                exclude_from_dump(page, page_size);
+
+                let key = std::slice::from_raw_parts_mut(page as *mut u8, KEY_SIZE);
+                getrandom::fill(key).map_err(|e| e.to_string())?;
+                Ok(Self {
+                    page: page as *mut u8,
+                })
+            }
+        }
+
+        fn cipher(&self) -> XChaCha20Poly1305 {
+            let key = unsafe { std::slice::from_raw_parts(self.page, KEY_SIZE) };
+            XChaCha20Poly1305::new(Key::from_slice(key))
+        }
+    }
+
+    /// 将内存排除出core dump, 平台不支持时忽略
+    pub(crate) unsafe fn exclude_from_dump(ptr: *mut std::ffi::c_void, len: usize) {
+        #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+        {
+            if libc::madvise(ptr, len, libc::MADV_NOCORE) != 0 {
+                eprintln!(
+                    "Warning: madvise MADV_NOCORE failed: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if libc::madvise(ptr, len, libc::MADV_DONTDUMP) != 0 {
+                eprintln!(
+                    "Warning: madvise MADV_DONTDUMP failed: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+
+        #[cfg(not(any(
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "linux",
+            target_os = "android"
+        )))]
+        let _ = (ptr, len);
+    }
+
+    fn cipher() -> Result<XChaCha20Poly1305, String> {
+        PROCESS_KEY
+            .get_or_init(ProcessKey::generate)
+            .as_ref()
+            .map(ProcessKey::cipher)
+            .map_err(Clone::clone)
+    }
+
     pub fn crypt_memory(data: &[u8]) -> Result<Vec<u8>, SecureDataError> {
-        Ok(data.to_vec())
+        let cipher = cipher().map_err(SecureDataError::EncryptionFailed)?;
+        let mut nonce = XNonce::default();
+        getrandom::fill(&mut nonce)
+            .map_err(|e| SecureDataError::EncryptionFailed(e.to_string()))?;
+
+        // 预先分配全部空间, 避免扩容时在旧缓冲区留下明文
+        let mut buffer = Vec::with_capacity(NONCE_SIZE + data.len() + TAG_SIZE);
+        buffer.extend_from_slice(&nonce);
+        buffer.extend_from_slice(data);
+        let tag = match cipher.encrypt_in_place_detached(&nonce, &[], &mut buffer[NONCE_SIZE..]) {
+            Ok(tag) => tag,
+            Err(e) => {
+                zeroize::Zeroize::zeroize(&mut buffer);
+                return Err(SecureDataError::EncryptionFailed(e.to_string()));
+            }
+        };
+        buffer.extend_from_slice(&tag);
+        Ok(buffer)
     }
 
     pub fn uncrypt_memory(data: &[u8], original_len: usize) -> Result<Vec<u8>, SecureDataError> {
-        Ok(data.to_vec())
+        let expected = NONCE_SIZE + original_len + TAG_SIZE;
+        if data.len() != expected {
+            return Err(SecureDataError::InvalidDataLength {
+                expected,
+                actual: data.len(),
+            });
+        }
+        let cipher = cipher().map_err(SecureDataError::DecryptionFailed)?;
+        let (nonce, rest) = data.split_at(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at(original_len);
+
+        let mut buffer = ciphertext.to_vec();
+        cipher
+            .decrypt_in_place_detached(
+                XNonce::from_slice(nonce),
+                &[],
+                &mut buffer,
+                Tag::from_slice(tag),
+            )
+            .map_err(|e| SecureDataError::DecryptionFailed(e.to_string()))?;
+        Ok(buffer)
     }
 
     pub fn mlock(data: &mut Vec<u8>) -> Result<(), SecureDataError> {
@@ -135,7 +274,7 @@ pub mod memory_crypt {
         }
 
         unsafe {
-            let ptr = data.as_mut_ptr() as *const std::ffi::c_void;
+            let ptr = data.as_mut_ptr() as *mut std::ffi::c_void;
             let len = data.len();
 
             if libc::mlock(ptr, len) != 0 {
@@ -146,25 +285,7 @@ pub mod memory_crypt {
                 )));
             }
 
-            #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
-            {
-                if libc::madvise(ptr, len, libc::MADV_NOCORE) != 0 {
-                    eprintln!(
-                        "Warning: madvise MADV_NOCORE failed: {}",
-                        std::io::Error::last_os_error()
-                    );
-                }
-            }
-
-            #[cfg(target_os = "linux")]
-            {
-                if libc::madvise(ptr, len, libc::MADV_DONTDUMP) != 0 {
-                    eprintln!(
-                        "Warning: madvise MADV_DONTDUMP failed: {}",
-                        std::io::Error::last_os_error()
-                    );
-                }
-            }
+            exclude_from_dump(ptr, len);
         }
         Ok(())
     }
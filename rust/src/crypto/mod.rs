@@ -3,4 +3,8 @@ pub mod errors;
 pub mod hash;
 pub mod kdf;
 pub mod memory_crypt;
+#[cfg(unix)]
+pub mod secure_arena;
 pub mod secure_data;
+#[cfg(test)]
+pub(crate) mod wipe_check;
@@ -0,0 +1,302 @@
+//! 存放敏感数据的内存池
+//!
+//! 每个chunk是一段独立mmap的内存, 前后各有一个`PROT_NONE`的保护页, 数据页被mlock并排除出core dump.
+//! 小块数据按大小分级共享chunk, 以免每个`SecureData`单独mlock而很快达到`RLIMIT_MEMLOCK`;
+//! 超过最大分级的数据独占一个chunk. 释放时slot被清零, chunk全部空闲后解锁并归还系统.
+//! mlock失败时chunk仍然可用, 只是不被锁定, 失败次数可通过[`stats`]查询.
+
+use std::ptr::NonNull;
+use std::sync::{Mutex, MutexGuard};
+
+use zeroize::Zeroize;
+
+use crate::crypto::memory_crypt::{memory_crypt, SecureDataError};
+
+/// 最小的slot大小, 更小的数据向上取整
+const MIN_SLOT_SIZE: usize = 32;
+/// 超过此大小的数据独占一个chunk
+const MAX_SLOT_SIZE: usize = 2048;
+/// 共享chunk的数据页数
+const CHUNK_PAGES: usize = 16;
+
+static ARENA: Mutex<Arena> = Mutex::new(Arena {
+    chunks: Vec::new(),
+    lock_failures: 0,
+});
+
+/// 内存池的使用情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ArenaStats {
+    pub chunks: usize,
+    /// 已被mlock的数据页字节数
+    pub locked_bytes: usize,
+    /// mlock失败而未被锁定的数据页字节数
+    pub unlocked_bytes: usize,
+    /// 进程启动以来mlock失败的次数
+    pub lock_failures: usize,
+}
+
+/// 从内存池分配的缓冲区, Drop时清零并归还slot
+pub struct SecureBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    locked: bool,
+}
+
+// 缓冲区独占其slot, 归还时经由全局锁
+unsafe impl Send for SecureBuffer {}
+
+impl SecureBuffer {
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// 所在chunk是否已被mlock
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl Drop for SecureBuffer {
+    fn drop(&mut self) {
+        self.as_mut_slice().zeroize();
+        lock_arena().free(self.ptr.as_ptr());
+    }
+}
+
+/// 分配`len`字节的清零缓冲区, `len`不能为0
+pub fn allocate(len: usize) -> Result<SecureBuffer, SecureDataError> {
+    if len == 0 {
+        return Err(SecureDataError::InvalidDataLength {
+            expected: 1,
+            actual: 0,
+        });
+    }
+    let (ptr, locked) = lock_arena().allocate(len)?;
+    Ok(SecureBuffer { ptr, len, locked })
+}
+
+pub fn stats() -> ArenaStats {
+    let arena = lock_arena();
+    let mut stats = ArenaStats {
+        chunks: arena.chunks.len(),
+        lock_failures: arena.lock_failures,
+        ..Default::default()
+    };
+    for chunk in &arena.chunks {
+        if chunk.locked {
+            stats.locked_bytes += chunk.data_len;
+        } else {
+            stats.unlocked_bytes += chunk.data_len;
+        }
+    }
+    stats
+}
+
+fn lock_arena() -> MutexGuard<'static, Arena> {
+    ARENA.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+fn slot_size(len: usize) -> usize {
+    len.next_power_of_two().max(MIN_SLOT_SIZE)
+}
+
+struct Arena {
+    chunks: Vec<Chunk>,
+    lock_failures: usize,
+}
+
+impl Arena {
+    fn allocate(&mut self, len: usize) -> Result<(NonNull<u8>, bool), SecureDataError> {
+        let page = page_size();
+        let (slot_size, data_len) = if len > MAX_SLOT_SIZE {
+            let size = len.div_ceil(page) * page;
+            (size, size)
+        } else {
+            (slot_size(len), CHUNK_PAGES * page)
+        };
+
+        if len <= MAX_SLOT_SIZE {
+            if let Some(chunk) = self
+                .chunks
+                .iter_mut()
+                .find(|c| c.slot_size == slot_size && !c.free.is_empty())
+            {
+                return Ok((chunk.take_slot(), chunk.locked));
+            }
+        }
+
+        let mut chunk = Chunk::map(slot_size, data_len, page)?;
+        if !chunk.locked {
+            self.lock_failures += 1;
+        }
+        let ptr = chunk.take_slot();
+        let locked = chunk.locked;
+        self.chunks.push(chunk);
+        Ok((ptr, locked))
+    }
+
+    fn free(&mut self, ptr: *mut u8) {
+        let Some(index) = self.chunks.iter().position(|c| c.contains(ptr)) else {
+            return;
+        };
+        let chunk = &mut self.chunks[index];
+        chunk
+            .free
+            .push((ptr as usize - chunk.data as usize) / chunk.slot_size);
+        if chunk.free.len() == chunk.slots() {
+            self.chunks.swap_remove(index);
+        }
+    }
+}
+
+struct Chunk {
+    /// 包含前后保护页的整个映射
+    base: *mut u8,
+    map_len: usize,
+    data: *mut u8,
+    data_len: usize,
+    slot_size: usize,
+    free: Vec<usize>,
+    locked: bool,
+}
+
+// chunk只在全局锁内访问
+unsafe impl Send for Chunk {}
+
+impl Chunk {
+    fn map(slot_size: usize, data_len: usize, page: usize) -> Result<Self, SecureDataError> {
+        let map_len = data_len + 2 * page;
+        unsafe {
+            let base = libc::mmap(
+                std::ptr::null_mut(),
+                map_len,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            );
+            if base == libc::MAP_FAILED {
+                return Err(SecureDataError::MemoryLockFailed(format!(
+                    "mmap failed for {} bytes: {}",
+                    map_len,
+                    std::io::Error::last_os_error()
+                )));
+            }
+            let data = (base as *mut u8).add(page);
+            if libc::mprotect(
+                data as *mut std::ffi::c_void,
+                data_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+            ) != 0
+            {
+                let errno = std::io::Error::last_os_error();
+                libc::munmap(base, map_len);
+                return Err(SecureDataError::MemoryLockFailed(format!(
+                    "mprotect failed: {}",
+                    errno
+                )));
+            }
+
+            let locked = libc::mlock(data as *const std::ffi::c_void, data_len) == 0;
+            if !locked {
+                eprintln!(
+                    "Warning: mlock failed for {} bytes, secrets may be swapped: {}",
+                    data_len,
+                    std::io::Error::last_os_error()
+                );
+            }
+            memory_crypt::exclude_from_dump(data as *mut std::ffi::c_void, data_len);
+
+            Ok(Self {
+                base: base as *mut u8,
+                map_len,
+                data,
+                data_len,
+                slot_size,
+                // 倒序存放, 先分配低地址的slot
+                free: (0..data_len / slot_size).rev().collect(),
+                locked,
+            })
+        }
+    }
+
+    fn slots(&self) -> usize {
+        self.data_len / self.slot_size
+    }
+
+    fn contains(&self, ptr: *mut u8) -> bool {
+        let offset = (ptr as usize).wrapping_sub(self.data as usize);
+        offset < self.data_len
+    }
+
+    fn take_slot(&mut self) -> NonNull<u8> {
+        let slot = self.free.pop().expect("chunk has a free slot");
+        unsafe { NonNull::new_unchecked(self.data.add(slot * self.slot_size)) }
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        unsafe {
+            std::slice::from_raw_parts_mut(self.data, self.data_len).zeroize();
+            if self.locked {
+                libc::munlock(self.data as *const std::ffi::c_void, self.data_len);
+            }
+            libc::munmap(self.base as *mut std::ffi::c_void, self.map_len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freed_slot_is_zeroed() {
+        let mut a = allocate(40).unwrap();
+        let b = allocate(50).unwrap();
+        assert_eq!(a.as_slice(), &[0u8; 40]);
+        assert_eq!(b.as_slice().len(), 50);
+
+        a.as_mut_slice().fill(0xA5);
+        let ptr = a.ptr.as_ptr();
+        drop(a);
+
+        // 持有锁期间chunk不会被释放, 仍在映射中时检查slot内容
+        let arena = lock_arena();
+        if arena.chunks.iter().any(|c| c.contains(ptr)) {
+            let freed = unsafe { std::slice::from_raw_parts(ptr, 40) };
+            assert!(freed.iter().all(|&x| x == 0));
+        }
+    }
+
+    #[test]
+    fn test_large_allocation_has_own_chunk() {
+        let page = page_size();
+        let mut buffer = allocate(MAX_SLOT_SIZE + 1).unwrap();
+        buffer.as_mut_slice().fill(1);
+        let arena = lock_arena();
+        let chunk = arena
+            .chunks
+            .iter()
+            .find(|c| c.contains(buffer.ptr.as_ptr()))
+            .unwrap();
+        assert_eq!(chunk.slots(), 1);
+        assert_eq!(chunk.data_len % page, 0);
+        assert_eq!(chunk.map_len, chunk.data_len + 2 * page);
+    }
+
+    #[test]
+    fn test_zero_length_rejected() {
+        assert!(allocate(0).is_err());
+    }
+}
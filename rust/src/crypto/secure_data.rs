@@ -3,10 +3,72 @@ use std::marker::PhantomData;
 use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::crypto::memory_crypt::{memory_crypt, SecureDataError};
+#[cfg(unix)]
+use crate::crypto::secure_arena::{self, SecureBuffer};
 
-#[derive(Zeroize, ZeroizeOnDrop)]
+/// 数据所在的内存, Unix上锁定的数据放在[`secure_arena`]中
+enum Storage {
+    Heap(Vec<u8>),
+    #[cfg(unix)]
+    Arena(SecureBuffer),
+}
+
+impl Storage {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Storage::Heap(data) => data,
+            #[cfg(unix)]
+            Storage::Arena(buffer) => buffer.as_slice(),
+        }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        match self {
+            Storage::Heap(data) => data,
+            #[cfg(unix)]
+            Storage::Arena(buffer) => buffer.as_mut_slice(),
+        }
+    }
+
+    /// 把数据移入锁定的内存并清零来源, 返回新的存储及是否真正锁定
+    ///
+    /// 内存池的mlock失败时数据仍放入内存池(排除出core dump), 但不视为已锁定
+    #[cfg(unix)]
+    fn locked(data: &mut Vec<u8>) -> Result<(Self, bool), SecureDataError> {
+        if data.is_empty() {
+            return Ok((Storage::Heap(Vec::new()), true));
+        }
+        let mut buffer = secure_arena::allocate(data.len())?;
+        buffer.as_mut_slice().copy_from_slice(data);
+        data.zeroize();
+        let locked = buffer.is_locked();
+        Ok((Storage::Arena(buffer), locked))
+    }
+
+    #[cfg(not(unix))]
+    fn locked(data: &mut Vec<u8>) -> Result<(Self, bool), SecureDataError> {
+        let mut data = std::mem::take(data);
+        if let Err(e) = memory_crypt::mlock(&mut data) {
+            data.zeroize();
+            return Err(e);
+        }
+        Ok((Storage::Heap(data), true))
+    }
+}
+
+impl Zeroize for Storage {
+    fn zeroize(&mut self) {
+        match self {
+            Storage::Heap(data) => data.zeroize(),
+            #[cfg(unix)]
+            Storage::Arena(buffer) => buffer.as_mut_slice().zeroize(),
+        }
+    }
+}
+
+#[derive(Zeroize)]
 pub struct SecureData {
-    data: Vec<u8>,
+    storage: Storage,
     original_len: usize,
     _marker: PhantomData<*const ()>,
 
@@ -23,7 +85,7 @@ impl PartialEq for SecureData {
 impl SecureData {
     pub fn new(data: &[u8]) -> Self {
         Self {
-            data: data.to_vec(),
+            storage: Storage::Heap(data.to_vec()),
             original_len: data.len(),
             _marker: PhantomData,
             is_crypt: false,
@@ -31,19 +93,63 @@ impl SecureData {
         }
     }
 
+    /// 复制数据后清零来源, 用于接管临时缓冲区中的密钥
+    pub fn take(data: &mut [u8]) -> Self {
+        let secure = Self::new(data);
+        data.zeroize();
+        secure
+    }
+
+    /// 加密保存的数据, 明文缓冲区会被清零, 已锁定的内存会换成锁定的密文缓冲区
     pub fn crypt(&mut self) -> Result<(), SecureDataError> {
-        self.data = memory_crypt::crypt_memory(&self.data)?;
+        if self.is_crypt {
+            return Ok(());
+        }
+        let mut encrypted = memory_crypt::crypt_memory(self.storage.as_slice())?;
+        let storage = if self.is_mlocked {
+            let (storage, locked) = Storage::locked(&mut encrypted)?;
+            self.release();
+            self.is_mlocked = locked;
+            storage
+        } else {
+            self.release();
+            Storage::Heap(encrypted)
+        };
+        self.storage = storage;
         self.is_crypt = true;
-        self.is_mlocked = true;
         Ok(())
     }
 
+    /// 锁定数据所在的内存, Unix上mlock失败时仍返回成功, 由[`SecureData::is_mlocked`]和
+    /// [`secure_arena::stats`]反映实际状态
     pub fn mlock(&mut self) -> Result<(), SecureDataError> {
-        memory_crypt::mlock(&mut self.data)?;
-        self.is_mlocked = true;
+        if self.is_mlocked {
+            return Ok(());
+        }
+        let Storage::Heap(data) = &mut self.storage else {
+            return Ok(());
+        };
+        let (storage, locked) = Storage::locked(data)?;
+        self.storage = storage;
+        self.is_mlocked = locked;
         Ok(())
     }
 
+    /// 清零当前存储, Windows上还会解锁堆内存
+    fn release(&mut self) {
+        self.storage.as_mut_slice().zeroize();
+        #[cfg(not(unix))]
+        if self.is_mlocked {
+            memory_crypt::munlock(self.storage.as_slice());
+        }
+        self.storage.zeroize();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        self.storage.as_slice().as_ptr()
+    }
+
     pub fn is_mlocked(&self) -> bool {
         self.is_mlocked
     }
@@ -54,14 +160,22 @@ impl SecureData {
 
     pub fn unsecure(&self) -> Result<Zeroizing<Vec<u8>>, SecureDataError> {
         if !self.is_crypt {
-            return Ok(Zeroizing::new(self.data.clone()));
+            return Ok(Zeroizing::new(self.storage.as_slice().to_vec()));
         }
 
-        let data = memory_crypt::uncrypt_memory(&self.data, self.original_len)?;
+        let data = memory_crypt::uncrypt_memory(self.storage.as_slice(), self.original_len)?;
         Ok(Zeroizing::new(data))
     }
 }
 
+impl Drop for SecureData {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+impl ZeroizeOnDrop for SecureData {}
+
 impl fmt::Debug for SecureData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SecureData")
@@ -78,9 +192,10 @@ impl fmt::Display for SecureData {
 }
 
 impl Clone for SecureData {
+    /// 锁定的数据复制到新的内存池slot中, 不会对每份副本单独mlock
     fn clone(&self) -> Self {
         let mut cloned = Self {
-            data: self.data.clone(),
+            storage: Storage::Heap(self.storage.as_slice().to_vec()),
             original_len: self.original_len,
             _marker: PhantomData,
             is_crypt: self.is_crypt,
@@ -94,3 +209,97 @@ impl Clone for SecureData {
         cloned
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::wipe_check;
+
+    #[test]
+    fn test_take_wipes_source() {
+        let mut source = [0x5Au8; 32];
+        let secure = SecureData::take(&mut source);
+        assert_eq!(source, [0u8; 32]);
+        assert_eq!(secure.unsecure().unwrap().as_slice(), &[0x5Au8; 32]);
+    }
+
+    #[test]
+    fn test_wiped_on_drop() {
+        let secure = SecureData::new(&[0xA5u8; 64]);
+        let watch = wipe_check::watch(secure.as_ptr());
+        drop(secure);
+        assert!(watch.wiped_on_free());
+    }
+
+    #[test]
+    fn test_crypt_roundtrip() {
+        let plain = [0xA5u8; 64];
+        let mut secure = SecureData::new(&plain);
+        secure.crypt().unwrap();
+        secure.crypt().unwrap();
+        assert!(!secure.is_mlocked());
+        assert_eq!(secure.len(), plain.len());
+        assert_eq!(secure.unsecure().unwrap().as_slice(), &plain);
+        assert_eq!(secure.clone().unsecure().unwrap().as_slice(), &plain);
+    }
+
+    #[test]
+    fn test_crypt_wipes_plaintext() {
+        let mut secure = SecureData::new(&[0xA5u8; 64]);
+        let watch = wipe_check::watch(secure.as_ptr());
+        secure.crypt().unwrap();
+        assert!(watch.wiped_on_free());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_crypt_unique_nonce_and_authenticated() {
+        let plain = [0xA5u8; 64];
+        let mut a = SecureData::new(&plain);
+        let mut b = SecureData::new(&plain);
+        a.crypt().unwrap();
+        b.crypt().unwrap();
+        assert_ne!(a.storage.as_slice(), b.storage.as_slice());
+        assert!(!a
+            .storage
+            .as_slice()
+            .windows(plain.len())
+            .any(|w| w == plain));
+
+        let last = a.original_len + 24 + 15;
+        a.storage.as_mut_slice()[last] ^= 1;
+        assert!(a.unsecure().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mlock_uses_arena() {
+        let plain = [0x5Au8; 48];
+        let mut secure = SecureData::new(&plain);
+        let watch = wipe_check::watch(secure.as_ptr());
+        secure.mlock().unwrap();
+        assert!(watch.wiped_on_free());
+        drop(watch);
+
+        assert!(
+            matches!(&secure.storage, Storage::Arena(buffer) if buffer.is_locked() == secure.is_mlocked())
+        );
+        let cloned = secure.clone();
+        assert!(matches!(cloned.storage, Storage::Arena(_)));
+        assert_ne!(cloned.as_ptr(), secure.as_ptr());
+        assert_eq!(cloned.unsecure().unwrap().as_slice(), &plain);
+
+        secure.crypt().unwrap();
+        assert!(matches!(secure.storage, Storage::Arena(_)));
+        assert_eq!(secure.unsecure().unwrap().as_slice(), &plain);
+    }
+
+    #[test]
+    fn test_unsecure_copy_wiped_on_drop() {
+        let secure = SecureData::new(&[0xA5u8; 64]);
+        let plain = secure.unsecure().unwrap();
+        let watch = wipe_check::watch(plain.as_ptr());
+        drop(plain);
+        assert!(watch.wiped_on_free());
+    }
+}
@@ -0,0 +1,70 @@
+//! 测试用的全局分配器, 检查被监视的内存块在释放时是否已被清零
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+struct WipeCheckAllocator;
+
+static WATCHED: AtomicUsize = AtomicUsize::new(0);
+static FREED: AtomicBool = AtomicBool::new(false);
+static WIPED: AtomicBool = AtomicBool::new(false);
+/// 同一时间只监视一个内存块
+static WATCH_LOCK: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for WipeCheckAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if !ptr.is_null()
+            && WATCHED
+                .compare_exchange(ptr as usize, 0, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        {
+            // 内存块在交还给系统前仍然有效
+            let bytes = std::slice::from_raw_parts(ptr, layout.size());
+            WIPED.store(bytes.iter().all(|&b| b == 0), Ordering::SeqCst);
+            FREED.store(true, Ordering::SeqCst);
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: WipeCheckAllocator = WipeCheckAllocator;
+
+pub struct Watch {
+    _guard: MutexGuard<'static, ()>,
+}
+
+/// 开始监视`ptr`所在的堆内存块, 它必须是一次分配的起始地址
+pub fn watch(ptr: *const u8) -> Watch {
+    let guard = WATCH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    FREED.store(false, Ordering::SeqCst);
+    WIPED.store(false, Ordering::SeqCst);
+    WATCHED.store(ptr as usize, Ordering::SeqCst);
+    Watch { _guard: guard }
+}
+
+impl Watch {
+    /// 内存块已释放且释放时全部为0
+    pub fn wiped_on_free(&self) -> bool {
+        FREED.load(Ordering::SeqCst) && WIPED.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        WATCHED.store(0, Ordering::SeqCst);
+    }
+}
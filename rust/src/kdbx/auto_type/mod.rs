@@ -0,0 +1,217 @@
+pub mod sequence;
+pub mod window;
+
+use thiserror::Error;
+
+use crate::kdbx::auto_type::sequence::AutoTypeAction;
+use crate::kdbx::auto_type::window::WindowMatcher;
+use crate::kdbx::placeholder::expander::PlaceholderExpander;
+use crate::kdbx::placeholder::{PlaceholderError, PlaceholderMode};
+use crate::kdbx::settings::EffectiveSettings;
+use crate::kdbx::xml::entities::{Entry, Group};
+
+/// KeePass 默认的自动输入序列
+pub const DEFAULT_SEQUENCE: &str = "{USERNAME}{TAB}{PASSWORD}{ENTER}";
+
+#[derive(Debug, Error)]
+pub enum AutoTypeError {
+    #[error("Unknown placeholder: {{{0}}}")]
+    UnknownPlaceholder(String),
+
+    #[error("Unbalanced braces in sequence")]
+    UnbalancedBraces,
+
+    #[error("Unbalanced parentheses in sequence")]
+    UnbalancedParentheses,
+
+    #[error("Invalid argument: {{{0}}}")]
+    InvalidArgument(String),
+
+    #[error("Modifier is not followed by a key")]
+    DanglingModifier,
+
+    #[error("Invalid window regex: {0}")]
+    InvalidWindowRegex(#[from] regex::Error),
+
+    #[error("Placeholder error")]
+    Placeholder(#[from] PlaceholderError),
+}
+
+/// 条目的默认自动输入序列
+///
+/// `path` 为从根组到条目所在组的路径 (见 `Group::find_entry_path`)。
+/// 条目未设置时沿父组向上继承, 均未设置时为 `DEFAULT_SEQUENCE`。
+pub fn effective_sequence(entry: &Entry, path: &[&Group]) -> String {
+    EffectiveSettings::for_entry(entry, path).auto_type_sequence
+}
+
+/// 查找与窗口标题匹配的关联序列, 按条目中的顺序返回
+///
+/// 关联的序列为空时使用条目的默认序列。
+pub fn find_sequences(
+    entry: &Entry,
+    path: &[&Group],
+    window_title: &str,
+) -> Result<Vec<String>, AutoTypeError> {
+    let Some(auto_type) = &entry.auto_type else {
+        return Ok(Vec::new());
+    };
+
+    let mut sequences = Vec::new();
+    for association in &auto_type.association {
+        if !WindowMatcher::parse(&association.window)?.is_match(window_title) {
+            continue;
+        }
+        if association.keystroke_sequence.is_empty() {
+            sequences.push(effective_sequence(entry, path));
+        } else {
+            sequences.push(association.keystroke_sequence.clone());
+        }
+    }
+    Ok(sequences)
+}
+
+/// 展开序列中的占位符并编译为操作列表
+pub fn compile_for_entry(
+    expander: &PlaceholderExpander,
+    sequence: &str,
+    entry: &Entry,
+) -> Result<Vec<AutoTypeAction>, AutoTypeError> {
+    let expander = expander.with_mode(PlaceholderMode::AutoType);
+    let expanded = expander.expand(sequence, entry)?;
+    sequence::compile(&expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::auto_type::sequence::{Key, KeyStroke, Modifiers, SpecialKey};
+    use crate::kdbx::placeholder::SystemEnvironment;
+    use crate::kdbx::xml::database::KeePassDatabase;
+    use crate::kdbx::xml::fixtures;
+
+    fn test_database() -> KeePassDatabase {
+        let login = fixtures::entry_xml(
+            &fixtures::uuid(1),
+            &[("UserName", "alice"), ("Password", "p+ss")],
+            r#"<AutoType>
+                <Enabled>True</Enabled>
+                <DataTransferObfuscation>0</DataTransferObfuscation>
+                <Association>
+                    <Window>*Firefox</Window>
+                    <KeystrokeSequence />
+                </Association>
+                <Association>
+                    <Window>//^Bank - .*//</Window>
+                    <KeystrokeSequence>{PASSWORD}~</KeystrokeSequence>
+                </Association>
+            </AutoType>"#,
+        );
+        let custom = fixtures::entry_xml(
+            &fixtures::uuid(2),
+            &[("UserName", "bob")],
+            r#"<AutoType>
+                <Enabled>True</Enabled>
+                <DataTransferObfuscation>0</DataTransferObfuscation>
+                <DefaultSequence>{USERNAME}{ENTER}</DefaultSequence>
+            </AutoType>"#,
+        );
+        let plain =
+            fixtures::entry_xml(&fixtures::uuid(3), &[("UserName", "carol@example.com")], "");
+        let work =
+            fixtures::group_xml(&fixtures::uuid(11), "Work", "", &format!("{login}{custom}"));
+        let internet = fixtures::group_xml(
+            &fixtures::uuid(12),
+            "Internet",
+            "<DefaultAutoTypeSequence>{USERNAME}{TAB 2}{PASSWORD}</DefaultAutoTypeSequence>",
+            &work,
+        );
+        fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(10),
+            "Root",
+            "",
+            &format!("{internet}{plain}"),
+        ))
+    }
+
+    fn find_entry(root: &Group, n: u8) -> (&Entry, Vec<&Group>) {
+        let uuid = fixtures::uuid_value(n);
+        let entry = root
+            .all_entries()
+            .into_iter()
+            .find(|entry| entry.uuid.value() == &uuid)
+            .unwrap();
+        (entry, root.find_entry_path(&uuid).unwrap())
+    }
+
+    #[test]
+    fn test_effective_sequence() {
+        let database = test_database();
+        let root = &database.document.root.group;
+
+        let (entry, path) = find_entry(root, 1);
+        assert_eq!(
+            effective_sequence(entry, &path),
+            "{USERNAME}{TAB 2}{PASSWORD}"
+        );
+
+        let (entry, path) = find_entry(root, 2);
+        assert_eq!(effective_sequence(entry, &path), "{USERNAME}{ENTER}");
+
+        let (entry, path) = find_entry(root, 3);
+        assert_eq!(effective_sequence(entry, &path), DEFAULT_SEQUENCE);
+    }
+
+    #[test]
+    fn test_find_sequences() {
+        let database = test_database();
+        let (entry, path) = find_entry(&database.document.root.group, 1);
+
+        assert_eq!(
+            find_sequences(entry, &path, "Login - Mozilla Firefox").unwrap(),
+            vec!["{USERNAME}{TAB 2}{PASSWORD}"]
+        );
+        assert_eq!(
+            find_sequences(entry, &path, "bank - online").unwrap(),
+            vec!["{PASSWORD}~"]
+        );
+        assert!(find_sequences(entry, &path, "Terminal").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compile_for_entry() {
+        let database = test_database();
+        let (entry, _) = find_entry(&database.document.root.group, 1);
+        let environment = SystemEnvironment::default();
+        let expander = PlaceholderExpander::new(
+            &database,
+            &crate::kdbx::placeholder::SystemClock,
+            &environment,
+        );
+
+        let actions = compile_for_entry(&expander, "{USERNAME}{TAB}{PASSWORD}~", entry).unwrap();
+        let key = |special_key| {
+            AutoTypeAction::Key(KeyStroke {
+                key: Key::Special(special_key),
+                modifiers: Modifiers::default(),
+            })
+        };
+        assert_eq!(
+            actions,
+            vec![
+                AutoTypeAction::Text("alice".into()),
+                key(SpecialKey::Tab),
+                AutoTypeAction::Text("p+ss".into()),
+                key(SpecialKey::Enter),
+            ]
+        );
+
+        // '@'是Meta修饰键, 字段中的'@'必须按字面输入
+        let (entry, _) = find_entry(&database.document.root.group, 3);
+        let actions = compile_for_entry(&expander, "{USERNAME}", entry).unwrap();
+        assert_eq!(
+            actions,
+            vec![AutoTypeAction::Text("carol@example.com".into())]
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use crate::kdbx::auto_type::AutoTypeError;
+
+/// `{KEY n}`允许的最大重复次数, 序列来自数据库内容, 不能让其耗尽内存
+const MAX_REPEAT: u32 = 1024;
+
+/// 修饰键
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// Windows键 / Command键
+    pub meta: bool,
+}
+
+impl Modifiers {
+    pub fn is_empty(&self) -> bool {
+        !(self.shift || self.ctrl || self.alt || self.meta)
+    }
+
+    fn union(&self, other: &Modifiers) -> Modifiers {
+        Modifiers {
+            shift: self.shift || other.shift,
+            ctrl: self.ctrl || other.ctrl,
+            alt: self.alt || other.alt,
+            meta: self.meta || other.meta,
+        }
+    }
+
+    fn set(&mut self, code: char) -> bool {
+        match code {
+            '+' => self.shift = true,
+            '^' => self.ctrl = true,
+            '%' => self.alt = true,
+            '@' => self.meta = true,
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialKey {
+    Tab,
+    Enter,
+    Space,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    Backspace,
+    Break,
+    CapsLock,
+    Escape,
+    Win,
+    RightWin,
+    Apps,
+    Help,
+    NumLock,
+    PrintScreen,
+    ScrollLock,
+    /// F1 - F24
+    Function(u8),
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    /// 小键盘数字 0 - 9
+    Numpad(u8),
+}
+
+impl SpecialKey {
+    fn from_name(name: &str) -> Option<Self> {
+        let key = match name {
+            "TAB" => SpecialKey::Tab,
+            "ENTER" => SpecialKey::Enter,
+            "SPACE" => SpecialKey::Space,
+            "UP" => SpecialKey::Up,
+            "DOWN" => SpecialKey::Down,
+            "LEFT" => SpecialKey::Left,
+            "RIGHT" => SpecialKey::Right,
+            "HOME" => SpecialKey::Home,
+            "END" => SpecialKey::End,
+            "PGUP" => SpecialKey::PageUp,
+            "PGDN" => SpecialKey::PageDown,
+            "INSERT" | "INS" => SpecialKey::Insert,
+            "DELETE" | "DEL" => SpecialKey::Delete,
+            "BACKSPACE" | "BS" | "BKSP" => SpecialKey::Backspace,
+            "BREAK" => SpecialKey::Break,
+            "CAPSLOCK" => SpecialKey::CapsLock,
+            "ESC" => SpecialKey::Escape,
+            "WIN" | "LWIN" => SpecialKey::Win,
+            "RWIN" => SpecialKey::RightWin,
+            "APPS" => SpecialKey::Apps,
+            "HELP" => SpecialKey::Help,
+            "NUMLOCK" => SpecialKey::NumLock,
+            "PRTSC" => SpecialKey::PrintScreen,
+            "SCROLLLOCK" => SpecialKey::ScrollLock,
+            "ADD" => SpecialKey::Add,
+            "SUBTRACT" => SpecialKey::Subtract,
+            "MULTIPLY" => SpecialKey::Multiply,
+            "DIVIDE" => SpecialKey::Divide,
+            _ => {
+                if let Some(n) = name.strip_prefix("NUMPAD") {
+                    return n.parse().ok().filter(|n| *n <= 9).map(SpecialKey::Numpad);
+                }
+                if let Some(n) = name.strip_prefix('F') {
+                    return n
+                        .parse()
+                        .ok()
+                        .filter(|n| (1..=24).contains(n))
+                        .map(SpecialKey::Function);
+                }
+                return None;
+            }
+        };
+        Some(key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Special(SpecialKey),
+    /// `{VKEY X}`, 平台相关的虚拟键码; `extended`对应 `{VKEY-EX X}` / `{VKEY-NX X}`
+    Virtual {
+        code: u16,
+        extended: Option<bool>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyStroke {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+/// 编译后的自动输入操作, 由平台代码负责实际输入
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AutoTypeAction {
+    /// 不带修饰键的连续文本
+    Text(String),
+    Key(KeyStroke),
+    /// `{DELAY X}`, 暂停X毫秒
+    Delay(u32),
+    /// `{DELAY=X}`, 设置按键之间的默认间隔
+    SetDefaultDelay(u32),
+    /// `{CLEARFIELD}`, 清空当前输入框
+    ClearField,
+}
+
+/// 将自动输入序列编译为操作列表
+///
+/// 序列中的字段占位符应该已经通过 `PlaceholderExpander` 以自动输入模式展开。
+pub fn compile(sequence: &str) -> Result<Vec<AutoTypeAction>, AutoTypeError> {
+    let mut compiler = Compiler::default();
+    let mut rest = sequence;
+
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '+' | '^' | '%' | '@' => {
+                compiler.pending.set(c);
+            }
+            '~' => compiler.key(Key::Special(SpecialKey::Enter)),
+            '(' => {
+                let group = compiler.current_modifiers();
+                compiler.groups.push(group);
+                compiler.pending = Modifiers::default();
+            }
+            ')' => {
+                if compiler.groups.pop().is_none() {
+                    return Err(AutoTypeError::UnbalancedParentheses);
+                }
+            }
+            '{' => {
+                // 跳过第一个字符, 以支持 `{}}`
+                let first = rest.chars().next().ok_or(AutoTypeError::UnbalancedBraces)?;
+                let end = rest[first.len_utf8()..]
+                    .find('}')
+                    .ok_or(AutoTypeError::UnbalancedBraces)?
+                    + first.len_utf8();
+                let content = &rest[..end];
+                rest = &rest[end + 1..];
+                compiler.command(content)?;
+            }
+            '}' => return Err(AutoTypeError::UnbalancedBraces),
+            c => compiler.key(Key::Char(c)),
+        }
+    }
+
+    if !compiler.groups.is_empty() {
+        return Err(AutoTypeError::UnbalancedParentheses);
+    }
+    if !compiler.pending.is_empty() {
+        return Err(AutoTypeError::DanglingModifier);
+    }
+    compiler.flush_text();
+    Ok(compiler.actions)
+}
+
+#[derive(Default)]
+struct Compiler {
+    actions: Vec<AutoTypeAction>,
+    text: String,
+    pending: Modifiers,
+    groups: Vec<Modifiers>,
+}
+
+impl Compiler {
+    fn current_modifiers(&self) -> Modifiers {
+        self.groups
+            .iter()
+            .fold(self.pending, |acc, group| acc.union(group))
+    }
+
+    fn flush_text(&mut self) {
+        if !self.text.is_empty() {
+            self.actions
+                .push(AutoTypeAction::Text(std::mem::take(&mut self.text)));
+        }
+    }
+
+    fn push(&mut self, action: AutoTypeAction) {
+        self.flush_text();
+        self.actions.push(action);
+    }
+
+    fn key(&mut self, key: Key) {
+        let modifiers = self.current_modifiers();
+        self.pending = Modifiers::default();
+        match key {
+            Key::Char(c) if modifiers.is_empty() => self.text.push(c),
+            key => self.push(AutoTypeAction::Key(KeyStroke { key, modifiers })),
+        }
+    }
+
+    /// 处理 `{...}` 中的内容
+    fn command(&mut self, content: &str) -> Result<(), AutoTypeError> {
+        if let Some(value) = content.strip_prefix("DELAY=") {
+            let delay = parse_number(value, content)?;
+            self.push(AutoTypeAction::SetDefaultDelay(delay));
+            return Ok(());
+        }
+
+        let (name, argument) = match content.split_once(' ') {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (content, None),
+        };
+        let upper = name.to_uppercase();
+
+        match upper.as_str() {
+            "DELAY" => {
+                let value =
+                    argument.ok_or_else(|| AutoTypeError::InvalidArgument(content.into()))?;
+                let delay = parse_number(value, content)?;
+                self.push(AutoTypeAction::Delay(delay));
+                return Ok(());
+            }
+            "CLEARFIELD" if argument.is_none() => {
+                self.push(AutoTypeAction::ClearField);
+                return Ok(());
+            }
+            "VKEY" | "VKEY-EX" | "VKEY-NX" => {
+                let value =
+                    argument.ok_or_else(|| AutoTypeError::InvalidArgument(content.into()))?;
+                let code = match value
+                    .strip_prefix("0x")
+                    .or_else(|| value.strip_prefix("0X"))
+                {
+                    Some(hex) => u16::from_str_radix(hex, 16).ok(),
+                    None => value.parse().ok(),
+                }
+                .ok_or_else(|| AutoTypeError::InvalidArgument(content.into()))?;
+                let extended = match upper.as_str() {
+                    "VKEY-EX" => Some(true),
+                    "VKEY-NX" => Some(false),
+                    _ => None,
+                };
+                self.key(Key::Virtual { code, extended });
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let count = match argument {
+            Some(value) => parse_number(value, content)?,
+            None => 1,
+        };
+        if count > MAX_REPEAT {
+            return Err(AutoTypeError::InvalidArgument(content.into()));
+        }
+
+        let mut chars = name.chars();
+        let key = match (chars.next(), chars.next()) {
+            // `{+}`, `{%}`, `{a 3}` 等单字符
+            (Some(c), None) => Key::Char(c),
+            _ => Key::Special(
+                SpecialKey::from_name(&upper)
+                    .ok_or_else(|| AutoTypeError::UnknownPlaceholder(content.into()))?,
+            ),
+        };
+
+        let modifiers = self.current_modifiers();
+        for _ in 0..count {
+            // 修饰键作用于所有重复的按键
+            self.pending = modifiers;
+            self.key(key);
+        }
+        self.pending = Modifiers::default();
+        Ok(())
+    }
+}
+
+fn parse_number(value: &str, content: &str) -> Result<u32, AutoTypeError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| AutoTypeError::InvalidArgument(content.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: Key, modifiers: Modifiers) -> AutoTypeAction {
+        AutoTypeAction::Key(KeyStroke { key, modifiers })
+    }
+
+    fn special(special_key: SpecialKey) -> AutoTypeAction {
+        key(Key::Special(special_key), Modifiers::default())
+    }
+
+    const CTRL: Modifiers = Modifiers {
+        shift: false,
+        ctrl: true,
+        alt: false,
+        meta: false,
+    };
+
+    #[test]
+    fn test_default_sequence() {
+        let actions = compile("alice{TAB}s3cret{ENTER}").unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                AutoTypeAction::Text("alice".into()),
+                special(SpecialKey::Tab),
+                AutoTypeAction::Text("s3cret".into()),
+                special(SpecialKey::Enter),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escaped_characters() {
+        let actions = compile("p{{}a{}}ss{+}{%}{^}{~}{(}{)}").unwrap();
+        assert_eq!(actions, vec![AutoTypeAction::Text("p{a}ss+%^~()".into())]);
+    }
+
+    #[test]
+    fn test_modifiers() {
+        let actions = compile("^a%{F4}+(ab)c").unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                key(Key::Char('a'), CTRL),
+                key(
+                    Key::Special(SpecialKey::Function(4)),
+                    Modifiers {
+                        alt: true,
+                        ..Default::default()
+                    }
+                ),
+                key(
+                    Key::Char('a'),
+                    Modifiers {
+                        shift: true,
+                        ..Default::default()
+                    }
+                ),
+                key(
+                    Key::Char('b'),
+                    Modifiers {
+                        shift: true,
+                        ..Default::default()
+                    }
+                ),
+                AutoTypeAction::Text("c".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_commands() {
+        let actions =
+            compile("{CLEARFIELD}{DELAY=50}x{DELAY 500}{VKEY 0x0D}{VKEY-EX 46}~").unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                AutoTypeAction::ClearField,
+                AutoTypeAction::SetDefaultDelay(50),
+                AutoTypeAction::Text("x".into()),
+                AutoTypeAction::Delay(500),
+                key(
+                    Key::Virtual {
+                        code: 13,
+                        extended: None
+                    },
+                    Modifiers::default()
+                ),
+                key(
+                    Key::Virtual {
+                        code: 46,
+                        extended: Some(true)
+                    },
+                    Modifiers::default()
+                ),
+                special(SpecialKey::Enter),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeat_count() {
+        let actions = compile("{TAB 2}{a 3}^{BS 2}").unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                special(SpecialKey::Tab),
+                special(SpecialKey::Tab),
+                AutoTypeAction::Text("aaa".into()),
+                key(Key::Special(SpecialKey::Backspace), CTRL),
+                key(Key::Special(SpecialKey::Backspace), CTRL),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_sequences() {
+        assert!(matches!(
+            compile("{TAB"),
+            Err(AutoTypeError::UnbalancedBraces)
+        ));
+        assert!(matches!(
+            compile("a}"),
+            Err(AutoTypeError::UnbalancedBraces)
+        ));
+        assert!(matches!(
+            compile("+(ab"),
+            Err(AutoTypeError::UnbalancedParentheses)
+        ));
+        assert!(matches!(
+            compile("ab)"),
+            Err(AutoTypeError::UnbalancedParentheses)
+        ));
+        assert!(matches!(
+            compile("ab^"),
+            Err(AutoTypeError::DanglingModifier)
+        ));
+        assert!(matches!(
+            compile("{USERNAME}"),
+            Err(AutoTypeError::UnknownPlaceholder(_))
+        ));
+        assert!(matches!(
+            compile("{DELAY abc}"),
+            Err(AutoTypeError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            compile("{F25}"),
+            Err(AutoTypeError::UnknownPlaceholder(_))
+        ));
+        assert!(matches!(
+            compile("{a 4000000000}"),
+            Err(AutoTypeError::InvalidArgument(_))
+        ));
+        assert_eq!(compile("{TAB 1024}").unwrap().len(), MAX_REPEAT as usize);
+    }
+}
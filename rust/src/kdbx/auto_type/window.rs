@@ -0,0 +1,91 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::kdbx::auto_type::AutoTypeError;
+
+/// 窗口标题过滤器
+///
+/// 与KeePass一致: 以`//`包围时为正则表达式, 否则为支持`*`和`?`通配符的模式,
+/// 两者均不区分大小写。
+#[derive(Debug, Clone)]
+pub enum WindowMatcher {
+    Wildcard(String),
+    Regex(Regex),
+}
+
+impl WindowMatcher {
+    pub fn parse(filter: &str) -> Result<Self, AutoTypeError> {
+        let filter = filter.trim();
+        if filter.len() > 4 && filter.starts_with("//") && filter.ends_with("//") {
+            let pattern = &filter[2..filter.len() - 2];
+            let regex = RegexBuilder::new(pattern).case_insensitive(true).build()?;
+            return Ok(WindowMatcher::Regex(regex));
+        }
+        Ok(WindowMatcher::Wildcard(filter.to_lowercase()))
+    }
+
+    pub fn is_match(&self, title: &str) -> bool {
+        match self {
+            WindowMatcher::Regex(regex) => regex.is_match(title),
+            WindowMatcher::Wildcard(pattern) => {
+                let pattern: Vec<char> = pattern.chars().collect();
+                let title: Vec<char> = title.to_lowercase().chars().collect();
+                wildcard_match(&pattern, &title)
+            }
+        }
+    }
+}
+
+/// 贪婪回溯匹配 `*` 和 `?`
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard() {
+        let matcher = WindowMatcher::parse("*Mozilla Firefox").unwrap();
+        assert!(matcher.is_match("Login - mozilla firefox"));
+        assert!(!matcher.is_match("Login - Mozilla Firefox Private"));
+
+        let matcher = WindowMatcher::parse("Log?n*").unwrap();
+        assert!(matcher.is_match("Login page"));
+        assert!(matcher.is_match("Logon"));
+        assert!(!matcher.is_match("Log in"));
+
+        assert!(WindowMatcher::parse("Notepad").unwrap().is_match("notepad"));
+        assert!(!WindowMatcher::parse("Notepad")
+            .unwrap()
+            .is_match("Notepad++"));
+        assert!(WindowMatcher::parse("*").unwrap().is_match(""));
+    }
+
+    #[test]
+    fn test_regex() {
+        let matcher = WindowMatcher::parse(r"//^(Login|Sign in) - .*\d$//").unwrap();
+        assert!(matcher.is_match("sign in - Example 2"));
+        assert!(!matcher.is_match("Sign in - Example"));
+        assert!(WindowMatcher::parse("//(//").is_err());
+    }
+}
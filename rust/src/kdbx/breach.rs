@@ -0,0 +1,369 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::kdbx::health::is_excluded;
+use crate::kdbx::xml::database::KeePassDatabase;
+use crate::kdbx::xml::entities::FIELD_PASSWORD;
+use crate::kdbx::xml::errors::KdbxDatabaseError;
+
+/// SHA-1 的十六进制长度
+const HASH_HEX_LEN: usize = 40;
+/// k-匿名范围文件名使用的哈希前缀长度
+const RANGE_PREFIX_LEN: usize = 5;
+
+#[derive(Debug, Error)]
+pub enum BreachError {
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid line in breach data: {0}")]
+    InvalidLine(String),
+
+    #[error("Database error")]
+    DatabaseError(#[from] KdbxDatabaseError),
+}
+
+/// 本地的 Have I Been Pwned 密码数据集, 所有查询都在本地完成
+///
+/// <https://haveibeenpwned.com/Passwords>
+pub trait BreachSource {
+    /// 返回SHA-1哈希在数据集中出现的次数, 未泄露时返回0
+    fn breach_count(&self, sha1: &[u8; 20]) -> Result<u64, BreachError>;
+}
+
+/// 大写的十六进制哈希
+fn hash_hex(sha1: &[u8; 20]) -> Zeroizing<[u8; HASH_HEX_LEN]> {
+    let mut hex = Zeroizing::new([0u8; HASH_HEX_LEN]);
+    hex::encode_to_slice(sha1, hex.as_mut()).expect("buffer has the exact length");
+    hex.make_ascii_uppercase();
+    hex
+}
+
+/// 解析 `HASH:COUNT` 格式的行, 返回哈希部分和次数
+fn parse_line(line: &[u8]) -> Result<(&[u8], u64), BreachError> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let invalid = || BreachError::InvalidLine(String::from_utf8_lossy(line).into_owned());
+    let separator = line.iter().position(|b| *b == b':').ok_or_else(invalid)?;
+    let count = std::str::from_utf8(&line[separator + 1..])
+        .ok()
+        .and_then(|count| count.trim().parse().ok())
+        .ok_or_else(invalid)?;
+    Ok((&line[..separator], count))
+}
+
+fn compare_hex(a: &[u8], b: &[u8]) -> Ordering {
+    a.iter()
+        .map(u8::to_ascii_uppercase)
+        .cmp(b.iter().map(u8::to_ascii_uppercase))
+}
+
+/// 按哈希排序的完整数据集, 例如 `pwned-passwords-sha1-ordered-by-hash-v8.txt`
+///
+/// 文件通过内存映射打开, 使用二分查找。
+pub struct SortedHashFile {
+    data: Mmap,
+}
+
+impl SortedHashFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BreachError> {
+        let file = File::open(path)?;
+        // SAFETY: 数据集文件在使用期间不应被修改, 与其他只读映射的用法相同
+        let data = unsafe { Mmap::map(&file)? };
+        Ok(Self { data })
+    }
+
+    /// `position` 所在行的起始位置
+    fn line_start(&self, position: usize) -> usize {
+        self.data[..position]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1)
+    }
+
+    /// 从 `start` 开始的行的结束位置 (不包含换行符)
+    fn line_end(&self, start: usize) -> usize {
+        self.data[start..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(self.data.len(), |i| start + i)
+    }
+}
+
+impl BreachSource for SortedHashFile {
+    fn breach_count(&self, sha1: &[u8; 20]) -> Result<u64, BreachError> {
+        let target = hash_hex(sha1);
+        // 不变量: `low` 和 `high` 都是行的起始位置
+        let (mut low, mut high) = (0, self.data.len());
+        while low < high {
+            let start = self.line_start(low + (high - low) / 2);
+            let end = self.line_end(start);
+            let line = &self.data[start..end];
+            if line.strip_suffix(b"\r").unwrap_or(line).is_empty() {
+                // 文件末尾的空行
+                high = start;
+                continue;
+            }
+            let (hash, count) = parse_line(line)?;
+            match compare_hex(hash, target.as_ref()) {
+                Ordering::Equal => return Ok(count),
+                Ordering::Less => low = end + 1,
+                Ordering::Greater => high = start,
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// k-匿名范围文件目录, 每个文件以5位哈希前缀命名 (例如 `21BD1.txt`),
+/// 内容为 `SUFFIX:COUNT` 格式的行, 与 Pwned Passwords 范围API的响应相同
+pub struct RangeDirectory {
+    root: PathBuf,
+}
+
+impl RangeDirectory {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn read_range(&self, prefix: &str) -> Result<Option<Vec<u8>>, BreachError> {
+        for name in [format!("{prefix}.txt"), prefix.to_string()] {
+            match std::fs::read(self.root.join(name)) {
+                Ok(data) => return Ok(Some(data)),
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl BreachSource for RangeDirectory {
+    fn breach_count(&self, sha1: &[u8; 20]) -> Result<u64, BreachError> {
+        let target = hash_hex(sha1);
+        let (prefix, suffix) = target.split_at(RANGE_PREFIX_LEN);
+        let prefix = std::str::from_utf8(prefix).expect("hex is ASCII");
+        let Some(data) = self.read_range(prefix)? else {
+            return Ok(0);
+        };
+        for line in data.split(|b| *b == b'\n') {
+            if line.strip_suffix(b"\r").unwrap_or(line).is_empty() {
+                continue;
+            }
+            let (hash, count) = parse_line(line)?;
+            if compare_hex(hash, suffix) == Ordering::Equal {
+                return Ok(count);
+            }
+        }
+        Ok(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryBreach {
+    pub uuid: Uuid,
+    /// 密码在泄露数据中出现的次数
+    pub count: u64,
+}
+
+/// 检查数据库中所有条目的密码是否出现在泄露数据中, 只返回已泄露的条目
+///
+/// 与健康报告一致, 不检查回收站、历史记录和被排除的条目。
+pub fn check_database(
+    database: &KeePassDatabase,
+    source: &dyn BreachSource,
+) -> Result<Vec<EntryBreach>, BreachError> {
+    let mut breaches = Vec::new();
+    for entry in database.active_entries() {
+        if is_excluded(entry) {
+            continue;
+        }
+        let Some(value) = entry.get_value(FIELD_PASSWORD) else {
+            continue;
+        };
+        let password = Zeroizing::new(database.get_value_string(value)?);
+        if password.is_empty() {
+            continue;
+        }
+        let sha1 = Zeroizing::new(<[u8; 20]>::from(Sha1::digest(password.as_bytes())));
+        let count = source.breach_count(&sha1)?;
+        if count > 0 {
+            breaches.push(EntryBreach {
+                uuid: *entry.uuid.value(),
+                count,
+            });
+        }
+    }
+    Ok(breaches)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::kdbx::xml::fixtures::{self, uuid_value};
+
+    const BREACHED: &[(&str, u64)] = &[
+        ("password", 9_545_824),
+        ("123456", 37_359_195),
+        ("hunter2", 24_230),
+    ];
+
+    fn sha1(password: &str) -> [u8; 20] {
+        Sha1::digest(password.as_bytes()).into()
+    }
+
+    /// 生成包含泄露密码和填充哈希的排序数据集
+    fn dataset() -> Vec<(String, u64)> {
+        let mut lines: Vec<(String, u64)> = BREACHED
+            .iter()
+            .map(|(password, count)| (hex::encode_upper(sha1(password)), *count))
+            .collect();
+        for i in 0..200u32 {
+            lines.push((
+                hex::encode_upper(sha1(&format!("filler-{i}"))),
+                u64::from(i) + 1,
+            ));
+        }
+        // 覆盖文件开头和结尾
+        lines.push(("0".repeat(40), 1));
+        lines.push(("F".repeat(40), 2));
+        lines.sort();
+        lines
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "keepass-one-{name}-{}-{}",
+                std::process::id(),
+                Uuid::new_v4()
+            ));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn write_sorted_file(dir: &TempDir, line_ending: &str) -> PathBuf {
+        let path = dir.0.join("pwned-passwords-sha1-ordered-by-hash.txt");
+        let content: String = dataset()
+            .iter()
+            .map(|(hash, count)| format!("{hash}:{count}{line_ending}"))
+            .collect();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn write_range_directory(dir: &TempDir) {
+        let mut ranges: BTreeMap<String, String> = BTreeMap::new();
+        for (hash, count) in dataset() {
+            let (prefix, suffix) = hash.split_at(5);
+            ranges
+                .entry(prefix.to_string())
+                .or_default()
+                .push_str(&format!("{suffix}:{count}\r\n"));
+        }
+        for (prefix, content) in ranges {
+            std::fs::write(dir.0.join(format!("{prefix}.txt")), content).unwrap();
+        }
+    }
+
+    fn assert_lookups(source: &dyn BreachSource) {
+        for (password, count) in BREACHED {
+            assert_eq!(source.breach_count(&sha1(password)).unwrap(), *count);
+        }
+        assert_eq!(source.breach_count(&sha1("filler-0")).unwrap(), 1);
+        assert_eq!(source.breach_count(&sha1("filler-199")).unwrap(), 200);
+        assert_eq!(source.breach_count(&[0; 20]).unwrap(), 1);
+        assert_eq!(source.breach_count(&[0xff; 20]).unwrap(), 2);
+        assert_eq!(source.breach_count(&sha1("not breached")).unwrap(), 0);
+        assert_eq!(source.breach_count(&[0x80; 20]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sorted_hash_file() {
+        let dir = TempDir::new("sorted");
+        for line_ending in ["\n", "\r\n"] {
+            let file = SortedHashFile::open(write_sorted_file(&dir, line_ending)).unwrap();
+            assert_lookups(&file);
+        }
+    }
+
+    #[test]
+    fn test_range_directory() {
+        let dir = TempDir::new("ranges");
+        write_range_directory(&dir);
+        assert_lookups(&RangeDirectory::new(&dir.0));
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        let dir = TempDir::new("invalid");
+        let path = dir.0.join("broken.txt");
+        std::fs::write(&path, "not a hash line\n").unwrap();
+        let file = SortedHashFile::open(&path).unwrap();
+        assert!(matches!(
+            file.breach_count(&[0; 20]),
+            Err(BreachError::InvalidLine(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_database() {
+        let dir = TempDir::new("database");
+        let file = SortedHashFile::open(write_sorted_file(&dir, "\n")).unwrap();
+
+        let entries = [
+            fixtures::entry_xml(&fixtures::uuid(1), &[("Password", "password")], ""),
+            fixtures::entry_xml(
+                &fixtures::uuid(2),
+                &[("Password", "rWibMFACxAUGZmxhVncy")],
+                "",
+            ),
+            fixtures::entry_xml(&fixtures::uuid(3), &[("Password", "hunter2")], ""),
+            fixtures::entry_xml(
+                &fixtures::uuid(4),
+                &[("Password", "123456")],
+                "<QualityCheck>False</QualityCheck>",
+            ),
+            fixtures::entry_xml(&fixtures::uuid(5), &[], ""),
+        ];
+        let database = fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(10),
+            "Root",
+            "",
+            &entries.concat(),
+        ));
+
+        let breaches = check_database(&database, &file).unwrap();
+        assert_eq!(
+            breaches,
+            vec![
+                EntryBreach {
+                    uuid: uuid_value(1),
+                    count: 9_545_824
+                },
+                EntryBreach {
+                    uuid: uuid_value(3),
+                    count: 24_230
+                },
+            ]
+        );
+    }
+}
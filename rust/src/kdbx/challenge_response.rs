@@ -0,0 +1,95 @@
+//! 挑战-响应密钥 (兼容KeePassXC的YubiKey HMAC-SHA1)
+//!
+//! KDBX4中以KDF的种子作为挑战, 所有响应的SHA-256与密码、密钥文件的哈希一同参与复合密钥的计算,
+//! 与KeePassXC的组合方式一致. 硬件通信由平台代码实现本trait完成.
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// YubiKey挑战的固定长度
+pub const YUBIKEY_CHALLENGE_SIZE: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum ChallengeResponseError {
+    #[error("Challenge-response device not found")]
+    DeviceNotFound,
+
+    #[error("Challenge-response was cancelled or timed out")]
+    Cancelled,
+
+    #[error("Challenge-response failed: {0}")]
+    Failed(String),
+}
+
+/// 挑战-响应密钥的提供者
+pub trait ChallengeResponseProvider: Send + Sync {
+    /// 对挑战计算响应, YubiKey HMAC-SHA1模式下响应为20字节
+    fn challenge(&self, challenge: &[u8]) -> Result<Zeroizing<Vec<u8>>, ChallengeResponseError>;
+}
+
+/// 按KeePassXC的方式以PKCS#7填充到64字节, 供平台代码发送给YubiKey
+///
+/// 可变长度模式的YubiKey会去除尾部填充, 因此响应等同于直接对原挑战计算HMAC
+pub fn pad_yubikey_challenge(
+    challenge: &[u8],
+) -> Result<Zeroizing<[u8; YUBIKEY_CHALLENGE_SIZE]>, ChallengeResponseError> {
+    if challenge.len() >= YUBIKEY_CHALLENGE_SIZE {
+        return Err(ChallengeResponseError::Failed(format!(
+            "challenge must be shorter than {} bytes",
+            YUBIKEY_CHALLENGE_SIZE
+        )));
+    }
+    let pad = (YUBIKEY_CHALLENGE_SIZE - challenge.len()) as u8;
+    let mut padded = Zeroizing::new([pad; YUBIKEY_CHALLENGE_SIZE]);
+    padded[..challenge.len()].copy_from_slice(challenge);
+    Ok(padded)
+}
+
+/// 以固定密钥模拟YubiKey可变长度HMAC-SHA1槽位的软件实现
+pub struct SoftwareChallengeResponse {
+    secret: Zeroizing<Vec<u8>>,
+}
+
+impl SoftwareChallengeResponse {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: Zeroizing::new(secret.to_vec()),
+        }
+    }
+}
+
+impl ChallengeResponseProvider for SoftwareChallengeResponse {
+    fn challenge(&self, challenge: &[u8]) -> Result<Zeroizing<Vec<u8>>, ChallengeResponseError> {
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.secret)
+            .map_err(|e| ChallengeResponseError::Failed(e.to_string()))?;
+        mac.update(challenge);
+        Ok(Zeroizing::new(mac.finalize().into_bytes().to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn test_software_challenge_response() {
+        // RFC 2202 测试向量2
+        let provider = SoftwareChallengeResponse::new(b"Jefe");
+        let response = provider.challenge(b"what do ya want for nothing?").unwrap();
+        assert_eq!(
+            response.as_slice(),
+            hex!("effcdf6ae5eb2fa2d27416d5f184df9c259a7c79")
+        );
+    }
+
+    #[test]
+    fn test_pad_yubikey_challenge() {
+        let padded = pad_yubikey_challenge(&[1u8; 32]).unwrap();
+        assert_eq!(&padded[..32], &[1u8; 32]);
+        assert!(padded[32..].iter().all(|&b| b == 32));
+        assert!(pad_yubikey_challenge(&[0u8; 64]).is_err());
+    }
+}
@@ -1,4 +1,20 @@
+use crate::crypto::memory_crypt::SecureDataError;
+use crate::crypto::secure_data::SecureData;
+
 pub struct MemoryProtectConfig {
     pub enable_memory_crypt: bool,
     pub enable_mlock: bool,
 }
+
+impl MemoryProtectConfig {
+    /// 按配置加密或锁定SecureData所在的内存
+    pub fn protect(&self, data: &mut SecureData) -> Result<(), SecureDataError> {
+        if self.enable_memory_crypt {
+            data.crypt()?;
+        }
+        if self.enable_mlock {
+            data.mlock()?;
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,14 @@
+use crate::kdbx::db::kdbx4::errors::Kdbx4ConfigError;
 use crate::kdbx::db::kdbx4::header_entity::{
     compression::CompressionConfig, encryption_algorithm::EncryptionAlgorithm,
     kdf_config::KdfConfig,
 };
 
+/// Argon2每个lane至少需要的内存(KiB)
+const ARGON2_MIN_MEMORY_PER_LANE: u64 = 8;
+const ARGON2_MIN_SALT_SIZE: usize = 8;
+const ARGON2_MAX_PARALLELISM: u32 = 0xFF_FFFF;
+
 #[derive(Debug, Clone)]
 pub struct Kdbx4Config {
     pub encryption_algorithm: EncryptionAlgorithm,
@@ -12,6 +18,14 @@ pub struct Kdbx4Config {
     pub kdf_parameters: KdfConfig,
 }
 
+/// 更换主密钥时一并修改的设置, 为`None`的项沿用当前配置
+#[derive(Debug, Clone, Default)]
+pub struct MasterKeyChange {
+    pub encryption_algorithm: Option<EncryptionAlgorithm>,
+    pub compression_config: Option<CompressionConfig>,
+    pub kdf_parameters: Option<KdfConfig>,
+}
+
 impl Kdbx4Config {
     pub fn rekey(&self) -> Result<Self, std::io::Error> {
         let mut master_salt_seed = [0; 32];
@@ -25,4 +39,71 @@ impl Kdbx4Config {
             kdf_parameters: self.kdf_parameters.rekey()?,
         })
     }
+
+    /// 应用修改后刷新所有盐值与IV, 并检查组合是否有效
+    pub fn with_change(&self, change: MasterKeyChange) -> Result<Self, Kdbx4ConfigError> {
+        let config = Self {
+            encryption_algorithm: change
+                .encryption_algorithm
+                .unwrap_or_else(|| self.encryption_algorithm.clone()),
+            compression_config: change
+                .compression_config
+                .unwrap_or_else(|| self.compression_config.clone()),
+            kdf_parameters: change
+                .kdf_parameters
+                .unwrap_or_else(|| self.kdf_parameters.clone()),
+            ..self.clone()
+        }
+        .rekey()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 检查IV长度与KDF参数是否能被加密算法和KDF实现接受
+    pub fn validate(&self) -> Result<(), Kdbx4ConfigError> {
+        let expected = self.encryption_algorithm.iv_size();
+        if self.encryption_iv.len() != expected {
+            return Err(Kdbx4ConfigError::InvalidIvLength {
+                expected,
+                actual: self.encryption_iv.len(),
+            });
+        }
+        match &self.kdf_parameters {
+            KdfConfig::Aes { rounds, .. } => {
+                if *rounds == 0 {
+                    return Err(Kdbx4ConfigError::InvalidAesRounds);
+                }
+            }
+            KdfConfig::Argon2 {
+                version,
+                salt,
+                iterations,
+                memory,
+                parallelism,
+                ..
+            } => {
+                let invalid = Kdbx4ConfigError::InvalidArgon2Parameters;
+                if argon2::Version::from_u32(*version).is_err() {
+                    return Err(invalid("unsupported version"));
+                }
+                if salt.len() < ARGON2_MIN_SALT_SIZE {
+                    return Err(invalid("salt is too short"));
+                }
+                if *iterations == 0 || *iterations > u32::MAX as u64 {
+                    return Err(invalid("iterations out of range"));
+                }
+                if *parallelism == 0 || *parallelism > ARGON2_MAX_PARALLELISM {
+                    return Err(invalid("parallelism out of range"));
+                }
+                let memory_kib = *memory / 1024;
+                if *memory % 1024 != 0 || memory_kib > u32::MAX as u64 {
+                    return Err(invalid("memory must be a whole number of KiB"));
+                }
+                if memory_kib < ARGON2_MIN_MEMORY_PER_LANE * *parallelism as u64 {
+                    return Err(invalid("memory is too small for the parallelism"));
+                }
+            }
+        }
+        Ok(())
+    }
 }
@@ -2,8 +2,11 @@ use thiserror::Error;
 
 use crate::crypto::errors::CryptoError;
 use crate::crypto::kdf::KdfError;
+use crate::crypto::memory_crypt::SecureDataError;
 use crate::kdbx::db::kdbx4::header_entity::kdf_config::KdfConfigError;
 use crate::kdbx::db::kdbx4::header_entity::variant_dictionary::VariantDictionaryError;
+use crate::kdbx::keys::KdbxKeyError;
+use crate::kdbx::quick_unlock::QuickUnlockError;
 use crate::kdbx::xml::errors::KdbxDatabaseError;
 
 #[derive(Debug, Error)]
@@ -30,6 +33,21 @@ pub enum Kdbx4HeaderError {
     MissingRequiredHeaderFields(&'static str),
 }
 
+#[derive(Debug, Error)]
+pub enum Kdbx4ConfigError {
+    #[error("Invalid encryption IV length: expected {expected}, got {actual}")]
+    InvalidIvLength { expected: usize, actual: usize },
+
+    #[error("AES-KDF rounds must not be zero")]
+    InvalidAesRounds,
+
+    #[error("Invalid Argon2 parameters: {0}")]
+    InvalidArgon2Parameters(&'static str),
+
+    #[error("Failed to generate random data")]
+    RandomError(#[from] std::io::Error),
+}
+
 #[derive(Debug, Error)]
 pub enum Kdbx4InnerHeaderError {
     #[error("Unknown inner header type: {0}")]
@@ -76,4 +94,13 @@ pub enum Kdbx4Error {
 
     #[error("XML parse error")]
     DatabaseError(#[from] KdbxDatabaseError),
+
+    #[error("Key error")]
+    KeyError(#[from] KdbxKeyError),
+
+    #[error("Secure data error")]
+    SecureDataError(#[from] SecureDataError),
+
+    #[error("Quick unlock error")]
+    QuickUnlockError(#[from] QuickUnlockError),
 }
@@ -25,6 +25,14 @@ pub struct Kdbx4Header {
 }
 
 impl Kdbx4Header {
+    pub fn new(config: Kdbx4Config) -> Self {
+        Self {
+            config,
+            public_custom_data: None,
+            unknown_header: HashMap::new(),
+        }
+    }
+
     pub fn copy_with(&self, config: Kdbx4Config) -> Self {
         Self {
             config,
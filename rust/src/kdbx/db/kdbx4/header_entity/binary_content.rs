@@ -1,8 +1,9 @@
 use crate::utils::writer::{FixedSize, Writable};
 use byteorder::WriteBytesExt;
 use std::io::{Seek, Write};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-#[derive(Clone)]
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
 pub struct BinaryContent {
     pub flag: u8,
     pub content: Vec<u8>,
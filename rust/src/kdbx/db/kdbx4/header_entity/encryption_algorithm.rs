@@ -49,12 +49,16 @@ impl EncryptionAlgorithm {
         }
     }
 
+    pub fn iv_size(&self) -> usize {
+        match self {
+            EncryptionAlgorithm::Aes256 => 16,
+            EncryptionAlgorithm::ChaCha20 => 12,
+            EncryptionAlgorithm::Twofish => 16,
+        }
+    }
+
     pub fn get_random_iv(&self) -> Result<Vec<u8>, std::io::Error> {
-        let mut iv = match self {
-            EncryptionAlgorithm::Aes256 => vec![0; 16],
-            EncryptionAlgorithm::ChaCha20 => vec![0; 12],
-            EncryptionAlgorithm::Twofish => vec![0; 16],
-        };
+        let mut iv = vec![0; self.iv_size()];
         getrandom::fill(&mut iv)?;
         Ok(iv)
     }
@@ -94,11 +94,20 @@ impl KdfConfig {
                     memory: *memory,
                     parallelism: *parallelism,
                     variant: *variant,
+                    secret: Default::default(),
                 })
             }
         }
     }
 
+    /// KDF的种子, 同时作为挑战-响应密钥的挑战
+    pub fn seed(&self) -> &[u8] {
+        match self {
+            KdfConfig::Aes { salt, .. } => salt,
+            KdfConfig::Argon2 { salt, .. } => salt,
+        }
+    }
+
     pub fn rekey(&self) -> Result<Self, std::io::Error> {
         match self {
             KdfConfig::Aes { rounds, .. } => {
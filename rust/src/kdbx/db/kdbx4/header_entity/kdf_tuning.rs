@@ -0,0 +1,269 @@
+//! 根据本机的基准测试结果计算KDF参数, 使解锁耗时接近目标时间
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::crypto::kdf::{AesKdf, Argon2Kdf, KdfError};
+use crate::kdbx::db::kdbx4::header_entity::kdf_config::KdfConfig;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Argon2允许调低到的最小内存
+pub const MIN_ARGON2_MEMORY: u64 = MIB;
+const MIN_AES_ROUNDS: u64 = 1000;
+const SALT_SIZE: usize = 32;
+
+/// AES基准测试的采样时长上限
+const MAX_AES_SAMPLE: Duration = Duration::from_millis(250);
+const MIN_AES_SAMPLE: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Error)]
+pub enum KdfTuningError {
+    #[error("KDF benchmark failed")]
+    KdfError(#[from] KdfError),
+
+    #[error("Failed to generate salt")]
+    RandomError(#[from] getrandom::Error),
+
+    #[error("Invalid tuning parameters")]
+    InvalidParameters,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfAlgorithm {
+    Aes,
+    Argon2d,
+    Argon2id,
+}
+
+/// 自动调整KDF参数的选项
+#[derive(Debug, Clone, PartialEq)]
+pub struct KdfTuning {
+    pub algorithm: KdfAlgorithm,
+    /// 期望的解锁耗时
+    pub target: Duration,
+    /// Argon2期望使用的内存(字节)
+    pub memory: u64,
+    /// Argon2内存上限(字节), 单次迭代超过目标时间时内存会逐步减半, 但不低于[`MIN_ARGON2_MEMORY`]
+    pub max_memory: u64,
+    pub parallelism: u32,
+}
+
+impl KdfTuning {
+    pub fn new(algorithm: KdfAlgorithm, target: Duration) -> Self {
+        Self {
+            algorithm,
+            target,
+            memory: 64 * MIB,
+            max_memory: 64 * MIB,
+            parallelism: 2,
+        }
+    }
+
+    /// 在本机测量并返回带有新盐值的`KdfConfig`
+    pub fn tune(&self) -> Result<KdfConfig, KdfTuningError> {
+        if self.target.is_zero() || self.parallelism == 0 {
+            return Err(KdfTuningError::InvalidParameters);
+        }
+        match self.algorithm {
+            KdfAlgorithm::Aes => self.tune_aes(),
+            KdfAlgorithm::Argon2d => self.tune_argon2(argon2::Variant::Argon2d),
+            KdfAlgorithm::Argon2id => self.tune_argon2(argon2::Variant::Argon2id),
+        }
+    }
+
+    fn tune_aes(&self) -> Result<KdfConfig, KdfTuningError> {
+        let sample = (self.target / 4).clamp(MIN_AES_SAMPLE, MAX_AES_SAMPLE);
+        let per_second = AesKdf::benchmark(sample)?;
+        let rounds = (per_second as f64 * self.target.as_secs_f64()) as u64;
+
+        let mut salt = [0u8; SALT_SIZE];
+        getrandom::fill(&mut salt)?;
+        Ok(KdfConfig::Aes {
+            salt,
+            rounds: rounds.max(MIN_AES_ROUNDS),
+        })
+    }
+
+    fn tune_argon2(&self, variant: argon2::Variant) -> Result<KdfConfig, KdfTuningError> {
+        let mut salt = vec![0u8; SALT_SIZE];
+        getrandom::fill(&mut salt)?;
+
+        // Argon2要求每个lane至少8KiB
+        let floor = MIN_ARGON2_MEMORY.max(8 * 1024 * self.parallelism as u64);
+        let mut kdf = Argon2Kdf {
+            memory: self.memory.min(self.max_memory).max(floor),
+            salt: salt.clone(),
+            iterations: 1,
+            parallelism: self.parallelism,
+            version: argon2::Version::Version13,
+            variant,
+            secret: Default::default(),
+        };
+        let mut elapsed = kdf.benchmark()?;
+        while elapsed > self.target && kdf.memory > floor {
+            kdf.memory = (kdf.memory / 2).max(floor);
+            elapsed = kdf.benchmark()?;
+        }
+        let iterations =
+            (self.target.as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON)) as u64;
+
+        Ok(KdfConfig::Argon2 {
+            version: kdf.version.as_u32(),
+            salt,
+            iterations: iterations.max(1),
+            memory: kdf.memory,
+            parallelism: kdf.parallelism,
+            variant,
+        })
+    }
+}
+
+/// 设置界面与新建数据库时提供的预设
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfPreset {
+    Fast,
+    Balanced,
+    Paranoid,
+}
+
+impl KdfPreset {
+    /// 预设的目标解锁耗时
+    pub fn target(&self) -> Duration {
+        match self {
+            KdfPreset::Fast => Duration::from_millis(300),
+            KdfPreset::Balanced => Duration::from_secs(1),
+            KdfPreset::Paranoid => Duration::from_secs(3),
+        }
+    }
+
+    /// 预设的Argon2内存(字节)
+    pub fn memory(&self) -> u64 {
+        match self {
+            KdfPreset::Fast => 16 * MIB,
+            KdfPreset::Balanced => 64 * MIB,
+            KdfPreset::Paranoid => 256 * MIB,
+        }
+    }
+
+    fn iterations(&self) -> u64 {
+        match self {
+            KdfPreset::Fast => 2,
+            KdfPreset::Balanced => 3,
+            KdfPreset::Paranoid => 8,
+        }
+    }
+
+    fn parallelism(&self) -> u32 {
+        match self {
+            KdfPreset::Fast | KdfPreset::Balanced => 2,
+            KdfPreset::Paranoid => 4,
+        }
+    }
+
+    /// 不经测量的固定参数, 使用Argon2id和新盐值
+    pub fn config(&self) -> Result<KdfConfig, KdfTuningError> {
+        let mut salt = vec![0u8; SALT_SIZE];
+        getrandom::fill(&mut salt)?;
+        Ok(KdfConfig::Argon2 {
+            version: argon2::Version::Version13.as_u32(),
+            salt,
+            iterations: self.iterations(),
+            memory: self.memory(),
+            parallelism: self.parallelism(),
+            variant: argon2::Variant::Argon2id,
+        })
+    }
+
+    /// 以预设的目标时间调整参数, 内存不超过`max_memory`
+    pub fn tuning(&self, max_memory: u64) -> KdfTuning {
+        KdfTuning {
+            algorithm: KdfAlgorithm::Argon2id,
+            target: self.target(),
+            memory: self.memory().min(max_memory),
+            max_memory,
+            parallelism: self.parallelism(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tune_aes() {
+        let tuning = KdfTuning::new(KdfAlgorithm::Aes, Duration::from_millis(40));
+        let a = tuning.tune().unwrap();
+        let b = tuning.tune().unwrap();
+        match (&a, &b) {
+            (
+                KdfConfig::Aes { salt, rounds },
+                KdfConfig::Aes {
+                    salt: other_salt, ..
+                },
+            ) => {
+                assert!(*rounds >= MIN_AES_ROUNDS);
+                assert_ne!(salt, other_salt);
+            }
+            _ => panic!("expected AES config"),
+        }
+    }
+
+    #[test]
+    fn test_tune_argon2_respects_memory_limit() {
+        let mut tuning = KdfTuning::new(KdfAlgorithm::Argon2id, Duration::from_millis(50));
+        tuning.memory = 4 * MIB;
+        tuning.max_memory = 2 * MIB;
+        match tuning.tune().unwrap() {
+            KdfConfig::Argon2 {
+                memory,
+                iterations,
+                variant,
+                salt,
+                ..
+            } => {
+                assert!((MIN_ARGON2_MEMORY..=2 * MIB).contains(&memory));
+                assert!(iterations >= 1);
+                assert_eq!(variant, argon2::Variant::Argon2id);
+                assert_eq!(salt.len(), SALT_SIZE);
+            }
+            _ => panic!("expected Argon2 config"),
+        }
+    }
+
+    #[test]
+    fn test_tune_invalid_parameters() {
+        let tuning = KdfTuning::new(KdfAlgorithm::Aes, Duration::ZERO);
+        assert!(matches!(
+            tuning.tune(),
+            Err(KdfTuningError::InvalidParameters)
+        ));
+    }
+
+    #[test]
+    fn test_presets() {
+        let fast = KdfPreset::Fast.config().unwrap();
+        let paranoid = KdfPreset::Paranoid.config().unwrap();
+        assert_ne!(fast, KdfPreset::Fast.config().unwrap());
+        match (fast, paranoid) {
+            (
+                KdfConfig::Argon2 {
+                    memory: m1,
+                    iterations: i1,
+                    ..
+                },
+                KdfConfig::Argon2 {
+                    memory: m2,
+                    iterations: i2,
+                    ..
+                },
+            ) => assert!(m1 < m2 && i1 < i2),
+            _ => panic!("expected Argon2 config"),
+        }
+        let tuning = KdfPreset::Paranoid.tuning(32 * MIB);
+        assert_eq!(tuning.memory, 32 * MIB);
+        assert_eq!(tuning.target, Duration::from_secs(3));
+    }
+}
@@ -1,6 +1,7 @@
 pub mod compression;
 pub mod encryption_algorithm;
 pub mod kdf_config;
+pub mod kdf_tuning;
 pub mod variant_dictionary;
 pub mod inner_encryption_algorithm;
 pub mod binary_content;
\ No newline at end of file
@@ -1,19 +1,16 @@
 use byteorder::{ByteOrder, LE};
-use generic_array::typenum::U64;
-use generic_array::GenericArray;
 use hex_literal::hex;
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::crypto::errors::CryptoError;
 use crate::crypto::hash;
+use crate::crypto::secure_data::SecureData;
 
 const HMAC_BLOCK_SIZE: usize = 1024 * 1024; // 1MB
 const KDBX4_MAIN_HMAC_SUFFIX: [u8; 1] = hex!("01");
 const KDBX4_HEADER_HMAC_SUFFIX: [u8; 8] = hex!("FFFFFFFFFFFFFFFF");
 
-pub fn parse_hmac_block(
-    data: &[u8],
-    hmac_key: &GenericArray<u8, U64>,
-) -> Result<Vec<u8>, CryptoError> {
+pub fn parse_hmac_block(data: &[u8], hmac_key: &[u8]) -> Result<Vec<u8>, CryptoError> {
     let mut total_block: Vec<u8> = Vec::new();
     let mut pos = 0;
     let mut block_index: u64 = 0;
@@ -30,12 +27,12 @@ pub fn parse_hmac_block(
         let mut block_index_buf = [0u8; 8];
         LE::write_u64(&mut block_index_buf, block_index);
 
-        let hmac_block_key = hash::calculate_sha512_multiple(&[&block_index_buf, &hmac_key]);
+        let hmac_block_key = calc_block_key(&block_index_buf, hmac_key);
 
         if block_hmac
             != hash::calculate_hmac_multiple(
                 &[&block_index_buf, &block_length_buf, &block_data],
-                &hmac_block_key,
+                hmac_block_key.as_slice(),
             )?
             .as_slice()
         {
@@ -54,7 +51,7 @@ pub fn parse_hmac_block(
 
 pub fn write_hmac_block<W: std::io::Write + std::io::Seek>(
     data: &[u8],
-    hmac_key: &GenericArray<u8, U64>,
+    hmac_key: &[u8],
     writer: &mut W,
 ) -> Result<(), CryptoError> {
     let mut pos = 0;
@@ -76,10 +73,10 @@ pub fn write_hmac_block<W: std::io::Write + std::io::Seek>(
 
         let mut block_length_buf = [0u8; 4];
         LE::write_u32(&mut block_length_buf, block_size as u32);
-        let hmac_block_key = hash::calculate_sha512_multiple(&[&block_index_buf, &hmac_key]);
+        let hmac_block_key = calc_block_key(&block_index_buf, hmac_key);
         let block_hmac = hash::calculate_hmac_multiple(
             &[&block_index_buf, &block_length_buf, &block_data],
-            &hmac_block_key,
+            hmac_block_key.as_slice(),
         )?;
 
         writer.write_all(&block_hmac)?;
@@ -96,11 +93,11 @@ pub fn write_hmac_block<W: std::io::Write + std::io::Seek>(
 
     let mut block_length_buf = [0u8; 4];
     LE::write_u32(&mut block_length_buf, 0);
-    let hmac_block_key = hash::calculate_sha512_multiple(&[&block_index_buf, &hmac_key]);
+    let hmac_block_key = calc_block_key(&block_index_buf, hmac_key);
 
     let block_hmac = hash::calculate_hmac_multiple(
         &[&block_index_buf, &block_length_buf, &[]], // 空数据
-        &hmac_block_key,
+        hmac_block_key.as_slice(),
     )?;
 
     writer.write_all(&block_hmac)?;
@@ -109,17 +106,29 @@ pub fn write_hmac_block<W: std::io::Write + std::io::Seek>(
     Ok(())
 }
 
-pub fn calc_kdbx4_hmac_key(salt: &[u8], transformed_key: &[u8]) -> GenericArray<u8, U64> {
-    hash::calculate_sha512_multiple(&[&salt, &transformed_key, &KDBX4_MAIN_HMAC_SUFFIX])
+fn calc_block_key(block_index_buf: &[u8; 8], hmac_key: &[u8]) -> Zeroizing<[u8; 64]> {
+    let mut hash = hash::calculate_sha512_multiple(&[block_index_buf, hmac_key]);
+    let mut block_key = Zeroizing::new([0u8; 64]);
+    block_key.copy_from_slice(&hash);
+    hash.as_mut_slice().zeroize();
+    block_key
+}
+
+pub fn calc_kdbx4_hmac_key(salt: &[u8], transformed_key: &[u8]) -> SecureData {
+    let mut hash =
+        hash::calculate_sha512_multiple(&[salt, transformed_key, &KDBX4_MAIN_HMAC_SUFFIX]);
+    SecureData::take(hash.as_mut_slice())
 }
 
-pub fn calc_kdbx4_header_hmac_key(hmac_key: &GenericArray<u8, U64>) -> GenericArray<u8, U64> {
-    hash::calculate_sha512_multiple(&[&KDBX4_HEADER_HMAC_SUFFIX, &hmac_key])
+pub fn calc_kdbx4_header_hmac_key(hmac_key: &[u8]) -> SecureData {
+    let mut hash = hash::calculate_sha512_multiple(&[&KDBX4_HEADER_HMAC_SUFFIX, hmac_key]);
+    SecureData::take(hash.as_mut_slice())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use generic_array::GenericArray;
     use std::io::Cursor;
 
     #[test]
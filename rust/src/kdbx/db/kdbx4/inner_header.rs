@@ -7,6 +7,7 @@ use crate::utils::writer::{FixedSizeExt, Writable, WSExt};
 use byteorder::LittleEndian;
 use byteorder::{ByteOrder, WriteBytesExt};
 use hex_literal::hex;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 pub const INNER_HEADER_END_OF_HEADER: u8 = 0x00;
 pub const INNER_HEADER_INNER_ENCRYPTION_ALGORITHM: u8 = 0x01;
@@ -19,7 +20,9 @@ pub struct Kdbx4InnerHeader {
     pub binary_content: Vec<BinaryContent>,
 }
 
+#[derive(Zeroize, ZeroizeOnDrop)]
 pub struct Kdbx4InnerEncryption {
+    #[zeroize(skip)]
     pub inner_encryption_algorithm: InnerEncryptionAlgorithm,
     pub inner_encryption_key: Vec<u8>,
 }
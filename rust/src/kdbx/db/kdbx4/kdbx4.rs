@@ -1,20 +1,30 @@
 use crate::crypto::hash;
+use crate::crypto::kdf::{KdfError, KdfOptions};
+use crate::crypto::memory_crypt::SecureDataError;
+use crate::crypto::secure_data::SecureData;
 use crate::kdbx::config::MemoryProtectConfig;
-use crate::kdbx::db::kdbx4::config::Kdbx4Config;
+use crate::kdbx::db::kdbx4::config::{Kdbx4Config, MasterKeyChange};
 use crate::kdbx::db::kdbx4::errors::Kdbx4Error;
 use crate::kdbx::db::kdbx4::header::Kdbx4Header;
 use crate::kdbx::db::kdbx4::hmac::{
     calc_kdbx4_header_hmac_key, calc_kdbx4_hmac_key, write_hmac_block,
 };
 use crate::kdbx::db::kdbx4::inner_header::Kdbx4InnerHeader;
+use crate::kdbx::keys::KdbxKey;
+use crate::kdbx::quick_unlock::{QuickUnlockCache, QuickUnlockError, QuickUnlockOptions};
+use crate::kdbx::snapshot::{self, SnapshotError};
 use crate::kdbx::xml::database::KeePassDatabase;
+use crate::kdbx::xml::entities::meta::MasterKeyStatus;
 use crate::kdbx::xml::errors::KdbxSaveError;
 use crate::utils::writer::WritableExt;
 use crate::{crypto, kdbx::db::kdbx4::hmac::parse_hmac_block};
-use generic_array::{typenum::U32, GenericArray};
+use chrono::Utc;
+use zeroize::Zeroizing;
 
 pub struct Kdbx4 {
-    pub key_hash: GenericArray<u8, U32>,
+    transformed_key: SecureData,
+    /// 最近一次读取或写入文件时外层header的SHA-256, 快照以此与磁盘上的文件对应
+    header_sha256: [u8; 32],
     pub header: Kdbx4Header,
     pub database: KeePassDatabase,
 }
@@ -22,75 +32,380 @@ pub struct Kdbx4 {
 impl Kdbx4 {
     pub fn open(
         data: &[u8],
-        key_hash: &GenericArray<u8, U32>,
+        key_hash: &SecureData,
         config: &MemoryProtectConfig,
     ) -> Result<Kdbx4, Kdbx4Error> {
+        let (header, header_size) = Self::read_header(data)?;
+        let transformed_key = transform_key(&header.config, key_hash, &KdfOptions::default())?;
+        Self::open_with_header(data, header, header_size, transformed_key, config)
+    }
+
+    /// 以新的配置和文档创建数据库, 复合密钥在此完成变换以便随后保存或创建快速解锁
+    pub fn create(
+        config: Kdbx4Config,
+        database: KeePassDatabase,
+        key: &KdbxKey,
+        memory_config: &MemoryProtectConfig,
+    ) -> Result<Kdbx4, Kdbx4Error> {
+        Self::create_with_options(config, database, key, memory_config, &KdfOptions::default())
+    }
+
+    /// 同[`Kdbx4::create`], 密钥变换时报告进度并可被取消
+    pub fn create_with_options(
+        config: Kdbx4Config,
+        database: KeePassDatabase,
+        key: &KdbxKey,
+        memory_config: &MemoryProtectConfig,
+        options: &KdfOptions,
+    ) -> Result<Kdbx4, Kdbx4Error> {
+        let key_hash = key.calc_key_hash_with_challenge(config.kdf_parameters.seed())?;
+        let mut transformed_key = transform_key(&config, &key_hash, options)?;
+        memory_config.protect(&mut transformed_key)?;
+        let header = Kdbx4Header::new(config);
+        Ok(Self {
+            transformed_key,
+            header_sha256: crypto::hash::calculate_sha256(&header.write_to_buffer()?).into(),
+            header,
+            database,
+        })
+    }
+
+    /// 以复合密钥打开数据库, 挑战-响应密钥以header中KDF的种子作为挑战
+    pub fn open_with_key(
+        data: &[u8],
+        key: &KdbxKey,
+        config: &MemoryProtectConfig,
+    ) -> Result<Kdbx4, Kdbx4Error> {
+        Self::open_with_key_options(data, key, config, &KdfOptions::default())
+    }
+
+    /// 同[`Kdbx4::open_with_key`], 密钥变换时报告进度并可被取消
+    pub fn open_with_key_options(
+        data: &[u8],
+        key: &KdbxKey,
+        config: &MemoryProtectConfig,
+        options: &KdfOptions,
+    ) -> Result<Kdbx4, Kdbx4Error> {
+        let (header, header_size) = Self::read_header(data)?;
+        let key_hash = key.calc_key_hash_with_challenge(header.config.kdf_parameters.seed())?;
+        let transformed_key = transform_key(&header.config, &key_hash, options)?;
+        Self::open_with_header(data, header, header_size, transformed_key, config)
+    }
+
+    /// 以快速解锁缓存打开数据库, 跳过KDF的密钥变换
+    ///
+    /// header的主种子或KDF参数已改变时缓存会被清除, 需要重新完整解锁
+    pub fn open_with_quick_unlock(
+        data: &[u8],
+        cache: &mut QuickUnlockCache,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        config: &MemoryProtectConfig,
+    ) -> Result<Kdbx4, Kdbx4Error> {
+        Self::open_with_quick_unlock_options(
+            data,
+            cache,
+            pin,
+            platform_secret,
+            config,
+            &KdfOptions::default(),
+        )
+    }
+
+    /// 同[`Kdbx4::open_with_quick_unlock`], 由PIN派生密钥时报告进度并可被取消
+    pub fn open_with_quick_unlock_options(
+        data: &[u8],
+        cache: &mut QuickUnlockCache,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        config: &MemoryProtectConfig,
+        options: &KdfOptions,
+    ) -> Result<Kdbx4, Kdbx4Error> {
+        let (header, header_size) = Self::read_header(data)?;
+        let transformed_key =
+            cache.unlock_with_options(&header.config, pin, platform_secret, options)?;
+        Self::open_with_header(data, header, header_size, transformed_key, config)
+    }
+
+    /// 为当前header创建快速解锁缓存
+    pub fn create_quick_unlock(
+        &self,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        options: QuickUnlockOptions,
+    ) -> Result<QuickUnlockCache, QuickUnlockError> {
+        self.create_quick_unlock_with_options(pin, platform_secret, options, &KdfOptions::default())
+    }
+
+    /// 同[`Kdbx4::create_quick_unlock`], 由PIN派生密钥时报告进度并可被取消
+    pub fn create_quick_unlock_with_options(
+        &self,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        options: QuickUnlockOptions,
+        kdf_options: &KdfOptions,
+    ) -> Result<QuickUnlockCache, QuickUnlockError> {
+        QuickUnlockCache::create_with_options(
+            &self.header.config,
+            &self.transformed_key,
+            pin,
+            platform_secret,
+            options,
+            kdf_options,
+        )
+    }
+
+    fn read_header(data: &[u8]) -> Result<(Kdbx4Header, usize), Kdbx4Error> {
         let (header, header_size) = Kdbx4Header::try_from(data)?;
-        let header_bytes = &data[..header_size];
         let header_sha256 = &data[header_size..header_size + 32];
-        let header_hmac = &data[header_size + 32..header_size + 64];
-
-        if header_sha256 != crypto::hash::calculate_sha256(header_bytes).as_slice() {
+        if header_sha256 != crypto::hash::calculate_sha256(&data[..header_size]).as_slice() {
             return Err(Kdbx4Error::HeaderSha256ChecksumMismatch);
         }
+        Ok((header, header_size))
+    }
 
-        let transformed_key = header
-            .config
-            .kdf_parameters
-            .get_kdf()
-            .transform_key(key_hash)?;
-        let hmac_key = calc_kdbx4_hmac_key(&header.config.master_salt_seed, &transformed_key);
-        let header_hmac_key = calc_kdbx4_header_hmac_key(&hmac_key);
+    fn open_with_header(
+        data: &[u8],
+        header: Kdbx4Header,
+        header_size: usize,
+        transformed_key: SecureData,
+        config: &MemoryProtectConfig,
+    ) -> Result<Kdbx4, Kdbx4Error> {
+        let mut keys = DerivedKeys::derive(&header.config, &transformed_key)?;
+        keys.protect(config)?;
+        Self::check_header_hmac(data, header_size, &keys)?;
+
+        let payload_encrypted =
+            parse_hmac_block(&data[header_size + 64..], &keys.hmac_key.unsecure()?)
+                .map_err(Kdbx4Error::ParseHmacBlockError)?;
+
+        let payload_decrypted = Zeroizing::new(
+            header
+                .config
+                .encryption_algorithm
+                .get_cipher(&keys.master_key.unsecure()?, &header.config.encryption_iv)
+                .decrypt(&payload_encrypted)
+                .map_err(Kdbx4Error::DecryptPayloadError)?,
+        );
+
+        let payload_uncompressed = Zeroizing::new(
+            header
+                .config
+                .compression_config
+                .get_compression()
+                .decompress(&payload_decrypted)
+                .map_err(Kdbx4Error::DecompressPayloadError)?,
+        );
 
+        let header_sha256 = crypto::hash::calculate_sha256(&data[..header_size]).into();
+        Self::from_payload(
+            header,
+            header_sha256,
+            &payload_uncompressed,
+            transformed_key,
+            config,
+        )
+    }
+
+    /// 校验header之后的HMAC, 确认变换后的密钥与文件对应
+    fn check_header_hmac(
+        data: &[u8],
+        header_size: usize,
+        keys: &DerivedKeys,
+    ) -> Result<(), Kdbx4Error> {
+        let header_hmac = data
+            .get(header_size + 32..header_size + 64)
+            .ok_or(Kdbx4Error::HeaderHmacChecksumMismatch)?;
         if header_hmac
-            != hash::calculate_hmac_multiple(&[&header_bytes], &header_hmac_key)
-                .map_err(Kdbx4Error::CalculateHmacError)?
-                .as_slice()
+            != hash::calculate_hmac_multiple(
+                &[&data[..header_size]],
+                &keys.header_hmac_key.unsecure()?,
+            )
+            .map_err(Kdbx4Error::CalculateHmacError)?
+            .as_slice()
         {
             return Err(Kdbx4Error::HeaderHmacChecksumMismatch);
         }
+        Ok(())
+    }
 
-        let payload_encrypted = parse_hmac_block(&data[header_size + 64..], &hmac_key)
-            .map_err(Kdbx4Error::ParseHmacBlockError)?;
-
-        let master_key =
-            hash::calculate_sha256_multiple(&[&header.config.master_salt_seed, &transformed_key]);
-
-        let payload_decrypted = header
-            .config
-            .encryption_algorithm
-            .get_cipher(&master_key, &header.config.encryption_iv)
-            .decrypt(&payload_encrypted)
-            .map_err(Kdbx4Error::DecryptPayloadError)?;
-
-        let payload_uncompressed = header
-            .config
-            .compression_config
-            .get_compression()
-            .decompress(&payload_decrypted)
-            .map_err(Kdbx4Error::DecompressPayloadError)?;
-
-        let (inner_header, header_size) = Kdbx4InnerHeader::try_from(&payload_uncompressed[..])?;
-        let xml = &payload_uncompressed[header_size..];
-        let now = chrono::Local::now();
-        let timestamp = now.format("%Y%m%d_%H%M%S").to_string();
-        let filename = format!("demo_{}.xml", timestamp);
-        std::fs::write(&filename, xml)?;
+    /// 由解密解压后的内层header与XML构建数据库
+    fn from_payload(
+        header: Kdbx4Header,
+        header_sha256: [u8; 32],
+        payload: &[u8],
+        mut transformed_key: SecureData,
+        config: &MemoryProtectConfig,
+    ) -> Result<Kdbx4, Kdbx4Error> {
+        let (inner_header, header_size) = Kdbx4InnerHeader::try_from(payload)?;
+        let xml = &payload[header_size..];
 
+        config.protect(&mut transformed_key)?;
         Ok(Self {
-            key_hash: key_hash.clone(),
+            transformed_key,
+            header_sha256,
             header,
             database: KeePassDatabase::try_from(xml, inner_header, config)?,
         })
     }
 
+    /// 把当前(可能未保存的)数据库加密为快照, `snapshot_key`由应用保存在平台密钥库中
+    ///
+    /// 快照对应打开或[`Kdbx4::change_master_key`]时的header, 以其他配置保存后需重新打开再生成
+    pub fn snapshot(&self, snapshot_key: &[u8]) -> Result<Vec<u8>, SnapshotError> {
+        let database = self.database.encrypt_database()?;
+        let payload = Zeroizing::new(database.write_to_buffer().map_err(KdbxSaveError::from)?);
+        snapshot::seal(
+            snapshot_key,
+            &self.header_sha256,
+            &self.transformed_key,
+            &payload,
+        )
+    }
+
+    /// 从快照恢复数据库, 跳过KDF; `data`为磁盘上的文件
+    ///
+    /// 其header与快照记录的不同, 或快照中的密钥无法通过header的HMAC校验时拒绝恢复
+    pub fn restore_snapshot(
+        snapshot: &[u8],
+        snapshot_key: &[u8],
+        data: &[u8],
+        config: &MemoryProtectConfig,
+    ) -> Result<Kdbx4, SnapshotError> {
+        let (header, header_size) = Self::read_header(data)?;
+        let header_sha256 = crypto::hash::calculate_sha256(&data[..header_size]).into();
+        let (transformed_key, payload) = snapshot::open(snapshot, snapshot_key, &header_sha256)?;
+
+        let keys = DerivedKeys::derive(&header.config, &transformed_key)?;
+        match Self::check_header_hmac(data, header_size, &keys) {
+            Err(Kdbx4Error::HeaderHmacChecksumMismatch) => {
+                return Err(SnapshotError::HeaderMismatch)
+            }
+            result => result?,
+        }
+        Self::from_payload(header, header_sha256, &payload, transformed_key, config)
+            .map_err(SnapshotError::from)
+    }
+
+    /// 以复合密钥和新的配置保存, 挑战-响应密钥以新配置中KDF的种子作为挑战
+    pub fn save_with_key<W>(
+        &self,
+        key: &KdbxKey,
+        config: Kdbx4Config,
+        writer: &mut W,
+    ) -> Result<(), KdbxSaveError>
+    where
+        W: std::io::Write + std::io::Seek,
+    {
+        self.save_with_key_options(key, config, writer, &KdfOptions::default())
+    }
+
+    /// 同[`Kdbx4::save_with_key`], 密钥变换时报告进度并可被取消
+    pub fn save_with_key_options<W>(
+        &self,
+        key: &KdbxKey,
+        config: Kdbx4Config,
+        writer: &mut W,
+        options: &KdfOptions,
+    ) -> Result<(), KdbxSaveError>
+    where
+        W: std::io::Write + std::io::Seek,
+    {
+        let key_hash = key.calc_key_hash_with_challenge(config.kdf_parameters.seed())?;
+        self.save_with_config_options(&key_hash, config, writer, options)
+    }
+
     // 以新的配置保存keepass数据库
     pub fn save_with_config<W>(
         &self,
-        key_hash: &GenericArray<u8, U32>,
+        key_hash: &SecureData,
+        config: Kdbx4Config,
+        writer: &mut W,
+    ) -> Result<(), KdbxSaveError>
+    where
+        W: std::io::Write + std::io::Seek,
+    {
+        self.save_with_config_options(key_hash, config, writer, &KdfOptions::default())
+    }
+
+    /// 同[`Kdbx4::save_with_config`], 密钥变换时报告进度并可被取消
+    pub fn save_with_config_options<W>(
+        &self,
+        key_hash: &SecureData,
         config: Kdbx4Config,
         writer: &mut W,
+        options: &KdfOptions,
+    ) -> Result<(), KdbxSaveError>
+    where
+        W: std::io::Write + std::io::Seek,
+    {
+        let transformed_key = transform_key(&config, key_hash, options)?;
+        self.write_with_transformed_key(config, &transformed_key, writer)?;
+        Ok(())
+    }
+
+    /// 一次性更换复合密钥、加密算法、KDF与压缩设置并保存
+    ///
+    /// 所有盐值与IV都会重新生成, 并更新`MasterKeyChanged`. 写入失败时内存中的数据库保持不变
+    pub fn change_master_key<W>(
+        &mut self,
+        key: &KdbxKey,
+        change: MasterKeyChange,
+        config: &MemoryProtectConfig,
+        writer: &mut W,
+    ) -> Result<(), KdbxSaveError>
+    where
+        W: std::io::Write + std::io::Seek,
+    {
+        self.change_master_key_with_options(key, change, config, writer, &KdfOptions::default())
+    }
+
+    /// 同[`Kdbx4::change_master_key`], 密钥变换时报告进度并可被取消
+    pub fn change_master_key_with_options<W>(
+        &mut self,
+        key: &KdbxKey,
+        change: MasterKeyChange,
+        config: &MemoryProtectConfig,
+        writer: &mut W,
+        options: &KdfOptions,
     ) -> Result<(), KdbxSaveError>
+    where
+        W: std::io::Write + std::io::Seek,
+    {
+        let new_config = self.header.config.with_change(change)?;
+        let key_hash = key.calc_key_hash_with_challenge(new_config.kdf_parameters.seed())?;
+        let mut transformed_key = transform_key(&new_config, &key_hash, options)?;
+
+        let meta = &mut self.database.document.meta;
+        let previous = std::mem::replace(&mut meta.master_key_changed, Utc::now().into());
+        let header_sha256 =
+            match self.write_with_transformed_key(new_config.clone(), &transformed_key, writer) {
+                Ok(header_sha256) => header_sha256,
+                Err(e) => {
+                    self.database.document.meta.master_key_changed = previous;
+                    return Err(e);
+                }
+            };
+
+        config.protect(&mut transformed_key)?;
+        self.header = self.header.copy_with(new_config);
+        self.header_sha256 = header_sha256;
+        self.transformed_key = transformed_key;
+        Ok(())
+    }
+
+    /// 主密钥是否已到建议或强制更换的时间
+    pub fn master_key_status(&self) -> MasterKeyStatus {
+        self.database.document.meta.master_key_status(Utc::now())
+    }
+
+    /// 写入完整的文件, 返回外层header的SHA-256
+    fn write_with_transformed_key<W>(
+        &self,
+        config: Kdbx4Config,
+        transformed_key: &SecureData,
+        writer: &mut W,
+    ) -> Result<[u8; 32], KdbxSaveError>
     where
         W: std::io::Write + std::io::Seek,
     {
@@ -101,37 +416,71 @@ impl Kdbx4 {
         let header_sha256 = crypto::hash::calculate_sha256(header_bytes.as_slice());
         writer.write_all(&header_sha256)?;
 
-        let transformed_key = header
-            .config
-            .kdf_parameters
-            .get_kdf()
-            .transform_key(key_hash)?;
-        let hmac_key = calc_kdbx4_hmac_key(&header.config.master_salt_seed, &transformed_key);
-        let header_hmac_key = calc_kdbx4_header_hmac_key(&hmac_key);
-        let header_hmac = hash::calculate_hmac_multiple(&[&header_bytes], &header_hmac_key)?;
+        let keys = DerivedKeys::derive(&header.config, transformed_key)?;
+        let header_hmac =
+            hash::calculate_hmac_multiple(&[&header_bytes], &keys.header_hmac_key.unsecure()?)?;
         writer.write_all(&header_hmac)?;
 
         // 计算内层数据
         let new_database = self.database.encrypt_database()?;
-        let new_database_bytes = new_database.write_to_buffer()?;
+        let new_database_bytes = Zeroizing::new(new_database.write_to_buffer()?);
 
-        let payload_compressed = header
-            .config
-            .compression_config
-            .get_compression()
-            .compress(&new_database_bytes)?;
-        let master_key =
-            hash::calculate_sha256_multiple(&[&header.config.master_salt_seed, &transformed_key]);
+        let payload_compressed = Zeroizing::new(
+            header
+                .config
+                .compression_config
+                .get_compression()
+                .compress(&new_database_bytes)?,
+        );
 
         let payload_encrypted = header
             .config
             .encryption_algorithm
-            .get_cipher(&master_key, &header.config.encryption_iv)
+            .get_cipher(&keys.master_key.unsecure()?, &header.config.encryption_iv)
             .encrypt(&payload_compressed)?;
 
-        write_hmac_block(&payload_encrypted, &hmac_key, writer)?;
+        write_hmac_block(&payload_encrypted, &keys.hmac_key.unsecure()?, writer)?;
 
-        Ok(())
+        Ok(header_sha256.into())
+    }
+}
+
+fn transform_key(
+    config: &Kdbx4Config,
+    key_hash: &SecureData,
+    options: &KdfOptions,
+) -> Result<SecureData, KdfError> {
+    config
+        .kdf_parameters
+        .get_kdf()
+        .transform_key_with(key_hash, options)
+}
+
+/// 由变换后的密钥派生出的各级密钥
+struct DerivedKeys {
+    hmac_key: SecureData,
+    header_hmac_key: SecureData,
+    master_key: SecureData,
+}
+
+impl DerivedKeys {
+    fn derive(config: &Kdbx4Config, transformed_key: &SecureData) -> Result<Self, SecureDataError> {
+        let transformed_key = transformed_key.unsecure()?;
+        let hmac_key = calc_kdbx4_hmac_key(&config.master_salt_seed, &transformed_key);
+        let header_hmac_key = calc_kdbx4_header_hmac_key(&hmac_key.unsecure()?);
+        let mut master_key =
+            hash::calculate_sha256_multiple(&[&config.master_salt_seed, &transformed_key]);
+        Ok(Self {
+            hmac_key,
+            header_hmac_key,
+            master_key: SecureData::take(master_key.as_mut_slice()),
+        })
+    }
+
+    fn protect(&mut self, config: &MemoryProtectConfig) -> Result<(), SecureDataError> {
+        config.protect(&mut self.hmac_key)?;
+        config.protect(&mut self.header_hmac_key)?;
+        config.protect(&mut self.master_key)
     }
 }
 
@@ -139,13 +488,402 @@ impl Kdbx4 {
 mod kdbx4_tests {
     use std::io::Cursor;
 
+    use crate::crypto::{
+        kdf::{CancellationToken, KdfError, KdfOptions},
+        secure_data::SecureData,
+        wipe_check,
+    };
     use crate::kdbx::{
+        challenge_response::SoftwareChallengeResponse,
         config::MemoryProtectConfig,
-        db::kdbx4::kdbx4::Kdbx4,
-        keys::KdbxKey,
-        xml::{database::KeePassDatabase, entities},
+        db::kdbx4::{
+            config::{Kdbx4Config, MasterKeyChange},
+            errors::{Kdbx4ConfigError, Kdbx4Error},
+            header::Kdbx4Header,
+            header_entity::{
+                compression::CompressionConfig, encryption_algorithm::EncryptionAlgorithm,
+                kdf_config::KdfConfig,
+            },
+            kdbx4::Kdbx4,
+        },
+        keys::{KdbxKey, KdbxKeyError},
+        quick_unlock::{QuickUnlockError, QuickUnlockOptions},
+        snapshot::{generate_snapshot_key, SnapshotError},
+        xml::{
+            database::KeePassDatabase, entities, entities::meta::MasterKeyStatus, errors, fixtures,
+        },
     };
 
+    fn memory_database(key: &KdbxKey) -> Vec<u8> {
+        let config = Kdbx4Config {
+            encryption_algorithm: EncryptionAlgorithm::ChaCha20,
+            compression_config: CompressionConfig::None,
+            master_salt_seed: [1; 32],
+            encryption_iv: EncryptionAlgorithm::ChaCha20.get_random_iv().unwrap(),
+            kdf_parameters: KdfConfig::Aes {
+                salt: [2; 32],
+                rounds: 10,
+            },
+        };
+        // 序列化时空值会写为空元素, 这里补全可空字段以便重新解析
+        let root = fixtures::group_xml(
+            &fixtures::uuid(1),
+            "Root",
+            "<IsExpanded>True</IsExpanded>\
+             <EnableAutoType>null</EnableAutoType>\
+             <EnableSearching>null</EnableSearching>",
+            &fixtures::entry_xml(
+                &fixtures::uuid(2),
+                &[("Title", "entry")],
+                "<AutoType><Enabled>True</Enabled>\
+                 <DataTransferObfuscation>0</DataTransferObfuscation></AutoType>",
+            ),
+        );
+        let kdbx = Kdbx4 {
+            transformed_key: SecureData::new(&[]),
+            header_sha256: [0; 32],
+            header: Kdbx4Header::new(config.clone()),
+            database: fixtures::database(&root),
+        };
+        let mut buffer = Vec::new();
+        kdbx.save_with_key(key, config, &mut Cursor::new(&mut buffer))
+            .unwrap();
+        buffer
+    }
+
+    fn yubikey(secret: &[u8]) -> KdbxKey {
+        let mut key = KdbxKey::new();
+        key.add_master_key("password");
+        key.add_challenge_response(Box::new(SoftwareChallengeResponse::new(secret)));
+        key
+    }
+
+    #[test]
+    fn test_kdbx4_key_wiped_on_drop() {
+        let config = MemoryProtectConfig {
+            enable_memory_crypt: false,
+            enable_mlock: false,
+        };
+        let mut key = KdbxKey::new();
+        key.add_master_key("password");
+        let data = memory_database(&key);
+        let kdbx = Kdbx4::open_with_key(&data, &key, &config).unwrap();
+
+        let watch = wipe_check::watch(kdbx.transformed_key.as_ptr());
+        drop(kdbx);
+        assert!(watch.wiped_on_free());
+    }
+
+    #[test]
+    fn test_kdbx4_quick_unlock() {
+        let config = MemoryProtectConfig {
+            enable_memory_crypt: false,
+            enable_mlock: false,
+        };
+        let options = QuickUnlockOptions {
+            max_attempts: 2,
+            memory_kib: 64,
+            iterations: 1,
+        };
+        let mut key = KdbxKey::new();
+        key.add_master_key("password");
+        let data = memory_database(&key);
+        let kdbx = Kdbx4::open_with_key(&data, &key, &config).unwrap();
+        let mut cache = kdbx.create_quick_unlock("1234", None, options).unwrap();
+
+        let unlocked =
+            Kdbx4::open_with_quick_unlock(&data, &mut cache, "1234", None, &config).unwrap();
+        assert_eq!(unlocked.database.document.root.group.entry.len(), 1);
+        assert!(matches!(
+            Kdbx4::open_with_quick_unlock(&data, &mut cache, "4321", None, &config),
+            Err(Kdbx4Error::QuickUnlockError(QuickUnlockError::WrongPin(1)))
+        ));
+
+        // 重新生成种子后旧缓存失效
+        let mut buffer = Vec::new();
+        let new_config = kdbx.header.config.rekey().unwrap();
+        kdbx.save_with_key(&key, new_config, &mut Cursor::new(&mut buffer))
+            .unwrap();
+        assert!(matches!(
+            Kdbx4::open_with_quick_unlock(&buffer, &mut cache, "1234", None, &config),
+            Err(Kdbx4Error::QuickUnlockError(QuickUnlockError::Invalidated))
+        ));
+        assert!(cache.is_wiped());
+    }
+
+    #[test]
+    fn test_kdbx4_cancelled_options() {
+        let config = MemoryProtectConfig {
+            enable_memory_crypt: false,
+            enable_mlock: false,
+        };
+        let quick_unlock = QuickUnlockOptions {
+            max_attempts: 2,
+            memory_kib: 64,
+            iterations: 1,
+        };
+        let token = CancellationToken::new();
+        token.cancel();
+        let cancelled = KdfOptions {
+            progress: None,
+            cancel: Some(token),
+        };
+        let mut key = KdbxKey::new();
+        key.add_master_key("password");
+        let data = memory_database(&key);
+        let mut kdbx = Kdbx4::open_with_key(&data, &key, &config).unwrap();
+
+        assert!(matches!(
+            Kdbx4::create_with_options(
+                kdbx.header.config.clone(),
+                fixtures::database(&fixtures::group_xml(&fixtures::uuid(1), "Root", "", "")),
+                &key,
+                &config,
+                &cancelled
+            ),
+            Err(Kdbx4Error::KdfTransformKeyError(KdfError::Cancelled))
+        ));
+
+        let mut buffer = Vec::new();
+        let new_config = kdbx.header.config.rekey().unwrap();
+        assert!(matches!(
+            kdbx.save_with_key_options(&key, new_config, &mut Cursor::new(&mut buffer), &cancelled),
+            Err(errors::KdbxSaveError::KdfError(KdfError::Cancelled))
+        ));
+        let seed = kdbx.header.config.master_salt_seed;
+        assert!(matches!(
+            kdbx.change_master_key_with_options(
+                &key,
+                MasterKeyChange::default(),
+                &config,
+                &mut Cursor::new(&mut buffer),
+                &cancelled
+            ),
+            Err(errors::KdbxSaveError::KdfError(KdfError::Cancelled))
+        ));
+        assert_eq!(kdbx.header.config.master_salt_seed, seed);
+        assert!(buffer.is_empty());
+
+        assert!(matches!(
+            kdbx.create_quick_unlock_with_options("1234", None, quick_unlock, &cancelled),
+            Err(QuickUnlockError::KdfError(KdfError::Cancelled))
+        ));
+        // 取消不计为一次错误的尝试
+        let mut cache = kdbx
+            .create_quick_unlock("1234", None, quick_unlock)
+            .unwrap();
+        assert!(matches!(
+            Kdbx4::open_with_quick_unlock_options(
+                &data, &mut cache, "0000", None, &config, &cancelled
+            ),
+            Err(Kdbx4Error::QuickUnlockError(QuickUnlockError::KdfError(
+                KdfError::Cancelled
+            )))
+        ));
+        assert_eq!(cache.remaining_attempts(), 2);
+    }
+
+    #[test]
+    fn test_kdbx4_challenge_response() {
+        let config = MemoryProtectConfig {
+            enable_memory_crypt: false,
+            enable_mlock: false,
+        };
+        let data = memory_database(&yubikey(b"secret"));
+
+        let kdbx = Kdbx4::open_with_key(&data, &yubikey(b"secret"), &config).unwrap();
+        assert_eq!(kdbx.database.document.root.group.entry.len(), 1);
+
+        assert!(matches!(
+            Kdbx4::open_with_key(&data, &yubikey(b"other"), &config),
+            Err(Kdbx4Error::HeaderHmacChecksumMismatch)
+        ));
+
+        let mut password_only = KdbxKey::new();
+        password_only.add_master_key("password");
+        assert!(matches!(
+            Kdbx4::open_with_key(&data, &password_only, &config),
+            Err(Kdbx4Error::HeaderHmacChecksumMismatch)
+        ));
+        assert!(
+            Kdbx4::open_with_key(&memory_database(&password_only), &password_only, &config).is_ok()
+        );
+
+        assert!(matches!(
+            yubikey(b"secret").calc_key_hash(),
+            Err(KdbxKeyError::ChallengeRequired)
+        ));
+    }
+
+    #[test]
+    fn test_kdbx4_change_master_key() {
+        let config = MemoryProtectConfig {
+            enable_memory_crypt: false,
+            enable_mlock: false,
+        };
+        let mut old_key = KdbxKey::new();
+        old_key.add_master_key("password");
+        let mut new_key = KdbxKey::new();
+        new_key.add_master_key("new password");
+
+        let data = memory_database(&old_key);
+        let mut kdbx = Kdbx4::open_with_key(&data, &old_key, &config).unwrap();
+        let old_seed = kdbx.header.config.master_salt_seed;
+        kdbx.database.document.meta.master_key_change_rec = 30;
+        assert_eq!(kdbx.master_key_status(), MasterKeyStatus::ChangeRecommended);
+
+        let invalid = MasterKeyChange {
+            kdf_parameters: Some(KdfConfig::Argon2 {
+                version: 0x13,
+                salt: vec![0; 32],
+                iterations: 1,
+                memory: 64 * 1024,
+                parallelism: 0,
+                variant: argon2::Variant::Argon2id,
+            }),
+            ..Default::default()
+        };
+        let mut buffer = Vec::new();
+        assert!(matches!(
+            kdbx.change_master_key(&new_key, invalid, &config, &mut Cursor::new(&mut buffer)),
+            Err(errors::KdbxSaveError::InvalidConfig(
+                Kdbx4ConfigError::InvalidArgon2Parameters(_)
+            ))
+        ));
+
+        let change = MasterKeyChange {
+            encryption_algorithm: Some(EncryptionAlgorithm::Twofish),
+            compression_config: Some(CompressionConfig::GZip),
+            kdf_parameters: Some(KdfConfig::Argon2 {
+                version: 0x13,
+                salt: vec![0; 32],
+                iterations: 1,
+                memory: 64 * 1024,
+                parallelism: 1,
+                variant: argon2::Variant::Argon2id,
+            }),
+        };
+        kdbx.change_master_key(&new_key, change, &config, &mut Cursor::new(&mut buffer))
+            .unwrap();
+        assert_eq!(kdbx.master_key_status(), MasterKeyStatus::Current);
+        assert_ne!(kdbx.header.config.master_salt_seed, old_seed);
+        assert_ne!(kdbx.header.config.kdf_parameters.seed(), &[0; 32]);
+
+        assert!(matches!(
+            Kdbx4::open_with_key(&buffer, &old_key, &config),
+            Err(Kdbx4Error::HeaderHmacChecksumMismatch)
+        ));
+        let reopened = Kdbx4::open_with_key(&buffer, &new_key, &config).unwrap();
+        assert!(matches!(
+            reopened.header.config.encryption_algorithm,
+            EncryptionAlgorithm::Twofish
+        ));
+        assert_eq!(reopened.database.document.root.group.entry.len(), 1);
+        let meta = &reopened.database.document.meta;
+        assert!(meta.master_key_changed.value().is_some());
+        assert_eq!(meta.master_key_change_rec, 30);
+
+        // 修改后缓存的变换密钥随之更新
+        assert!(kdbx
+            .create_quick_unlock("1234", None, QuickUnlockOptions::default())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_kdbx4_snapshot() {
+        let config = MemoryProtectConfig {
+            enable_memory_crypt: false,
+            enable_mlock: false,
+        };
+        let mut key = KdbxKey::new();
+        key.add_master_key("password");
+        let data = memory_database(&key);
+        let mut kdbx = Kdbx4::open_with_key(&data, &key, &config).unwrap();
+        // 未保存的修改也应被恢复
+        kdbx.database.document.meta.database_name = "unsaved".to_string();
+
+        let snapshot_key = generate_snapshot_key().unwrap();
+        let snapshot = kdbx.snapshot(snapshot_key.as_slice()).unwrap();
+        let restored =
+            Kdbx4::restore_snapshot(&snapshot, snapshot_key.as_slice(), &data, &config).unwrap();
+        assert_eq!(restored.database.document.meta.database_name, "unsaved");
+        assert_eq!(restored.database.document.root.group.entry.len(), 1);
+        assert_eq!(
+            restored.transformed_key.unsecure().unwrap(),
+            kdbx.transformed_key.unsecure().unwrap()
+        );
+
+        let other_key = generate_snapshot_key().unwrap();
+        assert!(matches!(
+            Kdbx4::restore_snapshot(&snapshot, other_key.as_slice(), &data, &config),
+            Err(SnapshotError::DecryptionFailed)
+        ));
+        let mut tampered = snapshot.clone();
+        tampered[4] = 2;
+        assert!(matches!(
+            Kdbx4::restore_snapshot(&tampered, snapshot_key.as_slice(), &data, &config),
+            Err(SnapshotError::UnsupportedVersion(2))
+        ));
+        *tampered.last_mut().unwrap() ^= 1;
+        tampered[4] = 1;
+        assert!(matches!(
+            Kdbx4::restore_snapshot(&tampered, snapshot_key.as_slice(), &data, &config),
+            Err(SnapshotError::DecryptionFailed)
+        ));
+
+        // 只有加密IV不同的header也不能恢复
+        let mut changed_iv = kdbx.header.config.clone();
+        changed_iv.encryption_iv = changed_iv.encryption_algorithm.get_random_iv().unwrap();
+        let mut saved = Vec::new();
+        kdbx.save_with_key(&key, changed_iv, &mut Cursor::new(&mut saved))
+            .unwrap();
+        assert!(matches!(
+            Kdbx4::restore_snapshot(&snapshot, snapshot_key.as_slice(), &saved, &config),
+            Err(SnapshotError::HeaderMismatch)
+        ));
+
+        // 文件被重新保存后header改变, 快照不再适用
+        let mut saved = Vec::new();
+        kdbx.save_with_key(
+            &key,
+            kdbx.header.config.rekey().unwrap(),
+            &mut Cursor::new(&mut saved),
+        )
+        .unwrap();
+        assert!(matches!(
+            Kdbx4::restore_snapshot(&snapshot, snapshot_key.as_slice(), &saved, &config),
+            Err(SnapshotError::HeaderMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_master_key_status() {
+        let config = MemoryProtectConfig {
+            enable_memory_crypt: false,
+            enable_mlock: false,
+        };
+        let mut key = KdbxKey::new();
+        key.add_master_key("password");
+        let mut kdbx = Kdbx4::open_with_key(&memory_database(&key), &key, &config).unwrap();
+        assert_eq!(kdbx.database.document.meta.master_key_change_rec, -1);
+        assert_eq!(kdbx.master_key_status(), MasterKeyStatus::Current);
+
+        let now = chrono::Utc::now();
+        let meta = &mut kdbx.database.document.meta;
+        meta.master_key_changed = (now - chrono::Duration::days(10)).into();
+        meta.master_key_change_rec = 7;
+        meta.master_key_change_force = 30;
+        assert_eq!(
+            meta.master_key_status(now),
+            MasterKeyStatus::ChangeRecommended
+        );
+        meta.master_key_change_force = 10;
+        assert_eq!(meta.master_key_status(now), MasterKeyStatus::ChangeForced);
+        meta.master_key_change_rec = -1;
+        meta.master_key_change_force = -1;
+        assert_eq!(meta.master_key_status(now), MasterKeyStatus::Current);
+    }
+
     #[test]
     fn test_kdbx4_open() -> anyhow::Result<()> {
         let config = MemoryProtectConfig {
@@ -4,4 +4,6 @@ pub mod header_entity;
 pub mod inner_header;
 mod kdbx4;
 pub mod hmac;
-pub mod config;
\ No newline at end of file
+pub mod config;
+
+pub use kdbx4::Kdbx4;
\ No newline at end of file
@@ -0,0 +1,158 @@
+use std::collections::BTreeSet;
+
+pub const UPPER_CASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+pub const LOWER_CASE: &str = "abcdefghijklmnopqrstuvwxyz";
+pub const DIGITS: &str = "0123456789";
+/// 与KeePass的 `PwCharSet.Special` 一致, 不包含括号、减号、下划线和空格
+pub const SPECIAL: &str = "!\"#$%&'*+,./:;=?@\\^`|~";
+/// 所有可打印的ASCII特殊字符
+pub const PRINTABLE_SPECIAL: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+pub const PUNCTUATION: &str = ",.;:";
+pub const BRACKETS: &str = "()[]{}<>";
+/// 容易混淆的字符
+pub const LOOK_ALIKE: &str = "O0Il1|";
+
+/// Latin-1 补充字符 (U+00A1 - U+00FF, 不包含软连字符U+00AD)
+fn high_ansi() -> impl Iterator<Item = char> {
+    ('\u{a1}'..='\u{ff}').filter(|c| *c != '\u{ad}')
+}
+
+/// 字符集配置中的字符类别
+///
+/// 顺序与KeePass `CharSetRanges` 的编码顺序一致。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CharClass {
+    Upper,
+    Lower,
+    Digits,
+    Special,
+    Punctuation,
+    Minus,
+    Underline,
+    Space,
+    Brackets,
+    HighAnsi,
+}
+
+impl CharClass {
+    pub const ALL: [CharClass; 10] = [
+        CharClass::Upper,
+        CharClass::Lower,
+        CharClass::Digits,
+        CharClass::Special,
+        CharClass::Punctuation,
+        CharClass::Minus,
+        CharClass::Underline,
+        CharClass::Space,
+        CharClass::Brackets,
+        CharClass::HighAnsi,
+    ];
+
+    /// KeePass `CharSetRanges` 中使用的编码
+    pub fn code(&self) -> char {
+        match self {
+            CharClass::Upper => 'U',
+            CharClass::Lower => 'L',
+            CharClass::Digits => 'D',
+            CharClass::Special => 'S',
+            CharClass::Punctuation => 'P',
+            CharClass::Minus => 'm',
+            CharClass::Underline => 'u',
+            CharClass::Space => 's',
+            CharClass::Brackets => 'B',
+            CharClass::HighAnsi => 'H',
+        }
+    }
+
+    pub fn from_code(code: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|class| class.code() == code)
+    }
+
+    pub fn chars(&self) -> CharSet {
+        match self {
+            CharClass::Upper => CharSet::from(UPPER_CASE),
+            CharClass::Lower => CharSet::from(LOWER_CASE),
+            CharClass::Digits => CharSet::from(DIGITS),
+            CharClass::Special => CharSet::from(SPECIAL),
+            CharClass::Punctuation => CharSet::from(PUNCTUATION),
+            CharClass::Minus => CharSet::from("-"),
+            CharClass::Underline => CharSet::from("_"),
+            CharClass::Space => CharSet::from(" "),
+            CharClass::Brackets => CharSet::from(BRACKETS),
+            CharClass::HighAnsi => CharSet(high_ansi().collect()),
+        }
+    }
+}
+
+/// 有序且不重复的字符集合
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CharSet(BTreeSet<char>);
+
+impl CharSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, chars: &CharSet) {
+        self.0.extend(chars.0.iter().copied());
+    }
+
+    pub fn add_str(&mut self, chars: &str) {
+        self.0.extend(chars.chars());
+    }
+
+    pub fn remove_str(&mut self, chars: &str) {
+        for c in chars.chars() {
+            self.0.remove(&c);
+        }
+    }
+
+    pub fn remove(&mut self, c: char) -> bool {
+        self.0.remove(&c)
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.0.contains(&c)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn to_vec(&self) -> Vec<char> {
+        self.0.iter().copied().collect()
+    }
+}
+
+impl From<&str> for CharSet {
+    fn from(value: &str) -> Self {
+        Self(value.chars().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_class_codes() {
+        for class in CharClass::ALL {
+            assert_eq!(CharClass::from_code(class.code()), Some(class));
+        }
+        assert_eq!(CharClass::from_code('_'), None);
+    }
+
+    #[test]
+    fn test_char_set() {
+        let mut set = CharClass::Digits.chars();
+        set.add(&CharClass::Upper.chars());
+        set.remove_str(LOOK_ALIKE);
+        assert_eq!(set.len(), 10 + 26 - 4);
+        assert!(!set.contains('0') && !set.contains('O') && !set.contains('I'));
+        assert_eq!(CharClass::HighAnsi.chars().len(), 94);
+    }
+}
@@ -0,0 +1,111 @@
+pub mod charset;
+pub mod passphrase;
+pub mod password;
+pub mod pattern;
+pub mod profile;
+pub mod wordlist;
+
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::crypto::secure_data::SecureData;
+
+/// 生成密码的最大长度, 配置会随数据库同步, 不能让其耗尽内存
+pub(crate) const MAX_PASSWORD_LENGTH: usize = 4096;
+
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+    #[error("Random number generator error")]
+    RandomError(#[from] getrandom::Error),
+
+    #[error("Character set is empty")]
+    EmptyCharacterSet,
+
+    #[error("Length {length} is shorter than the required {required} characters")]
+    LengthTooShort { length: usize, required: usize },
+
+    #[error("Not enough distinct characters for a password without repeats")]
+    NotEnoughCharacters,
+
+    #[error("Invalid pattern at position {0}")]
+    InvalidPattern(usize),
+
+    #[error("Invalid profile: {0}")]
+    InvalidProfile(String),
+
+    #[error("Invalid wordlist: {0}")]
+    InvalidWordlist(String),
+
+    #[error("Invalid passphrase options: {0}")]
+    InvalidPassphraseOptions(String),
+
+    #[error("Attachment not found: {0}")]
+    AttachmentNotFound(String),
+
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Profile XML parse error")]
+    ProfileParseError(#[from] quick_xml::DeError),
+
+    #[error("Profile XML write error")]
+    ProfileWriteError(#[from] quick_xml::SeError),
+}
+
+/// 均匀选取 `[0, bound)` 内的随机数, 通过拒绝采样避免取模偏差
+pub fn random_index(bound: usize) -> Result<usize, GeneratorError> {
+    debug_assert!(bound > 0);
+    let bound = bound as u64;
+    let zone = u64::MAX - (u64::MAX % bound);
+    loop {
+        let mut buf = [0u8; 8];
+        getrandom::fill(&mut buf)?;
+        let value = u64::from_le_bytes(buf);
+        if value < zone {
+            return Ok((value % bound) as usize);
+        }
+    }
+}
+
+/// Fisher-Yates 洗牌
+pub fn shuffle<T>(items: &mut [T]) -> Result<(), GeneratorError> {
+    for i in (1..items.len()).rev() {
+        let j = random_index(i + 1)?;
+        items.swap(i, j);
+    }
+    Ok(())
+}
+
+/// 将生成的字符写入 `SecureData`, 中间缓冲区会被清零
+fn into_secure_data(chars: &[char]) -> SecureData {
+    let mut buf = Zeroizing::new(Vec::with_capacity(chars.len() * 4));
+    let mut encoded = [0u8; 4];
+    for c in chars {
+        buf.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+    }
+    encoded.iter_mut().for_each(|b| *b = 0);
+    SecureData::new(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_index_range() {
+        for bound in [1, 2, 3, 7, 100] {
+            for _ in 0..200 {
+                assert!(random_index(bound).unwrap() < bound);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shuffle_keeps_items() {
+        let mut items: Vec<u32> = (0..50).collect();
+        shuffle(&mut items).unwrap();
+        let mut sorted = items.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..50).collect::<Vec<_>>());
+    }
+}
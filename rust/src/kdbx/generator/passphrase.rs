@@ -0,0 +1,218 @@
+use zeroize::Zeroizing;
+
+use crate::crypto::secure_data::SecureData;
+use crate::kdbx::generator::charset::{DIGITS, SPECIAL};
+use crate::kdbx::generator::wordlist::Wordlist;
+use crate::kdbx::generator::{random_index, GeneratorError};
+use crate::kdbx::xml::entities::Value;
+
+/// 单词的大小写形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WordCase {
+    /// 保持单词表中的形式
+    #[default]
+    AsIs,
+    Lower,
+    Upper,
+    /// 首字母大写
+    Title,
+    /// 每个单词随机选择全小写或首字母大写, 每个单词增加1比特熵
+    Random,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassphraseOptions {
+    pub word_count: usize,
+    pub separator: String,
+    pub case: WordCase,
+    /// 附加到随机单词末尾的数字个数
+    pub digits: usize,
+    /// 附加到随机单词末尾的特殊字符个数
+    pub symbols: usize,
+}
+
+impl Default for PassphraseOptions {
+    fn default() -> Self {
+        Self {
+            word_count: 6,
+            separator: " ".to_string(),
+            case: WordCase::AsIs,
+            digits: 0,
+            symbols: 0,
+        }
+    }
+}
+
+impl PassphraseOptions {
+    /// 估算使用指定单词表生成的口令短语的熵 (比特)
+    ///
+    /// 假设攻击者知道单词表和所有选项, 插入字符的熵按字符集大小加所在单词位置计算。
+    pub fn entropy_bits(&self, wordlist: &Wordlist) -> f64 {
+        let words = self.word_count as f64;
+        let mut bits = words * wordlist.bits_per_word();
+        if self.case == WordCase::Random {
+            bits += words;
+        }
+        if self.word_count > 0 {
+            let position = words.log2();
+            bits += self.digits as f64 * ((DIGITS.len() as f64).log2() + position);
+            bits += self.symbols as f64 * ((SPECIAL.chars().count() as f64).log2() + position);
+        }
+        bits
+    }
+}
+
+/// 生成的口令短语
+#[derive(Debug)]
+pub struct Passphrase {
+    /// 可直接写入条目的值, 保存时受内部流加密保护
+    pub value: Value,
+    pub entropy_bits: f64,
+}
+
+pub fn generate(
+    wordlist: &Wordlist,
+    options: &PassphraseOptions,
+) -> Result<Passphrase, GeneratorError> {
+    if options.word_count == 0 {
+        return Err(GeneratorError::InvalidPassphraseOptions(
+            "word count must be positive".to_string(),
+        ));
+    }
+
+    let mut words: Vec<Zeroizing<String>> = Vec::with_capacity(options.word_count);
+    for _ in 0..options.word_count {
+        let word = wordlist.word(random_index(wordlist.len())?);
+        let case = match options.case {
+            WordCase::Random if random_index(2)? == 0 => WordCase::Lower,
+            WordCase::Random => WordCase::Title,
+            case => case,
+        };
+        words.push(apply_case(word, case));
+    }
+
+    let digits: Vec<char> = DIGITS.chars().collect();
+    let symbols: Vec<char> = SPECIAL.chars().collect();
+    for (count, set) in [(options.digits, &digits), (options.symbols, &symbols)] {
+        for _ in 0..count {
+            let c = set[random_index(set.len())?];
+            let index = random_index(words.len())?;
+            words[index].push(c);
+        }
+    }
+
+    let mut passphrase = Zeroizing::new(String::new());
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            passphrase.push_str(&options.separator);
+        }
+        passphrase.push_str(word);
+    }
+
+    Ok(Passphrase {
+        value: Value::WaitProtect(SecureData::new(passphrase.as_bytes())),
+        entropy_bits: options.entropy_bits(wordlist),
+    })
+}
+
+fn apply_case(word: &str, case: WordCase) -> Zeroizing<String> {
+    let word = match case {
+        WordCase::AsIs | WordCase::Random => word.to_string(),
+        WordCase::Lower => word.to_lowercase(),
+        WordCase::Upper => word.to_uppercase(),
+        WordCase::Title => {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect(),
+                None => String::new(),
+            }
+        }
+    };
+    Zeroizing::new(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wordlist() -> Wordlist {
+        Wordlist::parse("alpha\nbravo\ncharlie\ndelta\necho\nfoxtrot\ngolf\nhotel\n").unwrap()
+    }
+
+    fn passphrase_string(passphrase: &Passphrase) -> String {
+        let Value::WaitProtect(value) = &passphrase.value else {
+            panic!("passphrase must be waiting for protection");
+        };
+        String::from_utf8(value.unsecure().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_generate_words() {
+        let wordlist = wordlist();
+        let options = PassphraseOptions {
+            word_count: 5,
+            separator: "-".to_string(),
+            case: WordCase::Title,
+            ..Default::default()
+        };
+        let passphrase = generate(&wordlist, &options).unwrap();
+        let text = passphrase_string(&passphrase);
+        let words: Vec<&str> = text.split('-').collect();
+        assert_eq!(words.len(), 5);
+        for word in words {
+            let lower = word.to_lowercase();
+            assert!((0..wordlist.len()).any(|i| wordlist.word(i) == lower));
+            assert!(word.chars().next().unwrap().is_uppercase());
+        }
+        assert_eq!(passphrase.entropy_bits, 15.0);
+    }
+
+    #[test]
+    fn test_digit_and_symbol_injection() {
+        let options = PassphraseOptions {
+            word_count: 4,
+            case: WordCase::Upper,
+            digits: 2,
+            symbols: 1,
+            ..Default::default()
+        };
+        let passphrase = generate(&wordlist(), &options).unwrap();
+        let text = passphrase_string(&passphrase);
+        assert_eq!(text.split(' ').count(), 4);
+        assert_eq!(text.chars().filter(|c| c.is_ascii_digit()).count(), 2);
+        assert_eq!(text.chars().filter(|c| SPECIAL.contains(*c)).count(), 1);
+        assert!(!text.chars().any(|c| c.is_lowercase()));
+
+        let expected = 4.0 * 3.0 + 2.0 * (10f64.log2() + 2.0) + (22f64.log2() + 2.0);
+        assert!((passphrase.entropy_bits - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_entropy_estimate() {
+        let wordlist =
+            Wordlist::parse(&(0..7776).map(|i| format!("w{i}\n")).collect::<String>()).unwrap();
+        let options = PassphraseOptions::default();
+        assert!((options.entropy_bits(&wordlist) - 77.55).abs() < 0.01);
+
+        let random_case = PassphraseOptions {
+            case: WordCase::Random,
+            ..options
+        };
+        assert!((random_case.entropy_bits(&wordlist) - 83.55).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        let options = PassphraseOptions {
+            word_count: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            generate(&wordlist(), &options),
+            Err(GeneratorError::InvalidPassphraseOptions(_))
+        ));
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::crypto::secure_data::SecureData;
+use crate::kdbx::generator::charset::{CharClass, CharSet, LOOK_ALIKE};
+use crate::kdbx::generator::{
+    into_secure_data, pattern, random_index, shuffle, GeneratorError, MAX_PASSWORD_LENGTH,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GeneratorType {
+    /// 从字符集中随机选取
+    #[default]
+    CharSet,
+    /// 按KeePass密码模式生成
+    Pattern,
+}
+
+/// 密码生成配置
+///
+/// 字段名与KeePass的 `PwProfile` 一致, `MinimumCounts` 为扩展字段。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "Profile")]
+pub struct PasswordProfile {
+    #[serde(rename = "GeneratorType", default)]
+    pub generator_type: GeneratorType,
+    /// 仅用于字符集模式
+    #[serde(rename = "Length")]
+    pub length: usize,
+    #[serde(rename = "CharSetRanges", with = "char_set_ranges")]
+    pub char_classes: BTreeSet<CharClass>,
+    /// 额外加入字符集的字符
+    #[serde(rename = "CharSetAdditional", default)]
+    pub additional: String,
+    #[serde(rename = "Pattern", default)]
+    pub pattern: String,
+    /// 生成后打乱模式中各字符的顺序
+    #[serde(rename = "PatternPermutePassword", default)]
+    pub permute_pattern: bool,
+    #[serde(rename = "ExcludeLookAlike", default)]
+    pub exclude_look_alike: bool,
+    #[serde(rename = "NoRepeatingCharacters", default)]
+    pub no_repeating_characters: bool,
+    #[serde(rename = "ExcludeCharacters", default)]
+    pub exclude: String,
+    /// 每个字符类别至少出现的次数, 仅用于字符集模式
+    #[serde(rename = "MinimumCounts", default, with = "minimum_counts")]
+    pub minimum_counts: BTreeMap<CharClass, usize>,
+}
+
+impl Default for PasswordProfile {
+    /// 与KeePass的默认配置一致: 20位大小写字母和数字
+    fn default() -> Self {
+        Self {
+            generator_type: GeneratorType::CharSet,
+            length: 20,
+            char_classes: BTreeSet::from([CharClass::Upper, CharClass::Lower, CharClass::Digits]),
+            additional: String::new(),
+            pattern: String::new(),
+            permute_pattern: false,
+            exclude_look_alike: false,
+            no_repeating_characters: false,
+            exclude: String::new(),
+            minimum_counts: BTreeMap::new(),
+        }
+    }
+}
+
+impl PasswordProfile {
+    pub fn generate(&self) -> Result<SecureData, GeneratorError> {
+        let chars = match self.generator_type {
+            GeneratorType::CharSet => self.generate_char_set()?,
+            GeneratorType::Pattern => self.generate_pattern()?,
+        };
+        Ok(into_secure_data(&chars))
+    }
+
+    fn apply_exclusions(&self, set: &mut CharSet) {
+        set.remove_str(&self.exclude);
+        if self.exclude_look_alike {
+            set.remove_str(LOOK_ALIKE);
+        }
+    }
+
+    fn generate_char_set(&self) -> Result<Zeroizing<Vec<char>>, GeneratorError> {
+        let mut set = CharSet::new();
+        for class in &self.char_classes {
+            set.add(&class.chars());
+        }
+        set.add_str(&self.additional);
+        self.apply_exclusions(&mut set);
+        if set.is_empty() {
+            return Err(GeneratorError::EmptyCharacterSet);
+        }
+
+        if self.length > MAX_PASSWORD_LENGTH {
+            return Err(GeneratorError::InvalidProfile(format!(
+                "length exceeds {}",
+                MAX_PASSWORD_LENGTH
+            )));
+        }
+        let required = self
+            .minimum_counts
+            .values()
+            .try_fold(0usize, |sum, count| sum.checked_add(*count))
+            .ok_or_else(|| GeneratorError::InvalidProfile("minimum counts overflow".into()))?;
+        if required > self.length {
+            return Err(GeneratorError::LengthTooShort {
+                length: self.length,
+                required,
+            });
+        }
+
+        let mut picker = Picker::new(self.no_repeating_characters);
+        for (class, count) in &self.minimum_counts {
+            if !self.char_classes.contains(class) {
+                return Err(GeneratorError::InvalidProfile(format!(
+                    "minimum count for disabled class {:?}",
+                    class
+                )));
+            }
+            let mut class_set = class.chars();
+            self.apply_exclusions(&mut class_set);
+            for _ in 0..*count {
+                picker.pick(&class_set)?;
+            }
+        }
+        while picker.chars.len() < self.length {
+            picker.pick(&set)?;
+        }
+
+        shuffle(&mut picker.chars)?;
+        Ok(picker.chars)
+    }
+
+    fn generate_pattern(&self) -> Result<Zeroizing<Vec<char>>, GeneratorError> {
+        let mut picker = Picker::new(self.no_repeating_characters);
+        for mut position in pattern::parse(&self.pattern)? {
+            self.apply_exclusions(&mut position);
+            picker.pick(&position)?;
+        }
+        if self.permute_pattern {
+            shuffle(&mut picker.chars)?;
+        }
+        Ok(picker.chars)
+    }
+}
+
+/// 从字符集中随机选取字符, 可选地禁止重复
+struct Picker {
+    chars: Zeroizing<Vec<char>>,
+    no_repeat: bool,
+}
+
+impl Picker {
+    fn new(no_repeat: bool) -> Self {
+        Self {
+            chars: Zeroizing::new(Vec::new()),
+            no_repeat,
+        }
+    }
+
+    fn pick(&mut self, set: &CharSet) -> Result<(), GeneratorError> {
+        if set.is_empty() {
+            return Err(GeneratorError::EmptyCharacterSet);
+        }
+        let mut candidates = set.to_vec();
+        if self.no_repeat {
+            candidates.retain(|c| !self.chars.contains(c));
+            if candidates.is_empty() {
+                return Err(GeneratorError::NotEnoughCharacters);
+            }
+        }
+        let c = candidates[random_index(candidates.len())?];
+        self.chars.push(c);
+        Ok(())
+    }
+}
+
+/// KeePass 格式的字符集编码, 例如 `ULD_______`
+mod char_set_ranges {
+    use std::collections::BTreeSet;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::kdbx::generator::charset::CharClass;
+
+    pub fn serialize<S: Serializer>(
+        classes: &BTreeSet<CharClass>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let value: String = CharClass::ALL
+            .iter()
+            .map(|class| {
+                if classes.contains(class) {
+                    class.code()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        serializer.serialize_str(&value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeSet<CharClass>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Ok(value.chars().filter_map(CharClass::from_code).collect())
+    }
+}
+
+/// 最少字符数编码, 例如 `U2,D1`
+mod minimum_counts {
+    use std::collections::BTreeMap;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::kdbx::generator::charset::CharClass;
+
+    pub fn serialize<S: Serializer>(
+        counts: &BTreeMap<CharClass, usize>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let value = counts
+            .iter()
+            .map(|(class, count)| format!("{}{}", class.code(), count))
+            .collect::<Vec<_>>()
+            .join(",");
+        serializer.serialize_str(&value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<CharClass, usize>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value
+            .split(',')
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                let mut chars = item.chars();
+                let class = chars
+                    .next()
+                    .and_then(CharClass::from_code)
+                    .ok_or_else(|| D::Error::custom(format!("Invalid class: {}", item)))?;
+                let count = chars
+                    .as_str()
+                    .parse()
+                    .map_err(|_| D::Error::custom(format!("Invalid count: {}", item)))?;
+                Ok((class, count))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::generator::charset::{DIGITS, SPECIAL, UPPER_CASE};
+
+    fn generate_string(profile: &PasswordProfile) -> String {
+        let data = profile.generate().unwrap();
+        String::from_utf8(data.unsecure().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_default_profile() {
+        let password = generate_string(&PasswordProfile::default());
+        assert_eq!(password.chars().count(), 20);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn test_minimum_counts() {
+        let profile = PasswordProfile {
+            length: 8,
+            char_classes: BTreeSet::from([CharClass::Lower, CharClass::Digits, CharClass::Special]),
+            minimum_counts: BTreeMap::from([(CharClass::Digits, 3), (CharClass::Special, 2)]),
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            let password = generate_string(&profile);
+            assert_eq!(password.chars().count(), 8);
+            assert!(password.chars().filter(|c| DIGITS.contains(*c)).count() >= 3);
+            assert!(password.chars().filter(|c| SPECIAL.contains(*c)).count() >= 2);
+        }
+
+        let too_short = PasswordProfile {
+            length: 4,
+            ..profile.clone()
+        };
+        assert!(matches!(
+            too_short.generate(),
+            Err(GeneratorError::LengthTooShort {
+                length: 4,
+                required: 5
+            })
+        ));
+
+        let disabled = PasswordProfile {
+            minimum_counts: BTreeMap::from([(CharClass::Upper, 1)]),
+            ..profile
+        };
+        assert!(matches!(
+            disabled.generate(),
+            Err(GeneratorError::InvalidProfile(_))
+        ));
+    }
+
+    #[test]
+    fn test_length_limits() {
+        let too_long = PasswordProfile {
+            length: 1_000_000_000_000,
+            ..Default::default()
+        };
+        assert!(matches!(
+            too_long.generate(),
+            Err(GeneratorError::InvalidProfile(_))
+        ));
+
+        let overflow = PasswordProfile {
+            char_classes: BTreeSet::from([CharClass::Lower, CharClass::Digits]),
+            minimum_counts: BTreeMap::from([
+                (CharClass::Lower, usize::MAX),
+                (CharClass::Digits, 1),
+            ]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            overflow.generate(),
+            Err(GeneratorError::InvalidProfile(_))
+        ));
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let profile = PasswordProfile {
+            length: 200,
+            char_classes: BTreeSet::from([CharClass::Upper, CharClass::Digits]),
+            additional: "|".to_string(),
+            exclude_look_alike: true,
+            exclude: "ABC".to_string(),
+            ..Default::default()
+        };
+        let password = generate_string(&profile);
+        assert!(!password.contains(['O', '0', 'I', '1', '|', 'A', 'B', 'C']));
+
+        let empty = PasswordProfile {
+            char_classes: BTreeSet::from([CharClass::Digits]),
+            exclude: DIGITS.to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            empty.generate(),
+            Err(GeneratorError::EmptyCharacterSet)
+        ));
+    }
+
+    #[test]
+    fn test_no_repeating_characters() {
+        let profile = PasswordProfile {
+            length: 26,
+            char_classes: BTreeSet::from([CharClass::Upper]),
+            no_repeating_characters: true,
+            ..Default::default()
+        };
+        let mut chars: Vec<char> = generate_string(&profile).chars().collect();
+        chars.sort();
+        assert_eq!(chars.into_iter().collect::<String>(), UPPER_CASE);
+
+        let too_long = PasswordProfile {
+            length: 27,
+            ..profile
+        };
+        assert!(matches!(
+            too_long.generate(),
+            Err(GeneratorError::NotEnoughCharacters)
+        ));
+    }
+
+    #[test]
+    fn test_pattern() {
+        let profile = PasswordProfile {
+            generator_type: GeneratorType::Pattern,
+            pattern: r"uu\-d{4}[\a\b]".to_string(),
+            ..Default::default()
+        };
+        let password = generate_string(&profile);
+        let chars: Vec<char> = password.chars().collect();
+        assert_eq!(chars.len(), 8);
+        assert!(chars[0..2].iter().all(|c| c.is_ascii_uppercase()));
+        assert_eq!(chars[2], '-');
+        assert!(chars[3..7].iter().all(|c| c.is_ascii_digit()));
+        assert!(chars[7] == 'a' || chars[7] == 'b');
+
+        let permuted = PasswordProfile {
+            pattern: "d{10}u".to_string(),
+            permute_pattern: true,
+            ..profile
+        };
+        let password = generate_string(&permuted);
+        assert_eq!(password.chars().filter(|c| c.is_ascii_digit()).count(), 10);
+        assert_eq!(
+            password.chars().filter(|c| c.is_ascii_uppercase()).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_profile_xml() {
+        let profile = PasswordProfile {
+            char_classes: BTreeSet::from([CharClass::Upper, CharClass::Minus, CharClass::HighAnsi]),
+            minimum_counts: BTreeMap::from([(CharClass::Upper, 2), (CharClass::Minus, 1)]),
+            exclude: "<&>".to_string(),
+            ..Default::default()
+        };
+        let xml = quick_xml::se::to_string(&profile).unwrap();
+        assert!(xml.contains("<CharSetRanges>U____m___H</CharSetRanges>"));
+        assert!(xml.contains("<MinimumCounts>U2,m1</MinimumCounts>"));
+        assert_eq!(
+            quick_xml::de::from_str::<PasswordProfile>(&xml).unwrap(),
+            profile
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use crate::kdbx::generator::charset::{
+    CharClass, CharSet, BRACKETS, DIGITS, LOWER_CASE, PRINTABLE_SPECIAL, PUNCTUATION, UPPER_CASE,
+};
+use crate::kdbx::generator::{GeneratorError, MAX_PASSWORD_LENGTH};
+
+/// `{n}`允许的最大重复次数
+const MAX_REPEAT: usize = 1024;
+
+/// 模式占位符对应的字符集, 无法识别时返回None
+///
+/// <https://keepass.info/help/base/pwgenerator.html#pattern>
+fn placeholder(c: char) -> Option<CharSet> {
+    let mut set = CharSet::new();
+    match c {
+        'a' => set.add_str(&format!("{LOWER_CASE}{DIGITS}")),
+        'A' => set.add_str(&format!("{UPPER_CASE}{LOWER_CASE}{DIGITS}")),
+        'U' => set.add_str(&format!("{UPPER_CASE}{DIGITS}")),
+        'd' => set.add_str(DIGITS),
+        'h' => set.add_str("0123456789abcdef"),
+        'H' => set.add_str("0123456789ABCDEF"),
+        'l' => set.add_str(LOWER_CASE),
+        'L' => set.add_str(&format!("{UPPER_CASE}{LOWER_CASE}")),
+        'u' => set.add_str(UPPER_CASE),
+        's' => set.add_str(PRINTABLE_SPECIAL),
+        'S' => set.add_str(&format!(
+            "{UPPER_CASE}{LOWER_CASE}{DIGITS}{PRINTABLE_SPECIAL}"
+        )),
+        'v' => set.add_str("aeiou"),
+        'V' => set.add_str("AEIOUaeiou"),
+        'Z' => set.add_str("AEIOU"),
+        'c' => set.add_str("bcdfghjklmnpqrstvwxyz"),
+        'C' => set.add_str("BCDFGHJKLMNPQRSTVWXYZbcdfghjklmnpqrstvwxyz"),
+        'z' => set.add_str("BCDFGHJKLMNPQRSTVWXYZ"),
+        'p' => set.add_str(PUNCTUATION),
+        'b' => set.add_str(BRACKETS),
+        'x' => set.add(&CharClass::HighAnsi.chars()),
+        _ => return None,
+    }
+    Some(set)
+}
+
+/// 解析KeePass密码模式, 返回每个位置可选的字符集
+///
+/// - 占位符 (例如 `u` `l` `d` `s`) 表示对应字符集中的一个字符
+/// - `\x` 输出字符 `x` 本身
+/// - `{n}` 将前一个元素重复 `n` 次
+/// - `[...]` 自定义字符集, 可包含占位符和转义字符, `^` 之后的字符从集合中移除
+/// - 其他字符按字面输出
+pub fn parse(pattern: &str) -> Result<Vec<CharSet>, GeneratorError> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut positions: Vec<CharSet> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' => {
+                let literal = chars.get(i + 1).ok_or(GeneratorError::InvalidPattern(i))?;
+                positions.push(CharSet::from(literal.to_string().as_str()));
+                i += 2;
+            }
+            '[' => {
+                let (set, end) = parse_custom_set(&chars, i)?;
+                positions.push(set);
+                i = end + 1;
+            }
+            '{' => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|c| *c == '}')
+                    .ok_or(GeneratorError::InvalidPattern(i))?
+                    + i;
+                let count: usize = chars[i + 1..end]
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .map_err(|_| GeneratorError::InvalidPattern(i))?;
+                let last = positions.pop().ok_or(GeneratorError::InvalidPattern(i))?;
+                if count > MAX_REPEAT || positions.len() + count > MAX_PASSWORD_LENGTH {
+                    return Err(GeneratorError::InvalidPattern(i));
+                }
+                for _ in 0..count {
+                    positions.push(last.clone());
+                }
+                i = end + 1;
+            }
+            c => {
+                positions
+                    .push(placeholder(c).unwrap_or_else(|| CharSet::from(c.to_string().as_str())));
+                i += 1;
+            }
+        }
+        if positions.len() > MAX_PASSWORD_LENGTH {
+            return Err(GeneratorError::InvalidPattern(i));
+        }
+    }
+    Ok(positions)
+}
+
+/// 解析从 `start` 处的 `[` 开始的自定义字符集, 返回字符集和 `]` 的位置
+fn parse_custom_set(chars: &[char], start: usize) -> Result<(CharSet, usize), GeneratorError> {
+    let mut set = CharSet::new();
+    let mut excluded = CharSet::new();
+    let mut exclude = false;
+    let mut i = start + 1;
+
+    loop {
+        let c = *chars.get(i).ok_or(GeneratorError::InvalidPattern(start))?;
+        let target = if exclude { &mut excluded } else { &mut set };
+        match c {
+            ']' => break,
+            '^' => exclude = true,
+            '\\' => {
+                i += 1;
+                let literal = chars.get(i).ok_or(GeneratorError::InvalidPattern(start))?;
+                target.add_str(&literal.to_string());
+            }
+            c => match placeholder(c) {
+                Some(placeholder) => target.add(&placeholder),
+                None => target.add_str(&c.to_string()),
+            },
+        }
+        i += 1;
+    }
+
+    for c in excluded.to_vec() {
+        set.remove(c);
+    }
+    Ok((set, i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholders_and_repeat() {
+        let positions = parse("uld{3}s").unwrap();
+        assert_eq!(positions.len(), 6);
+        assert_eq!(positions[0], CharSet::from(UPPER_CASE));
+        assert_eq!(positions[1], CharSet::from(LOWER_CASE));
+        assert!(positions[2..5].iter().all(|p| *p == CharSet::from(DIGITS)));
+        assert_eq!(positions[5], CharSet::from(PRINTABLE_SPECIAL));
+    }
+
+    #[test]
+    fn test_literals() {
+        let positions = parse(r"\u-#d").unwrap();
+        assert_eq!(positions[0], CharSet::from("u"));
+        assert_eq!(positions[1], CharSet::from("-"));
+        assert_eq!(positions[2], CharSet::from("#"));
+        assert_eq!(positions[3], CharSet::from(DIGITS));
+    }
+
+    #[test]
+    fn test_custom_set() {
+        let positions = parse(r"[d\u^13]{2}[*+]").unwrap();
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions[0], CharSet::from("02456789u"));
+        assert_eq!(positions[1], positions[0]);
+        assert_eq!(positions[2], CharSet::from("*+"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(matches!(
+            parse("[ab"),
+            Err(GeneratorError::InvalidPattern(0))
+        ));
+        assert!(matches!(
+            parse("{3}"),
+            Err(GeneratorError::InvalidPattern(0))
+        ));
+        assert!(matches!(
+            parse("d{x}"),
+            Err(GeneratorError::InvalidPattern(1))
+        ));
+        assert!(matches!(
+            parse("d{3"),
+            Err(GeneratorError::InvalidPattern(1))
+        ));
+        assert!(matches!(
+            parse("d\\"),
+            Err(GeneratorError::InvalidPattern(1))
+        ));
+    }
+
+    #[test]
+    fn test_repeat_limits() {
+        assert_eq!(parse("d{1024}").unwrap().len(), MAX_REPEAT);
+        assert!(matches!(
+            parse("d{1000000000}"),
+            Err(GeneratorError::InvalidPattern(1))
+        ));
+        assert!(matches!(
+            parse(&"d{1024}".repeat(5)),
+            Err(GeneratorError::InvalidPattern(29))
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::Utc;
+
+use crate::kdbx::generator::password::PasswordProfile;
+use crate::kdbx::generator::GeneratorError;
+use crate::kdbx::xml::entities::custom_data::{CustomData, Item};
+use crate::kdbx::xml::entities::Meta;
+
+/// 保存在 `Meta.custom_data` 中的密码生成配置的键前缀, 键的剩余部分为配置名称
+pub const PROFILE_KEY_PREFIX: &str = "KeePassOne.PasswordProfile.";
+
+/// 读取数据库中保存的所有密码生成配置, 按名称排序
+pub fn load_profiles(meta: &Meta) -> Result<Vec<(String, PasswordProfile)>, GeneratorError> {
+    let Some(custom_data) = &meta.custom_data else {
+        return Ok(Vec::new());
+    };
+    let mut profiles = custom_data
+        .item
+        .iter()
+        .filter_map(|item| {
+            let name = item.key.strip_prefix(PROFILE_KEY_PREFIX)?;
+            Some(
+                quick_xml::de::from_str::<PasswordProfile>(&item.value)
+                    .map(|profile| (name.to_string(), profile)),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    profiles.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(profiles)
+}
+
+pub fn load_profile(meta: &Meta, name: &str) -> Result<Option<PasswordProfile>, GeneratorError> {
+    let key = format!("{PROFILE_KEY_PREFIX}{name}");
+    let Some(item) = meta
+        .custom_data
+        .as_ref()
+        .and_then(|data| data.item.iter().find(|item| item.key == key))
+    else {
+        return Ok(None);
+    };
+    Ok(Some(quick_xml::de::from_str(&item.value)?))
+}
+
+/// 保存密码生成配置, 同名配置会被覆盖
+pub fn save_profile(
+    meta: &mut Meta,
+    name: &str,
+    profile: &PasswordProfile,
+) -> Result<(), GeneratorError> {
+    if name.is_empty() {
+        return Err(GeneratorError::InvalidProfile("empty name".to_string()));
+    }
+    let key = format!("{PROFILE_KEY_PREFIX}{name}");
+    let value = quick_xml::se::to_string(profile)?;
+    let now = Utc::now().into();
+
+    let custom_data = meta.custom_data.get_or_insert_with(CustomData::default);
+    match custom_data.item.iter_mut().find(|item| item.key == key) {
+        Some(item) => {
+            item.value = value;
+            item.last_modification_time = now;
+        }
+        None => custom_data.item.push(Item {
+            key,
+            value,
+            last_modification_time: now,
+        }),
+    }
+    Ok(())
+}
+
+/// 删除密码生成配置, 返回配置是否存在
+pub fn remove_profile(meta: &mut Meta, name: &str) -> bool {
+    let key = format!("{PROFILE_KEY_PREFIX}{name}");
+    let Some(custom_data) = &mut meta.custom_data else {
+        return false;
+    };
+    let len = custom_data.item.len();
+    custom_data.item.retain(|item| item.key != key);
+    custom_data.item.len() != len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::generator::password::GeneratorType;
+    use crate::kdbx::xml::fixtures;
+
+    #[test]
+    fn test_profiles_in_meta() {
+        let mut database =
+            fixtures::database(&fixtures::group_xml(&fixtures::uuid(1), "Root", "", ""));
+        let meta = &mut database.document.meta;
+        assert!(load_profiles(meta).unwrap().is_empty());
+
+        let pin = PasswordProfile {
+            generator_type: GeneratorType::Pattern,
+            pattern: "d{6}".to_string(),
+            ..Default::default()
+        };
+        save_profile(meta, "PIN", &pin).unwrap();
+        save_profile(meta, "Default", &PasswordProfile::default()).unwrap();
+        let long = PasswordProfile {
+            length: 40,
+            ..Default::default()
+        };
+        save_profile(meta, "Default", &long).unwrap();
+        assert!(save_profile(meta, "", &long).is_err());
+
+        let profiles = load_profiles(meta).unwrap();
+        assert_eq!(
+            profiles,
+            vec![
+                ("Default".to_string(), long),
+                ("PIN".to_string(), pin.clone())
+            ]
+        );
+        assert_eq!(load_profile(meta, "PIN").unwrap(), Some(pin));
+        assert_eq!(load_profile(meta, "Missing").unwrap(), None);
+
+        assert!(remove_profile(meta, "PIN"));
+        assert!(!remove_profile(meta, "PIN"));
+        assert_eq!(load_profiles(meta).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::kdbx::generator::GeneratorError;
+use crate::kdbx::xml::database::KeePassDatabase;
+use crate::kdbx::xml::entities::Entry;
+
+/// 生成口令短语至少需要的单词数量
+const MIN_WORDS: usize = 2;
+
+/// 口令短语单词表
+///
+/// 支持两种格式:
+/// - Diceware/EFF格式, 每行为骰子编号和单词, 例如 `11111\tabacus`
+/// - 每行一个单词
+///
+/// 空行和以 `#` 开头的行会被忽略, 重复的单词只保留一个。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wordlist {
+    words: Vec<String>,
+}
+
+impl Wordlist {
+    pub fn parse(text: &str) -> Result<Self, GeneratorError> {
+        let mut seen = HashSet::new();
+        let mut words = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let word = match line.split_once(char::is_whitespace) {
+                Some((index, word)) if index.chars().all(|c| c.is_ascii_digit()) => word.trim(),
+                _ => line,
+            };
+            if word.chars().any(char::is_whitespace) {
+                return Err(GeneratorError::InvalidWordlist(format!(
+                    "word contains whitespace: {}",
+                    word
+                )));
+            }
+            if seen.insert(word) {
+                words.push(word.to_string());
+            }
+        }
+
+        if words.len() < MIN_WORDS {
+            return Err(GeneratorError::InvalidWordlist(format!(
+                "at least {} words are required",
+                MIN_WORDS
+            )));
+        }
+        Ok(Self { words })
+    }
+
+    /// EFF大单词表, 7776个单词, 对应五枚骰子
+    ///
+    /// <https://www.eff.org/dice>
+    #[cfg(feature = "eff-wordlists")]
+    pub fn eff_large() -> Self {
+        Self::parse(include_str!("data/eff_large_wordlist.txt")).expect("bundled EFF wordlist")
+    }
+
+    /// EFF短单词表, 1296个单词, 对应四枚骰子
+    #[cfg(feature = "eff-wordlists")]
+    pub fn eff_short() -> Self {
+        Self::parse(include_str!("data/eff_short_wordlist_1.txt")).expect("bundled EFF wordlist")
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, GeneratorError> {
+        let text = std::str::from_utf8(data)
+            .map_err(|_| GeneratorError::InvalidWordlist("not valid UTF-8".to_string()))?;
+        Self::parse(text)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, GeneratorError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// 从条目附件中加载单词表
+    pub fn from_attachment(
+        database: &KeePassDatabase,
+        entry: &Entry,
+        name: &str,
+    ) -> Result<Self, GeneratorError> {
+        let data = database
+            .get_attachment(entry, name)
+            .ok_or_else(|| GeneratorError::AttachmentNotFound(name.to_string()))?;
+        Self::from_bytes(data)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn word(&self, index: usize) -> &str {
+        &self.words[index]
+    }
+
+    /// 每个单词提供的熵 (比特)
+    pub fn bits_per_word(&self) -> f64 {
+        (self.words.len() as f64).log2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diceware_format() {
+        let wordlist =
+            Wordlist::parse("# EFF\n11111\tabacus\n11112 abdomen\n\n11113\tabdominal\n").unwrap();
+        assert_eq!(wordlist.len(), 3);
+        assert_eq!(wordlist.word(0), "abacus");
+        assert_eq!(wordlist.word(1), "abdomen");
+        assert_eq!(wordlist.word(2), "abdominal");
+    }
+
+    #[test]
+    fn test_parse_plain_format() {
+        let wordlist = Wordlist::parse("correct\r\nhorse\nbattery\nstaple\nhorse\n").unwrap();
+        assert_eq!(wordlist.len(), 4);
+        assert_eq!(wordlist.bits_per_word(), 2.0);
+    }
+
+    #[test]
+    fn test_invalid_wordlists() {
+        assert!(matches!(
+            Wordlist::parse("only\nonly\n"),
+            Err(GeneratorError::InvalidWordlist(_))
+        ));
+        assert!(matches!(
+            Wordlist::parse("two words\nthree more words\n"),
+            Err(GeneratorError::InvalidWordlist(_))
+        ));
+        assert!(matches!(
+            Wordlist::from_bytes(&[0xff, 0xfe, b'\n', b'a']),
+            Err(GeneratorError::InvalidWordlist(_))
+        ));
+    }
+
+    #[cfg(feature = "eff-wordlists")]
+    #[test]
+    fn test_eff_wordlists() {
+        let large = Wordlist::eff_large();
+        assert_eq!(large.len(), 7776);
+        assert_eq!(large.words.iter().collect::<HashSet<_>>().len(), 7776);
+        assert!((large.bits_per_word() - 12.925).abs() < 0.001);
+
+        let short = Wordlist::eff_short();
+        assert_eq!(short.len(), 1296);
+        assert_eq!(short.words.iter().collect::<HashSet<_>>().len(), 1296);
+        assert!((short.bits_per_word() - 10.340).abs() < 0.001);
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::kdbx::strength::{self, is_quality_checked};
+use crate::kdbx::xml::database::KeePassDatabase;
+use crate::kdbx::xml::entities::{Entry, Group, FIELD_PASSWORD};
+use crate::kdbx::xml::errors::KdbxDatabaseError;
+
+#[derive(Debug, Error)]
+pub enum HealthError {
+    #[error("Random number generator error")]
+    RandomError(#[from] getrandom::Error),
+
+    #[error("Database error")]
+    DatabaseError(#[from] KdbxDatabaseError),
+}
+
+/// KeePassXC 用于标记 "不参与数据库报告" 的条目自定义数据
+pub const KNOWN_BAD_KEY: &str = "KnownBad";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthOptions {
+    pub now: DateTime<Utc>,
+    /// 密码超过该天数未修改时报告, None表示不检查
+    pub max_password_age_days: Option<i64>,
+    /// 在过期前该天数内报告即将过期
+    pub expiry_warning_days: i64,
+    /// 强度评分低于该值的密码视为弱密码 (0 - 4)
+    pub weak_score: u8,
+}
+
+impl Default for HealthOptions {
+    fn default() -> Self {
+        Self {
+            now: Utc::now(),
+            max_password_age_days: Some(365),
+            expiry_warning_days: 14,
+            weak_score: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthIssue {
+    NoPassword,
+    Weak {
+        score: u8,
+        entropy_bits: f64,
+    },
+    /// 与其他 `other_entries` 个条目使用相同的密码
+    Reused {
+        other_entries: usize,
+    },
+    /// 密码已有 `days` 天未修改
+    Old {
+        days: i64,
+    },
+    /// 已过期 `days` 天
+    Expired {
+        days: i64,
+    },
+    /// 将在 `days` 天后过期
+    ExpiringSoon {
+        days: i64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryHealth {
+    pub uuid: Uuid,
+    pub issues: Vec<HealthIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HealthReport {
+    /// 存在问题的条目, 按数据库中的顺序排列
+    pub entries: Vec<EntryHealth>,
+    /// 使用相同密码的条目组
+    pub reused_groups: Vec<Vec<Uuid>>,
+    /// 参与检查的条目数量
+    pub checked: usize,
+    /// 因 `QualityCheck=False` 或 `KnownBad` 被排除的条目数量
+    pub excluded: usize,
+}
+
+/// 条目是否被排除在数据库报告之外
+pub fn is_excluded(entry: &Entry) -> bool {
+    !is_quality_checked(entry)
+        || entry
+            .get_custom_data(KNOWN_BAD_KEY)
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+impl KeePassDatabase {
+    /// 检查数据库中所有条目的密码健康状况, 回收站中的条目和历史记录不参与检查
+    pub fn health_report(
+        &self,
+        options: &HealthOptions,
+    ) -> Result<HealthReport, HealthError> {
+        let mut report = HealthReport::default();
+
+        // 相同的密码通过随机密钥的HMAC比较, 报告中不包含密码或哈希
+        let mut key = Zeroizing::new([0u8; 32]);
+        getrandom::fill(key.as_mut())?;
+        let mut by_hash: HashMap<[u8; 32], Vec<usize>> = HashMap::new();
+
+        let entries = self.active_entries();
+        let mut issues: Vec<Vec<HealthIssue>> = Vec::with_capacity(entries.len());
+
+        for (index, entry) in entries.iter().enumerate() {
+            let mut entry_issues = Vec::new();
+            if is_excluded(entry) {
+                report.excluded += 1;
+                issues.push(entry_issues);
+                continue;
+            }
+            report.checked += 1;
+
+            let password = match entry.get_value(FIELD_PASSWORD) {
+                Some(value) => Zeroizing::new(self.get_value_string(value)?),
+                None => Zeroizing::new(String::new()),
+            };
+
+            if password.is_empty() {
+                entry_issues.push(HealthIssue::NoPassword);
+            } else {
+                let strength = strength::estimate(&password);
+                if strength.score < options.weak_score {
+                    entry_issues.push(HealthIssue::Weak {
+                        score: strength.score,
+                        entropy_bits: strength.entropy_bits,
+                    });
+                }
+
+                let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key.as_ref())
+                    .expect("HMAC accepts any key length");
+                mac.update(password.as_bytes());
+                by_hash
+                    .entry(mac.finalize().into_bytes().into())
+                    .or_default()
+                    .push(index);
+
+                if let Some(max_days) = options.max_password_age_days {
+                    if let Some(changed) = self.password_changed_at(entry)? {
+                        let days = (options.now - changed).num_days();
+                        if days > max_days {
+                            entry_issues.push(HealthIssue::Old { days });
+                        }
+                    }
+                }
+            }
+
+            if entry.times.expires.value() {
+                if let Some(expiry) = entry.times.expiry_time.value() {
+                    if expiry <= options.now {
+                        entry_issues.push(HealthIssue::Expired {
+                            days: (options.now - expiry).num_days(),
+                        });
+                    } else if expiry - options.now <= Duration::days(options.expiry_warning_days) {
+                        entry_issues.push(HealthIssue::ExpiringSoon {
+                            days: (expiry - options.now).num_days(),
+                        });
+                    }
+                }
+            }
+            issues.push(entry_issues);
+        }
+
+        let mut groups: Vec<Vec<usize>> = by_hash
+            .into_values()
+            .filter(|indexes| indexes.len() > 1)
+            .collect();
+        groups.sort();
+        for group in &groups {
+            for index in group {
+                issues[*index].push(HealthIssue::Reused {
+                    other_entries: group.len() - 1,
+                });
+            }
+        }
+        report.reused_groups = groups
+            .iter()
+            .map(|group| group.iter().map(|i| *entries[*i].uuid.value()).collect())
+            .collect();
+
+        report.entries = entries
+            .iter()
+            .zip(issues)
+            .filter(|(_, issues)| !issues.is_empty())
+            .map(|(entry, issues)| EntryHealth {
+                uuid: *entry.uuid.value(),
+                issues,
+            })
+            .collect();
+        Ok(report)
+    }
+
+    /// 除回收站外的所有条目
+    pub(crate) fn active_entries(&self) -> Vec<&Entry> {
+        let meta = &self.document.meta;
+        let recycle_bin = meta
+            .recycle_bin_uuid
+            .value()
+            .filter(|uuid| meta.recycle_bin_enabled.value() && !uuid.is_nil());
+
+        fn collect<'a>(group: &'a Group, recycle_bin: Option<&Uuid>, entries: &mut Vec<&'a Entry>) {
+            if recycle_bin == Some(group.uuid.value()) {
+                return;
+            }
+            entries.extend(group.entry.iter());
+            for child in &group.group {
+                collect(child, recycle_bin, entries);
+            }
+        }
+
+        let mut entries = Vec::new();
+        collect(&self.document.root.group, recycle_bin, &mut entries);
+        entries
+    }
+
+    /// 根据历史记录推算当前密码的设置时间
+    ///
+    /// 取当前密码连续未变的最早版本的修改时间; 所有历史版本密码都相同时使用条目的创建时间,
+    /// 没有历史记录时使用条目的最后修改时间。
+    fn password_changed_at(
+        &self,
+        entry: &Entry,
+    ) -> Result<Option<DateTime<Utc>>, KdbxDatabaseError> {
+        let current = self.password_of(entry)?;
+        let history: Vec<&Entry> = entry
+            .history
+            .as_ref()
+            .map(|history| history.entry.iter().collect())
+            .unwrap_or_default();
+        if history.is_empty() {
+            return Ok(entry.times.last_modification_time.value());
+        }
+
+        // 历史记录按时间从旧到新排列
+        let mut changed_at = entry.times.last_modification_time.value();
+        for version in history.iter().rev() {
+            if *self.password_of(version)? != *current {
+                return Ok(changed_at);
+            }
+            changed_at = version.times.last_modification_time.value();
+        }
+        Ok(entry.times.creation_time.value().or(changed_at))
+    }
+
+    fn password_of(&self, entry: &Entry) -> Result<Zeroizing<String>, KdbxDatabaseError> {
+        Ok(Zeroizing::new(match entry.get_value(FIELD_PASSWORD) {
+            Some(value) => self.get_value_string(value)?,
+            None => String::new(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::kdbx::xml::fixtures::{self, uuid_value};
+
+    const STRONG: &str = "rWibMFACxAUGZmxhVncy";
+
+    fn options() -> HealthOptions {
+        HealthOptions {
+            now: Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn issues(report: &HealthReport, n: u8) -> Vec<HealthIssue> {
+        report
+            .entries
+            .iter()
+            .find(|entry| entry.uuid == uuid_value(n))
+            .map(|entry| entry.issues.clone())
+            .unwrap_or_default()
+    }
+
+    fn entry(n: u8, times: &str, password: Option<&str>, extra: &str) -> String {
+        let fields: Vec<(&str, &str)> = password.map(|p| ("Password", p)).into_iter().collect();
+        fixtures::entry_xml_with_times(&fixtures::uuid(n), times, &fields, extra)
+    }
+
+    fn recent() -> String {
+        fixtures::times_xml("2024-05-01T00:00:00Z", "2024-05-01T00:00:00Z", None)
+    }
+
+    fn test_database() -> KeePassDatabase {
+        let ok = entry(1, &recent(), Some(STRONG), "");
+        let reused_a = entry(2, &recent(), Some("Shared#Secret-2024!x"), "");
+        let reused_b = entry(3, &recent(), Some("Shared#Secret-2024!x"), "");
+        let weak = entry(4, &recent(), Some("password1"), "");
+        let empty = entry(5, &recent(), None, "");
+        let expired = entry(
+            6,
+            &fixtures::times_xml(
+                "2024-05-01T00:00:00Z",
+                "2024-05-01T00:00:00Z",
+                Some("2024-05-20T00:00:00Z"),
+            ),
+            Some("Hp3&zK8!qLw5#Tn0$bVy"),
+            "",
+        );
+        let expiring = entry(
+            7,
+            &fixtures::times_xml(
+                "2024-05-01T00:00:00Z",
+                "2024-05-01T00:00:00Z",
+                Some("2024-06-11T00:00:00Z"),
+            ),
+            Some("jQ7#vLp2@xN9!mZr4$kW"),
+            "",
+        );
+        // 密码在2022年设置, 之后只修改了其他字段
+        let old_history = format!(
+            "<History>{}{}</History>",
+            entry(
+                8,
+                &fixtures::times_xml("2020-01-01T00:00:00Z", "2021-01-01T00:00:00Z", None),
+                Some("Old-Password-Value-1!"),
+                ""
+            ),
+            entry(
+                8,
+                &fixtures::times_xml("2020-01-01T00:00:00Z", "2022-01-01T00:00:00Z", None),
+                Some("Qe8!tYz#4WmP1$vRx7&n"),
+                ""
+            ),
+        );
+        let old = entry(
+            8,
+            &fixtures::times_xml("2020-01-01T00:00:00Z", "2024-05-30T00:00:00Z", None),
+            Some("Qe8!tYz#4WmP1$vRx7&n"),
+            &old_history,
+        );
+        let quality_off = entry(
+            9,
+            &recent(),
+            Some("123456"),
+            "<QualityCheck>False</QualityCheck>",
+        );
+        let known_bad = entry(
+            10,
+            &recent(),
+            None,
+            "<CustomData><Item><Key>KnownBad</Key><Value>true</Value></Item></CustomData>",
+        );
+        let recycled = entry(11, &recent(), None, "");
+        let recycle_bin = fixtures::group_xml(&fixtures::uuid(21), "Recycle Bin", "", &recycled);
+
+        let mut database = fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(20),
+            "Root",
+            "",
+            &format!(
+                "{ok}{reused_a}{reused_b}{weak}{empty}{expired}{expiring}{old}{quality_off}{known_bad}{recycle_bin}"
+            ),
+        ));
+        database.document.meta.recycle_bin_uuid = Some(uuid_value(21)).into();
+        database
+    }
+
+    #[test]
+    fn test_health_report() {
+        let database = test_database();
+        let report = database.health_report(&options()).unwrap();
+
+        assert_eq!(report.checked, 8);
+        assert_eq!(report.excluded, 2);
+        assert_eq!(
+            report.reused_groups,
+            vec![vec![uuid_value(2), uuid_value(3)]]
+        );
+
+        assert!(issues(&report, 1).is_empty());
+        assert_eq!(
+            issues(&report, 2),
+            vec![HealthIssue::Reused { other_entries: 1 }]
+        );
+        assert!(matches!(
+            issues(&report, 4).as_slice(),
+            [HealthIssue::Weak { score: 0, .. }]
+        ));
+        assert_eq!(issues(&report, 5), vec![HealthIssue::NoPassword]);
+        assert_eq!(issues(&report, 6), vec![HealthIssue::Expired { days: 12 }]);
+        assert_eq!(
+            issues(&report, 7),
+            vec![HealthIssue::ExpiringSoon { days: 10 }]
+        );
+        assert_eq!(issues(&report, 8), vec![HealthIssue::Old { days: 882 }]);
+        assert!(issues(&report, 9).is_empty());
+        assert!(issues(&report, 10).is_empty());
+        assert!(issues(&report, 11).is_empty());
+    }
+
+    #[test]
+    fn test_password_age() {
+        let database = test_database();
+        let entries = database.document.root.group.all_entries();
+        let changed = |n: u8| {
+            let entry = entries
+                .iter()
+                .find(|e| *e.uuid.value() == uuid_value(n))
+                .unwrap();
+            database.password_changed_at(entry).unwrap()
+        };
+        assert_eq!(
+            changed(8),
+            Some(Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            changed(1),
+            Some(Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap())
+        );
+
+        let options = HealthOptions {
+            max_password_age_days: None,
+            ..options()
+        };
+        let report = database.health_report(&options).unwrap();
+        assert!(issues(&report, 8).is_empty());
+    }
+}
@@ -1,13 +1,14 @@
 use crate::crypto;
 use crate::crypto::hash::calculate_sha256;
+use crate::crypto::secure_data::SecureData;
+use crate::kdbx::challenge_response::{ChallengeResponseError, ChallengeResponseProvider};
 use base64::Engine;
-use generic_array::{typenum::U32, GenericArray};
 use secrecy::{ExposeSecret, SecretBox, SecretSlice};
 use std::io::Read;
 use sxd_document::parser;
 use sxd_xpath::evaluate_xpath;
 use thiserror::Error;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 #[derive(Debug, Error)]
 pub enum KdbxKeyError {
@@ -15,11 +16,26 @@ pub enum KdbxKeyError {
     NoKeyParts,
     #[error("Failed to parse key file. Ensure it is a valid key file format.")]
     FailedToParseKeyFile,
+    #[error("Key file is corrupted: the stored hash does not match the key data.")]
+    KeyFileHashMismatch,
+    #[error("Unsupported key file version: {0}")]
+    UnsupportedKeyFileVersion(String),
+    #[error("Key files of this format cannot be created.")]
+    UnsupportedKeyFileFormat,
+    #[error("Invalid key length for this key file format: {0}")]
+    InvalidKeyLength(usize),
+    #[error("Random number generator error")]
+    RandomError(#[from] getrandom::Error),
+    #[error("Challenge-response keys require the KDF seed as challenge.")]
+    ChallengeRequired,
+    #[error("Challenge-response error")]
+    ChallengeResponse(#[from] ChallengeResponseError),
 }
 
 pub struct KdbxKey {
     pub master_key: Option<SecretBox<String>>,
     pub key_file: Option<SecretSlice<u8>>,
+    pub challenge_response: Vec<Box<dyn ChallengeResponseProvider>>,
 }
 
 impl KdbxKey {
@@ -27,6 +43,7 @@ impl KdbxKey {
         KdbxKey {
             master_key: None,
             key_file: None,
+            challenge_response: Vec::new(),
         }
     }
 
@@ -41,80 +58,263 @@ impl KdbxKey {
         Ok(())
     }
 
+    pub fn add_challenge_response(&mut self, provider: Box<dyn ChallengeResponseProvider>) {
+        self.challenge_response.push(provider);
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.master_key.is_none() && self.key_file.is_none()
+        self.master_key.is_none() && self.key_file.is_none() && self.challenge_response.is_empty()
     }
 
-    pub fn calc_key_hash(&self) -> Result<GenericArray<u8, U32>, KdbxKeyError> {
-        let mut key_parts: Vec<Vec<u8>> = Vec::new();
+    /// 计算不含挑战-响应的复合密钥, 添加了挑战-响应密钥时需改用[`Self::calc_key_hash_with_challenge`]
+    pub fn calc_key_hash(&self) -> Result<SecureData, KdbxKeyError> {
+        if !self.challenge_response.is_empty() {
+            return Err(KdbxKeyError::ChallengeRequired);
+        }
+        self.calc_composite_key(None)
+    }
+
+    /// 以KDF的种子作为挑战计算复合密钥
+    ///
+    /// 与KeePassXC一致: 各响应的SHA-256追加在密码与密钥文件的哈希之后, 没有挑战-响应密钥时结果与[`Self::calc_key_hash`]相同
+    pub fn calc_key_hash_with_challenge(
+        &self,
+        challenge: &[u8],
+    ) -> Result<SecureData, KdbxKeyError> {
+        self.calc_composite_key(Some(challenge))
+    }
+
+    fn calc_composite_key(&self, challenge: Option<&[u8]>) -> Result<SecureData, KdbxKeyError> {
+        let mut key_parts: Zeroizing<Vec<Vec<u8>>> = Zeroizing::new(Vec::new());
 
         if let Some(ref master_key) = self.master_key {
-            let master_key_hash = calculate_sha256(master_key.expose_secret().as_bytes());
+            let mut master_key_hash = calculate_sha256(master_key.expose_secret().as_bytes());
             key_parts.push(master_key_hash.to_vec());
+            master_key_hash.as_mut_slice().zeroize();
         }
 
         if let Some(ref key_file_buf) = self.key_file {
-            key_parts.push(
-                parse_keyfile(key_file_buf.expose_secret())
+            let key_file = parse_keyfile(key_file_buf.expose_secret())?;
+            key_parts.push(key_file.key.to_vec());
+        }
+
+        if let Some(challenge) = challenge.filter(|_| !self.challenge_response.is_empty()) {
+            let mut responses = Vec::with_capacity(self.challenge_response.len());
+            for provider in &self.challenge_response {
+                responses.push(provider.challenge(challenge)?);
+            }
+            let mut response_hash = crypto::hash::calculate_sha256_multiple(
+                &responses
+                    .iter()
+                    .map(|r| r.as_slice())
+                    .collect::<Vec<&[u8]>>(),
             );
+            key_parts.push(response_hash.to_vec());
+            response_hash.as_mut_slice().zeroize();
         }
 
         if key_parts.is_empty() {
             return Err(KdbxKeyError::NoKeyParts);
         }
 
-        let result = Ok(crypto::hash::calculate_sha256_multiple(
+        let mut composite_key = crypto::hash::calculate_sha256_multiple(
             &key_parts.iter().map(Vec::as_slice).collect::<Vec<&[u8]>>(),
-        ));
-        key_parts.zeroize();
-        result
+        );
+        Ok(SecureData::take(composite_key.as_mut_slice()))
     }
 }
 
-pub fn parse_keyfile(key_buf: &[u8]) -> Vec<u8> {
-    if let Some(v) = try_parse_xml_keyfile(key_buf) {
-        v
-    } else if key_buf.len() == 32 {
-        key_buf.to_vec()
-    } else {
-        calculate_sha256(key_buf).to_vec()
+/// 密钥文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFileFormat {
+    /// KeePass XML 1.0, 密钥以Base64存储
+    XmlV1,
+    /// KeePass XML 2.0, 密钥以十六进制存储并带有校验值
+    XmlV2,
+    /// 32字节的原始密钥
+    Binary,
+    /// 64个字符的十六进制密钥
+    Hex,
+    /// 任意文件, 以其SHA-256作为密钥
+    Hashed,
+}
+
+/// 解析后的密钥文件
+pub struct KeyFile {
+    pub format: KeyFileFormat,
+    pub key: Zeroizing<Vec<u8>>,
+}
+
+/// 解析密钥文件, 按XML、32字节原始数据、64位十六进制的顺序识别, 都不符合时取SHA-256
+pub fn parse_keyfile(key_buf: &[u8]) -> Result<KeyFile, KdbxKeyError> {
+    if let Some(key_file) = try_parse_xml_keyfile(key_buf)? {
+        return Ok(key_file);
+    }
+    if key_buf.len() == 32 {
+        return Ok(KeyFile {
+            format: KeyFileFormat::Binary,
+            key: Zeroizing::new(key_buf.to_vec()),
+        });
+    }
+    if key_buf.len() == 64 && key_buf.iter().all(u8::is_ascii_hexdigit) {
+        if let Ok(key) = hex::decode(key_buf) {
+            return Ok(KeyFile {
+                format: KeyFileFormat::Hex,
+                key: Zeroizing::new(key),
+            });
+        }
     }
+    Ok(KeyFile {
+        format: KeyFileFormat::Hashed,
+        key: Zeroizing::new(calculate_sha256(key_buf).to_vec()),
+    })
 }
 
-fn try_parse_xml_keyfile(key_buf: &[u8]) -> Option<Vec<u8>> {
-    let xml = std::str::from_utf8(key_buf).ok()?;
-    let package = parser::parse(xml).ok()?;
+/// 不是KeePass的XML密钥文件时返回`None`
+fn try_parse_xml_keyfile(key_buf: &[u8]) -> Result<Option<KeyFile>, KdbxKeyError> {
+    let Some(xml) = std::str::from_utf8(key_buf).ok() else {
+        return Ok(None);
+    };
+    let Ok(package) = parser::parse(xml) else {
+        return Ok(None);
+    };
     let document = package.as_document();
 
-    let version: Option<String> = evaluate_xpath(&document, "//Meta/Version/text()")
-        .ok()
-        .map(|v| v.string());
-
-    let data = evaluate_xpath(&document, "//Key/Data/text()")
-        .ok()?
-        .string()
-        .split_whitespace()
-        .collect::<String>();
-
-    if version == Some("2.0".to_string()) {
-        return if let Ok(key_buf) = hex::decode(&data) {
-            Some(key_buf)
-        } else {
-            Some(data.as_bytes().to_vec())
-        };
+    let is_keyfile = evaluate_xpath(&document, "boolean(/KeyFile/Key/Data)")
+        .map(|v| v.boolean())
+        .unwrap_or(false);
+    if !is_keyfile {
+        return Ok(None);
     }
 
-    let key_bytes = data.as_bytes().to_vec();
+    let version = evaluate_xpath(&document, "//Meta/Version/text()")
+        .map(|v| v.string())
+        .unwrap_or_default();
+
+    let data = Zeroizing::new(
+        evaluate_xpath(&document, "//Key/Data/text()")
+            .map(|v| v.string())
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect::<String>(),
+    );
 
-    if let Ok(key) = base64::engine::general_purpose::STANDARD.decode(&key_bytes) {
-        Some(key)
-    } else {
-        Some(key_bytes)
+    match version.trim() {
+        "2.0" => {
+            let key = Zeroizing::new(
+                hex::decode(data.as_bytes()).map_err(|_| KdbxKeyError::FailedToParseKeyFile)?,
+            );
+            let hash = evaluate_xpath(&document, "//Key/Data/@Hash")
+                .map(|v| v.string())
+                .unwrap_or_default();
+            if !hash.is_empty() {
+                let expected =
+                    hex::decode(hash.trim()).map_err(|_| KdbxKeyError::KeyFileHashMismatch)?;
+                if expected != keyfile_hash(&key) {
+                    return Err(KdbxKeyError::KeyFileHashMismatch);
+                }
+            }
+            Ok(Some(KeyFile {
+                format: KeyFileFormat::XmlV2,
+                key,
+            }))
+        }
+        "1.0" | "1.00" => {
+            let key = base64::engine::general_purpose::STANDARD
+                .decode(data.as_bytes())
+                .map_err(|_| KdbxKeyError::FailedToParseKeyFile)?;
+            Ok(Some(KeyFile {
+                format: KeyFileFormat::XmlV1,
+                key: Zeroizing::new(key),
+            }))
+        }
+        other => Err(KdbxKeyError::UnsupportedKeyFileVersion(other.to_string())),
     }
 }
 
+/// XML 2.0密钥文件的校验值: 密钥SHA-256的前4字节
+fn keyfile_hash(key: &[u8]) -> [u8; 4] {
+    let hash = calculate_sha256(key);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// 生成随机的32字节密钥, 并按指定格式写出密钥文件
+pub fn create_keyfile(format: KeyFileFormat) -> Result<Zeroizing<Vec<u8>>, KdbxKeyError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    getrandom::fill(key.as_mut())?;
+    write_keyfile(key.as_ref(), format)
+}
+
+/// 将密钥按指定格式写出, `Hashed`格式无法由密钥还原
+pub fn write_keyfile(
+    key: &[u8],
+    format: KeyFileFormat,
+) -> Result<Zeroizing<Vec<u8>>, KdbxKeyError> {
+    let content = match format {
+        KeyFileFormat::XmlV2 => {
+            let data = Zeroizing::new(hex::encode_upper(key));
+            let mut lines = String::new();
+            for line in data.as_bytes().chunks(32) {
+                let groups = line
+                    .chunks(8)
+                    .map(|group| std::str::from_utf8(group).unwrap_or_default())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                lines.push_str("\t\t\t");
+                lines.push_str(&groups);
+                lines.push('\n');
+            }
+            let content = format!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                 <KeyFile>\n\
+                 \t<Meta>\n\
+                 \t\t<Version>2.0</Version>\n\
+                 \t</Meta>\n\
+                 \t<Key>\n\
+                 \t\t<Data Hash=\"{}\">\n\
+                 {}\
+                 \t\t</Data>\n\
+                 \t</Key>\n\
+                 </KeyFile>\n",
+                hex::encode_upper(keyfile_hash(key)),
+                lines.as_str(),
+            );
+            lines.zeroize();
+            content.into_bytes()
+        }
+        KeyFileFormat::XmlV1 => {
+            let data = Zeroizing::new(base64::engine::general_purpose::STANDARD.encode(key));
+            format!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                 <KeyFile>\n\
+                 \t<Meta>\n\
+                 \t\t<Version>1.00</Version>\n\
+                 \t</Meta>\n\
+                 \t<Key>\n\
+                 \t\t<Data>{}</Data>\n\
+                 \t</Key>\n\
+                 </KeyFile>\n",
+                data.as_str(),
+            )
+            .into_bytes()
+        }
+        KeyFileFormat::Binary if key.len() == 32 => key.to_vec(),
+        KeyFileFormat::Hex if key.len() == 32 => hex::encode_upper(key).into_bytes(),
+        KeyFileFormat::Binary | KeyFileFormat::Hex => {
+            return Err(KdbxKeyError::InvalidKeyLength(key.len()))
+        }
+        KeyFileFormat::Hashed => return Err(KdbxKeyError::UnsupportedKeyFileFormat),
+    };
+    Ok(Zeroizing::new(content))
+}
+
 #[cfg(test)]
 mod key_tests {
+    use super::{
+        create_keyfile, parse_keyfile, write_keyfile, KdbxKey, KdbxKeyError, KeyFileFormat,
+    };
+    use crate::crypto::hash::calculate_sha256;
+    use crate::kdbx::challenge_response::{ChallengeResponseProvider, SoftwareChallengeResponse};
     use hex_literal::hex;
 
     const XML_KEYFILE1: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -134,9 +334,9 @@ mod key_tests {
 
     #[test]
     fn test_try_parse_xml_keyfile() {
-        let result = super::try_parse_xml_keyfile(XML_KEYFILE1.as_bytes());
+        let result = super::try_parse_xml_keyfile(XML_KEYFILE1.as_bytes()).unwrap();
         assert!(result.is_some());
-        let key = result.unwrap();
+        let key = result.unwrap().key.to_vec();
         assert_eq!(key.len(), 32);
         assert_eq!(
             key,
@@ -146,13 +346,145 @@ mod key_tests {
 
     #[test]
     fn test_try_parse_xml_keyfile2() {
-        let result = super::try_parse_xml_keyfile(XML_KEYFILE2.as_bytes());
+        let result = super::try_parse_xml_keyfile(XML_KEYFILE2.as_bytes()).unwrap();
         assert!(result.is_some());
-        let key = result.unwrap();
+        let key = result.unwrap().key.to_vec();
         assert_eq!(key.len(), 32);
         assert_eq!(
             key,
             hex!("357C9888930783796CF9E0668DB02359E73D95C393A098A87DB84D88531324CC")
         );
     }
+
+    #[test]
+    fn test_parse_keyfile_formats() {
+        let v2 = parse_keyfile(XML_KEYFILE1.as_bytes()).unwrap();
+        assert_eq!(v2.format, KeyFileFormat::XmlV2);
+        let v1 = parse_keyfile(XML_KEYFILE2.as_bytes()).unwrap();
+        assert_eq!(v1.format, KeyFileFormat::XmlV1);
+
+        let raw = [7u8; 32];
+        let binary = parse_keyfile(&raw).unwrap();
+        assert_eq!(binary.format, KeyFileFormat::Binary);
+        assert_eq!(binary.key.as_slice(), &raw);
+
+        let hex_file = b"5d008fbc4e6be14a89cac795ddb9a180d7662141e6662ecc8d33e1680882516d";
+        let hex_key = parse_keyfile(hex_file).unwrap();
+        assert_eq!(hex_key.format, KeyFileFormat::Hex);
+        assert_eq!(
+            hex_key.key.as_slice(),
+            hex!("5D008FBC4E6BE14A89CAC795DDB9A180D7662141E6662ECC8D33E1680882516D")
+        );
+
+        let other = b"any file can be a key file";
+        let hashed = parse_keyfile(other).unwrap();
+        assert_eq!(hashed.format, KeyFileFormat::Hashed);
+        assert_eq!(hashed.key.as_slice(), calculate_sha256(other).as_slice());
+
+        let not_keyfile = b"<?xml version=\"1.0\"?><Other><Key><Data>AA==</Data></Key></Other>";
+        assert_eq!(
+            parse_keyfile(not_keyfile).unwrap().format,
+            KeyFileFormat::Hashed
+        );
+    }
+
+    #[test]
+    fn test_parse_keyfile_hash_mismatch() {
+        let corrupted = XML_KEYFILE1.replace("5D008FBC", "5D008FBD");
+        assert!(matches!(
+            parse_keyfile(corrupted.as_bytes()),
+            Err(KdbxKeyError::KeyFileHashMismatch)
+        ));
+        let bad_hash = XML_KEYFILE1.replace("7DEDDE1D", "7DEDDE1E");
+        assert!(matches!(
+            parse_keyfile(bad_hash.as_bytes()),
+            Err(KdbxKeyError::KeyFileHashMismatch)
+        ));
+        let bad_data = XML_KEYFILE1.replace("5D008FBC", "5D008FBZ");
+        assert!(matches!(
+            parse_keyfile(bad_data.as_bytes()),
+            Err(KdbxKeyError::FailedToParseKeyFile)
+        ));
+        let bad_version = XML_KEYFILE1.replace("<Version>2.0", "<Version>3.0");
+        assert!(matches!(
+            parse_keyfile(bad_version.as_bytes()),
+            Err(KdbxKeyError::UnsupportedKeyFileVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_write_keyfile() {
+        let key = hex!("5D008FBC4E6BE14A89CAC795DDB9A180D7662141E6662ECC8D33E1680882516D");
+        let xml = write_keyfile(&key, KeyFileFormat::XmlV2).unwrap();
+        let xml = std::str::from_utf8(&xml).unwrap();
+        assert!(xml.contains(r#"<Data Hash="7DEDDE1D">"#));
+        assert!(xml.contains("5D008FBC 4E6BE14A 89CAC795 DDB9A180\n"));
+
+        for format in [
+            KeyFileFormat::XmlV2,
+            KeyFileFormat::XmlV1,
+            KeyFileFormat::Binary,
+            KeyFileFormat::Hex,
+        ] {
+            let content = write_keyfile(&key, format).unwrap();
+            let parsed = parse_keyfile(&content).unwrap();
+            assert_eq!(parsed.format, format);
+            assert_eq!(parsed.key.as_slice(), &key);
+        }
+
+        assert!(matches!(
+            write_keyfile(&key, KeyFileFormat::Hashed),
+            Err(KdbxKeyError::UnsupportedKeyFileFormat)
+        ));
+        assert!(matches!(
+            write_keyfile(&key[..16], KeyFileFormat::Binary),
+            Err(KdbxKeyError::InvalidKeyLength(16))
+        ));
+    }
+
+    #[test]
+    fn test_create_keyfile() {
+        let first = create_keyfile(KeyFileFormat::XmlV2).unwrap();
+        let second = create_keyfile(KeyFileFormat::XmlV2).unwrap();
+        let first = parse_keyfile(&first).unwrap();
+        let second = parse_keyfile(&second).unwrap();
+        assert_eq!(first.format, KeyFileFormat::XmlV2);
+        assert_eq!(first.key.len(), 32);
+        assert_ne!(first.key.as_slice(), second.key.as_slice());
+    }
+
+    #[test]
+    fn test_calc_key_hash_with_challenge() {
+        let seed = [3u8; 32];
+        let mut key = KdbxKey::new();
+        key.add_master_key("password");
+        let hash_with = |key: &KdbxKey, challenge: &[u8]| {
+            key.calc_key_hash_with_challenge(challenge)
+                .unwrap()
+                .unsecure()
+                .unwrap()
+        };
+        let plain = key.calc_key_hash().unwrap().unsecure().unwrap();
+        assert_eq!(hash_with(&key, &seed), plain);
+
+        let provider = SoftwareChallengeResponse::new(b"secret");
+        let response = provider.challenge(&seed).unwrap();
+        key.add_challenge_response(Box::new(provider));
+        let expected = crate::crypto::hash::calculate_sha256_multiple(&[
+            &calculate_sha256(b"password"),
+            &calculate_sha256(&response),
+        ]);
+        assert_eq!(hash_with(&key, &seed).as_slice(), expected.as_slice());
+        assert_ne!(hash_with(&key, &[4u8; 32]).as_slice(), expected.as_slice());
+    }
+
+    #[test]
+    fn test_key_hash_wiped_on_drop() {
+        let mut key = KdbxKey::new();
+        key.add_master_key("password");
+        let hash = key.calc_key_hash().unwrap();
+        let watch = crate::crypto::wipe_check::watch(hash.as_ptr());
+        drop(hash);
+        assert!(watch.wiped_on_free());
+    }
 }
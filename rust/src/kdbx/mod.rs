@@ -2,6 +2,17 @@ mod config;
 mod db;
 pub mod error;
 pub mod keys;
+pub mod challenge_response;
+pub mod quick_unlock;
+mod session;
+mod snapshot;
 mod compression;
 mod xml;
-mod otp;
\ No newline at end of file
+pub mod otp;
+mod placeholder;
+mod auto_type;
+mod settings;
+mod generator;
+mod strength;
+mod health;
+mod breach;
\ No newline at end of file
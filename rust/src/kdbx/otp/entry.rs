@@ -0,0 +1,976 @@
+use base64::Engine;
+use chrono::Utc;
+use data_encoding::BASE32_NOPAD;
+use thiserror::Error;
+use url::Url;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::crypto::secure_data::SecureData;
+use crate::kdbx::otp::export::{config_uri, hotp_uri};
+use crate::kdbx::otp::hotp::Hotp;
+use crate::kdbx::otp::motp::MobileOtp;
+use crate::kdbx::otp::rfc6238::{
+    OtpAuthUrl, Rfc6238ParseError, Rfc6238Totp, TotpAlgorithm, DEFAULT_DIGITS, DEFAULT_PERIOD,
+};
+use crate::kdbx::otp::steam::{SteamTotp, STEAM_ENCODER, STEAM_PERIOD};
+use crate::kdbx::otp::yandex::YandexTotp;
+use crate::kdbx::otp::{Totp, TotpGenerateError};
+use crate::kdbx::xml::database::KeePassDatabase;
+use crate::kdbx::xml::entities::{Entry, Value};
+use crate::kdbx::xml::errors::KdbxDatabaseError;
+
+/// KeePassXC: 完整的otpauth URL
+pub const FIELD_OTP: &str = "otp";
+
+/// KeePass 2.x: <https://keepass.info/help/base/placeholders.html#otp>
+pub const FIELD_TIME_OTP_SECRET: &str = "TimeOtp-Secret";
+pub const FIELD_TIME_OTP_SECRET_HEX: &str = "TimeOtp-Secret-Hex";
+pub const FIELD_TIME_OTP_SECRET_BASE32: &str = "TimeOtp-Secret-Base32";
+pub const FIELD_TIME_OTP_SECRET_BASE64: &str = "TimeOtp-Secret-Base64";
+pub const FIELD_TIME_OTP_LENGTH: &str = "TimeOtp-Length";
+pub const FIELD_TIME_OTP_PERIOD: &str = "TimeOtp-Period";
+pub const FIELD_TIME_OTP_ALGORITHM: &str = "TimeOtp-Algorithm";
+
+/// KeePassXC 2.6之前及KeeTrayTOTP: Base32种子, 设置为 `周期;位数`, 位数为 `S` 时表示Steam
+pub const FIELD_LEGACY_SEED: &str = "TOTP Seed";
+pub const FIELD_LEGACY_SETTINGS: &str = "TOTP Settings";
+
+/// KeePass 2.x HOTP, 固定为SHA-1和6位, 计数器为下一次使用的值
+pub const FIELD_HMAC_OTP_SECRET: &str = "HmacOtp-Secret";
+pub const FIELD_HMAC_OTP_SECRET_HEX: &str = "HmacOtp-Secret-Hex";
+pub const FIELD_HMAC_OTP_SECRET_BASE32: &str = "HmacOtp-Secret-Base32";
+pub const FIELD_HMAC_OTP_SECRET_BASE64: &str = "HmacOtp-Secret-Base64";
+pub const FIELD_HMAC_OTP_COUNTER: &str = "HmacOtp-Counter";
+
+/// Yandex Key与mOTP的PIN, 写为受保护字段
+///
+/// 密钥保存在 `otp` 字段的 `otpauth://yaotp/...` 或 `otpauth://motp/...` URL中,
+/// URL不包含PIN, 两者须同时存在才能生成验证码
+pub const FIELD_OTP_PIN: &str = "otp-pin";
+
+const OTP_FIELDS: [&str; 11] = [
+    FIELD_OTP,
+    FIELD_OTP_PIN,
+    FIELD_TIME_OTP_SECRET,
+    FIELD_TIME_OTP_SECRET_HEX,
+    FIELD_TIME_OTP_SECRET_BASE32,
+    FIELD_TIME_OTP_SECRET_BASE64,
+    FIELD_TIME_OTP_LENGTH,
+    FIELD_TIME_OTP_PERIOD,
+    FIELD_TIME_OTP_ALGORITHM,
+    FIELD_LEGACY_SEED,
+    FIELD_LEGACY_SETTINGS,
+];
+
+const HOTP_FIELDS: [&str; 5] = [
+    FIELD_HMAC_OTP_SECRET,
+    FIELD_HMAC_OTP_SECRET_HEX,
+    FIELD_HMAC_OTP_SECRET_BASE32,
+    FIELD_HMAC_OTP_SECRET_BASE64,
+    FIELD_HMAC_OTP_COUNTER,
+];
+
+/// KeePass按UTF-8、Hex、Base32、Base64的顺序读取密钥
+const TIME_OTP_SECRETS: [&str; 4] = [
+    FIELD_TIME_OTP_SECRET,
+    FIELD_TIME_OTP_SECRET_HEX,
+    FIELD_TIME_OTP_SECRET_BASE32,
+    FIELD_TIME_OTP_SECRET_BASE64,
+];
+const HMAC_OTP_SECRETS: [&str; 4] = [
+    FIELD_HMAC_OTP_SECRET,
+    FIELD_HMAC_OTP_SECRET_HEX,
+    FIELD_HMAC_OTP_SECRET_BASE32,
+    FIELD_HMAC_OTP_SECRET_BASE64,
+];
+
+const OTPAUTH_HOTP_PREFIX: &str = "otpauth://hotp";
+pub(crate) const OTPAUTH_YANDEX: &str = "yaotp";
+pub(crate) const OTPAUTH_MOTP: &str = "motp";
+const LEGACY_STEAM_DIGITS: &str = "S";
+
+#[derive(Debug, Error)]
+pub enum OtpEntryError {
+    #[error("Invalid OTP configuration: {0}")]
+    Parse(#[from] Rfc6238ParseError),
+
+    #[error("Invalid OTP secret in {0}")]
+    InvalidSecret(&'static str),
+
+    #[error("Invalid OTP setting {key}: {value}")]
+    InvalidSetting { key: &'static str, value: String },
+
+    #[error("OTP configuration cannot be stored as {0:?}")]
+    UnsupportedStorage(OtpStorage),
+
+    #[error("OTP generation error: {0}")]
+    Generate(#[from] TotpGenerateError),
+
+    #[error("Database error")]
+    DatabaseError(#[from] KdbxDatabaseError),
+}
+
+/// 条目中的OTP配置
+#[derive(Debug)]
+pub enum OtpConfig {
+    Totp(Rfc6238Totp),
+    Steam(SteamTotp),
+    Yandex(YandexTotp),
+    Motp(MobileOtp),
+}
+
+impl OtpConfig {
+    pub fn into_totp(self) -> Box<dyn Totp> {
+        match self {
+            OtpConfig::Totp(totp) => Box::new(totp),
+            OtpConfig::Steam(steam) => Box::new(steam),
+            OtpConfig::Yandex(yandex) => Box::new(yandex),
+            OtpConfig::Motp(motp) => Box::new(motp),
+        }
+    }
+
+    /// 需要PIN的配置返回PIN的可变引用
+    fn pin_mut(&mut self) -> Option<&mut SecureData> {
+        match self {
+            OtpConfig::Yandex(yandex) => Some(&mut yandex.pin),
+            OtpConfig::Motp(motp) => Some(&mut motp.pin),
+            _ => None,
+        }
+    }
+}
+
+/// 写回条目时使用的字段格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpStorage {
+    /// `otp` 字段, Yandex Key和mOTP另有 `otp-pin` 字段
+    KeePassXc,
+    /// `TimeOtp-*` 字段, 仅支持RFC 6238
+    KeePass,
+    /// `TOTP Seed` / `TOTP Settings` 字段, 仅支持SHA-1和Steam
+    Legacy,
+}
+
+/// 条目是否包含任一OTP字段
+pub fn has_otp(entry: &Entry) -> bool {
+    OTP_FIELDS
+        .iter()
+        .chain(HOTP_FIELDS.iter())
+        .any(|key| entry.get_value(key).is_some())
+}
+
+/// 读取条目的OTP配置, 依次检查KeePassXC、KeePass 2.x和旧版字段
+///
+/// `otp` 字段为HOTP URL时忽略该字段, 参阅 `read_hotp`
+pub fn read_otp(db: &KeePassDatabase, entry: &Entry) -> Result<Option<OtpConfig>, OtpEntryError> {
+    if let Some(url) = read_field(db, entry, FIELD_OTP)? {
+        if !is_hotp_url(&url) {
+            let mut config = parse_otp_field(&url)?;
+            if let Some(pin) = config.pin_mut() {
+                if let Some(value) = read_field(db, entry, FIELD_OTP_PIN)? {
+                    *pin = SecureData::new(value.as_bytes());
+                }
+            }
+            return Ok(Some(config));
+        }
+    }
+    if let Some(config) = read_time_otp(db, entry)? {
+        return Ok(Some(config));
+    }
+    read_legacy(db, entry)
+}
+
+/// 读取条目的OTP配置并创建生成器
+pub fn entry_totp(
+    db: &KeePassDatabase,
+    entry: &Entry,
+) -> Result<Option<Box<dyn Totp>>, OtpEntryError> {
+    Ok(read_otp(db, entry)?.map(OtpConfig::into_totp))
+}
+
+/// 将OTP配置写入条目, 会先删除条目中已有的OTP字段
+///
+/// 密钥写为 `Value::WaitProtect`, 保存时使用内部流加密
+pub fn write_otp(
+    entry: &mut Entry,
+    config: &OtpConfig,
+    storage: OtpStorage,
+) -> Result<(), OtpEntryError> {
+    let fields = match storage {
+        OtpStorage::KeePassXc => {
+            let mut fields = vec![(FIELD_OTP, protected(&config_uri(config)))];
+            let pin = match config {
+                OtpConfig::Yandex(yandex) => Some(&yandex.pin),
+                OtpConfig::Motp(motp) => Some(&motp.pin),
+                _ => None,
+            };
+            if let Some(pin) = pin.filter(|pin| pin.len() > 0) {
+                fields.push((FIELD_OTP_PIN, Value::WaitProtect(pin.clone())));
+            }
+            fields
+        }
+        OtpStorage::KeePass => time_otp_fields(config)?,
+        OtpStorage::Legacy => legacy_fields(config)?,
+    };
+    clear_otp(entry);
+    for (key, value) in fields {
+        entry.set_value(key, value);
+    }
+    Ok(())
+}
+
+/// 读取条目的HOTP配置及其存储格式
+///
+/// KeePassXC本身不支持HOTP, 此处沿用 `otp` 字段保存 `otpauth://hotp` URL
+pub fn read_hotp(
+    db: &KeePassDatabase,
+    entry: &Entry,
+) -> Result<Option<(Hotp, OtpStorage)>, OtpEntryError> {
+    if let Some(url) = read_field(db, entry, FIELD_OTP)? {
+        if is_hotp_url(&url) {
+            return Ok(Some((parse_hotp_url(&url)?, OtpStorage::KeePassXc)));
+        }
+    }
+
+    let Some(secret) = read_keepass_secret(db, entry, &HMAC_OTP_SECRETS)? else {
+        return Ok(None);
+    };
+    let counter = match read_field(db, entry, FIELD_HMAC_OTP_COUNTER)? {
+        Some(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| OtpEntryError::InvalidSetting {
+                key: FIELD_HMAC_OTP_COUNTER,
+                value: value.to_string(),
+            })?,
+        None => 0,
+    };
+    let hotp = Hotp {
+        issuer: None,
+        account: None,
+        algorithm: TotpAlgorithm::Sha1,
+        digits: DEFAULT_DIGITS,
+        counter,
+        secret,
+    };
+    Ok(Some((hotp, OtpStorage::KeePass)))
+}
+
+/// 将HOTP配置写入条目, 会先删除条目中已有的HOTP字段
+///
+/// KeePass格式仅支持SHA-1和6位, 不支持旧版格式
+pub fn write_hotp(
+    entry: &mut Entry,
+    hotp: &Hotp,
+    storage: OtpStorage,
+) -> Result<(), OtpEntryError> {
+    let fields = match storage {
+        OtpStorage::KeePassXc => vec![(FIELD_OTP, protected(&hotp_uri(hotp)))],
+        OtpStorage::KeePass
+            if hotp.algorithm == TotpAlgorithm::Sha1 && hotp.digits == DEFAULT_DIGITS =>
+        {
+            let secret = Zeroizing::new(BASE32_NOPAD.encode(&hotp.secret));
+            vec![
+                (FIELD_HMAC_OTP_SECRET_BASE32, protected(&secret)),
+                (
+                    FIELD_HMAC_OTP_COUNTER,
+                    Value::Unprotected(hotp.counter.to_string()),
+                ),
+            ]
+        }
+        _ => return Err(OtpEntryError::UnsupportedStorage(storage)),
+    };
+    clear_hotp(entry);
+    for (key, value) in fields {
+        entry.set_value(key, value);
+    }
+    Ok(())
+}
+
+/// 生成条目当前计数器的HOTP验证码, 并将递增后的计数器写回条目
+///
+/// 计数器在返回验证码前写入, 即使验证码未被使用也不会重复
+pub fn next_hotp_code(
+    db: &mut KeePassDatabase,
+    uuid: &Uuid,
+) -> Result<Option<String>, OtpEntryError> {
+    let Some(entry) = db.document.root.group.find_entry(uuid) else {
+        return Ok(None);
+    };
+    let Some((mut hotp, storage)) = read_hotp(db, entry)? else {
+        return Ok(None);
+    };
+    let code = hotp.current()?;
+    hotp.counter = hotp.counter.saturating_add(1);
+    store_hotp_counter(db, uuid, &hotp, storage)?;
+    Ok(Some(code))
+}
+
+/// 使用用户提供的两个连续验证码重新同步计数器, 成功时写回条目
+pub fn resync_hotp(
+    db: &mut KeePassDatabase,
+    uuid: &Uuid,
+    first: &str,
+    second: &str,
+    look_ahead: u64,
+) -> Result<bool, OtpEntryError> {
+    let Some(entry) = db.document.root.group.find_entry(uuid) else {
+        return Ok(false);
+    };
+    let Some((mut hotp, storage)) = read_hotp(db, entry)? else {
+        return Ok(false);
+    };
+    let Some(counter) = hotp.resync(first.trim(), second.trim(), look_ahead)? else {
+        return Ok(false);
+    };
+    hotp.counter = counter;
+    store_hotp_counter(db, uuid, &hotp, storage)?;
+    Ok(true)
+}
+
+/// 删除条目中的KeePass HOTP字段, 返回是否有字段被删除
+pub fn clear_hotp(entry: &mut Entry) -> bool {
+    let mut removed = false;
+    for key in HOTP_FIELDS {
+        removed |= entry.remove_value(key);
+    }
+    removed
+}
+
+/// 删除条目中的全部TOTP字段, 返回是否有字段被删除
+pub fn clear_otp(entry: &mut Entry) -> bool {
+    let mut removed = false;
+    for key in OTP_FIELDS {
+        removed |= entry.remove_value(key);
+    }
+    removed
+}
+
+/// 解析 `otp` 字段, 支持otpauth URL和裸Base32密钥
+pub(crate) fn parse_otp_field(value: &str) -> Result<OtpConfig, OtpEntryError> {
+    let value = value.trim();
+    if !value.starts_with("otpauth://") {
+        let secret = decode_base32(value)?;
+        return totp_config(secret, TotpAlgorithm::Sha1, DEFAULT_DIGITS, DEFAULT_PERIOD);
+    }
+
+    let url = Url::parse(value).map_err(Rfc6238ParseError::from)?;
+    match url.host_str() {
+        Some(OTPAUTH_YANDEX) => {
+            let parsed = OtpAuthUrl::parse(&url, OTPAUTH_YANDEX)?;
+            let yandex = YandexTotp::new(parsed.account, &parsed.secret, SecureData::new(&[]))
+                .map_err(|_| OtpEntryError::InvalidSecret(FIELD_OTP))?;
+            return Ok(OtpConfig::Yandex(yandex));
+        }
+        Some(OTPAUTH_MOTP) => {
+            let parsed = OtpAuthUrl::parse(&url, OTPAUTH_MOTP)?;
+            return Ok(OtpConfig::Motp(MobileOtp {
+                issuer: parsed.issuer,
+                account: parsed.account,
+                secret: parsed.secret,
+                pin: SecureData::new(&[]),
+            }));
+        }
+        _ => {}
+    }
+    let totp = Rfc6238Totp::from_url(&url)?;
+    let is_steam = url
+        .query_pairs()
+        .any(|(key, value)| key == "encoder" && value.eq_ignore_ascii_case(STEAM_ENCODER));
+    if is_steam {
+        return Ok(OtpConfig::Steam(SteamTotp {
+            secret: totp.secret,
+        }));
+    }
+    validate(totp.digits, totp.period)?;
+    Ok(OtpConfig::Totp(totp))
+}
+
+/// 解析并校验 `otpauth://hotp` URL
+pub(crate) fn parse_hotp_url(value: &str) -> Result<Hotp, OtpEntryError> {
+    let url = Url::parse(value.trim()).map_err(Rfc6238ParseError::from)?;
+    let hotp = Hotp::from_url(&url)?;
+    validate(hotp.digits, DEFAULT_PERIOD)?;
+    Ok(hotp)
+}
+
+fn read_time_otp(db: &KeePassDatabase, entry: &Entry) -> Result<Option<OtpConfig>, OtpEntryError> {
+    let Some(secret) = read_keepass_secret(db, entry, &TIME_OTP_SECRETS)? else {
+        return Ok(None);
+    };
+
+    let digits = match read_field(db, entry, FIELD_TIME_OTP_LENGTH)? {
+        Some(value) => parse_setting(FIELD_TIME_OTP_LENGTH, &value)?,
+        None => DEFAULT_DIGITS,
+    };
+    let period = match read_field(db, entry, FIELD_TIME_OTP_PERIOD)? {
+        Some(value) => parse_setting(FIELD_TIME_OTP_PERIOD, &value)?,
+        None => DEFAULT_PERIOD,
+    };
+    let algorithm = match read_field(db, entry, FIELD_TIME_OTP_ALGORITHM)? {
+        Some(value) => parse_keepass_algorithm(&value)?,
+        None => TotpAlgorithm::Sha1,
+    };
+    totp_config(secret, algorithm, digits, period).map(Some)
+}
+
+/// 按 `[UTF-8, Hex, Base32, Base64]` 的顺序读取第一个存在的密钥字段
+fn read_keepass_secret(
+    db: &KeePassDatabase,
+    entry: &Entry,
+    keys: &[&'static str; 4],
+) -> Result<Option<Vec<u8>>, OtpEntryError> {
+    let [utf8, hex, base32, base64] = *keys;
+    let secret = if let Some(secret) = read_field(db, entry, utf8)? {
+        secret.as_bytes().to_vec()
+    } else if let Some(secret) = read_field(db, entry, hex)? {
+        let secret: Zeroizing<String> =
+            Zeroizing::new(secret.chars().filter(|c| !c.is_whitespace()).collect());
+        hex::decode(secret.as_bytes()).map_err(|_| OtpEntryError::InvalidSecret(hex))?
+    } else if let Some(secret) = read_field(db, entry, base32)? {
+        decode_base32(&secret)?
+    } else if let Some(secret) = read_field(db, entry, base64)? {
+        base64::engine::general_purpose::STANDARD
+            .decode(secret.trim())
+            .map_err(|_| OtpEntryError::InvalidSecret(base64))?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(secret))
+}
+
+/// 写入递增或重新同步后的计数器, 并更新条目的修改时间
+fn store_hotp_counter(
+    db: &mut KeePassDatabase,
+    uuid: &Uuid,
+    hotp: &Hotp,
+    storage: OtpStorage,
+) -> Result<(), OtpEntryError> {
+    let Some(entry) = db.document.root.group.find_entry_mut(uuid) else {
+        return Ok(());
+    };
+    match storage {
+        // 仅更新计数器, 保留密钥原有的编码字段
+        OtpStorage::KeePass => entry.set_value(
+            FIELD_HMAC_OTP_COUNTER,
+            Value::Unprotected(hotp.counter.to_string()),
+        ),
+        _ => write_hotp(entry, hotp, storage)?,
+    }
+    entry.times.last_modification_time = Utc::now().into();
+    Ok(())
+}
+
+pub(crate) fn is_hotp_url(value: &str) -> bool {
+    value
+        .trim()
+        .get(..OTPAUTH_HOTP_PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(OTPAUTH_HOTP_PREFIX))
+}
+
+fn read_legacy(db: &KeePassDatabase, entry: &Entry) -> Result<Option<OtpConfig>, OtpEntryError> {
+    let Some(seed) = read_field(db, entry, FIELD_LEGACY_SEED)? else {
+        return Ok(None);
+    };
+    let secret = decode_base32(&seed)?;
+    let settings = read_field(db, entry, FIELD_LEGACY_SETTINGS)?;
+    let mut parts = settings.as_deref().map_or("", |s| s.as_str()).split(';');
+
+    let period = match parts.next().map(str::trim).filter(|part| !part.is_empty()) {
+        Some(value) => parse_setting(FIELD_LEGACY_SETTINGS, value)?,
+        None => DEFAULT_PERIOD,
+    };
+    match parts.next().map(str::trim).filter(|part| !part.is_empty()) {
+        Some(LEGACY_STEAM_DIGITS) => Ok(Some(OtpConfig::Steam(SteamTotp { secret }))),
+        Some(value) => {
+            let digits = parse_setting(FIELD_LEGACY_SETTINGS, value)?;
+            totp_config(secret, TotpAlgorithm::Sha1, digits, period).map(Some)
+        }
+        None => totp_config(secret, TotpAlgorithm::Sha1, DEFAULT_DIGITS, period).map(Some),
+    }
+}
+
+/// 读取字段明文, 空字段视为不存在
+pub(crate) fn read_field(
+    db: &KeePassDatabase,
+    entry: &Entry,
+    key: &str,
+) -> Result<Option<Zeroizing<String>>, OtpEntryError> {
+    let Some(value) = entry.get_value(key) else {
+        return Ok(None);
+    };
+    let value = Zeroizing::new(db.get_value_string(value)?);
+    if value.trim().is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(value))
+    }
+}
+
+/// 忽略空白、填充符和大小写的Base32解码
+fn decode_base32(value: &str) -> Result<Vec<u8>, OtpEntryError> {
+    let normalized: Zeroizing<String> = Zeroizing::new(
+        value
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '=')
+            .map(|c| c.to_ascii_uppercase())
+            .collect(),
+    );
+    Ok(BASE32_NOPAD
+        .decode(normalized.as_bytes())
+        .map_err(Rfc6238ParseError::from)?)
+}
+
+fn parse_setting(key: &'static str, value: &str) -> Result<u32, OtpEntryError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| OtpEntryError::InvalidSetting {
+            key,
+            value: value.to_string(),
+        })
+}
+
+/// KeePass使用 `HMAC-SHA-1`、`HMAC-SHA-256`、`HMAC-SHA-512`
+fn parse_keepass_algorithm(value: &str) -> Result<TotpAlgorithm, OtpEntryError> {
+    let normalized = value.trim().to_uppercase().replace(['-', '_'], "");
+    match normalized.trim_start_matches("HMAC") {
+        "SHA1" => Ok(TotpAlgorithm::Sha1),
+        "SHA256" => Ok(TotpAlgorithm::Sha256),
+        "SHA512" => Ok(TotpAlgorithm::Sha512),
+        _ => Err(OtpEntryError::InvalidSetting {
+            key: FIELD_TIME_OTP_ALGORITHM,
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn keepass_algorithm(algorithm: &TotpAlgorithm) -> &'static str {
+    match algorithm {
+        TotpAlgorithm::Sha1 => "HMAC-SHA-1",
+        TotpAlgorithm::Sha256 => "HMAC-SHA-256",
+        TotpAlgorithm::Sha512 => "HMAC-SHA-512",
+    }
+}
+
+/// 位数超过9时 `10^digits` 溢出u32, 周期为0时无法计算计数器
+fn validate(digits: u32, period: u32) -> Result<(), OtpEntryError> {
+    if !(1..=9).contains(&digits) {
+        return Err(OtpEntryError::InvalidSetting {
+            key: "digits",
+            value: digits.to_string(),
+        });
+    }
+    if period == 0 {
+        return Err(OtpEntryError::InvalidSetting {
+            key: "period",
+            value: period.to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn totp_config(
+    secret: Vec<u8>,
+    algorithm: TotpAlgorithm,
+    digits: u32,
+    period: u32,
+) -> Result<OtpConfig, OtpEntryError> {
+    validate(digits, period)?;
+    Ok(OtpConfig::Totp(Rfc6238Totp {
+        issuer: None,
+        account: None,
+        algorithm,
+        digits,
+        period,
+        secret,
+    }))
+}
+
+fn protected(value: &str) -> Value {
+    Value::WaitProtect(SecureData::new(value.as_bytes()))
+}
+
+fn time_otp_fields(config: &OtpConfig) -> Result<Vec<(&'static str, Value)>, OtpEntryError> {
+    let OtpConfig::Totp(totp) = config else {
+        return Err(OtpEntryError::UnsupportedStorage(OtpStorage::KeePass));
+    };
+    let secret = Zeroizing::new(BASE32_NOPAD.encode(&totp.secret));
+    Ok(vec![
+        (FIELD_TIME_OTP_SECRET_BASE32, protected(&secret)),
+        (
+            FIELD_TIME_OTP_LENGTH,
+            Value::Unprotected(totp.digits.to_string()),
+        ),
+        (
+            FIELD_TIME_OTP_PERIOD,
+            Value::Unprotected(totp.period.to_string()),
+        ),
+        (
+            FIELD_TIME_OTP_ALGORITHM,
+            Value::Unprotected(keepass_algorithm(&totp.algorithm).to_string()),
+        ),
+    ])
+}
+
+fn legacy_fields(config: &OtpConfig) -> Result<Vec<(&'static str, Value)>, OtpEntryError> {
+    let (secret, settings) = match config {
+        OtpConfig::Totp(totp) if totp.algorithm == TotpAlgorithm::Sha1 => {
+            (&totp.secret, format!("{};{}", totp.period, totp.digits))
+        }
+        OtpConfig::Steam(steam) => (
+            &steam.secret,
+            format!("{STEAM_PERIOD};{LEGACY_STEAM_DIGITS}"),
+        ),
+        _ => return Err(OtpEntryError::UnsupportedStorage(OtpStorage::Legacy)),
+    };
+    let secret = Zeroizing::new(BASE32_NOPAD.encode(secret));
+    Ok(vec![
+        (FIELD_LEGACY_SEED, protected(&secret)),
+        (FIELD_LEGACY_SETTINGS, Value::Unprotected(settings)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::otp::steam::STEAM_CODE_LENGTH;
+    use crate::kdbx::xml::fixtures;
+
+    /// RFC 6238 附录B的SHA-1密钥 `12345678901234567890`
+    const SECRET_BASE32: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    const SECRET_HEX: &str = "3132333435363738393031323334353637383930";
+    const SECRET_BASE64: &str = "MTIzNDU2Nzg5MDEyMzQ1Njc4OTA=";
+
+    fn database_with(fields: &[(&str, &str)]) -> KeePassDatabase {
+        let entry = fixtures::entry_xml(&fixtures::uuid(1), fields, "");
+        fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(10),
+            "Root",
+            "",
+            &entry,
+        ))
+    }
+
+    fn code(fields: &[(&str, &str)], timestamp: u64) -> String {
+        let db = database_with(fields);
+        let entry = &db.document.root.group.entry[0];
+        let totp = entry_totp(&db, entry).unwrap().unwrap();
+        totp.generate(timestamp).unwrap().code
+    }
+
+    #[test]
+    fn test_keepassxc_otp_field() {
+        let url = format!("otpauth://totp/ACME:alice?secret={SECRET_BASE32}&digits=8&issuer=ACME");
+        assert_eq!(code(&[(FIELD_OTP, &url)], 59), "94287082");
+        assert_eq!(code(&[(FIELD_OTP, SECRET_BASE32)], 59), "287082");
+
+        let db = database_with(&[(FIELD_OTP, &url)]);
+        let totp = entry_totp(&db, &db.document.root.group.entry[0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(totp.get_issuer(), Some("ACME"));
+        assert_eq!(totp.get_account(), Some("alice"));
+    }
+
+    #[test]
+    fn test_keepassxc_steam() {
+        let url =
+            format!("otpauth://totp/Steam:none?secret={SECRET_BASE32}&issuer=Steam&encoder=steam");
+        let db = database_with(&[(FIELD_OTP, &url)]);
+        let config = read_otp(&db, &db.document.root.group.entry[0])
+            .unwrap()
+            .unwrap();
+        assert!(matches!(config, OtpConfig::Steam(_)));
+        assert_eq!(code(&[(FIELD_OTP, &url)], 59).len(), STEAM_CODE_LENGTH);
+    }
+
+    #[test]
+    fn test_keepass_time_otp_fields() {
+        let expected = "94287082";
+        for (key, secret) in [
+            (FIELD_TIME_OTP_SECRET, "12345678901234567890"),
+            (FIELD_TIME_OTP_SECRET_HEX, SECRET_HEX),
+            (
+                FIELD_TIME_OTP_SECRET_BASE32,
+                "gezd gnbv gy3t qojq gezd gnbv gy3t qojq",
+            ),
+            (FIELD_TIME_OTP_SECRET_BASE64, SECRET_BASE64),
+        ] {
+            assert_eq!(
+                code(&[(key, secret), (FIELD_TIME_OTP_LENGTH, "8")], 59),
+                expected,
+                "{key}"
+            );
+        }
+
+        let sha256 = code(
+            &[
+                (FIELD_TIME_OTP_SECRET, "12345678901234567890123456789012"),
+                (FIELD_TIME_OTP_LENGTH, "8"),
+                (FIELD_TIME_OTP_PERIOD, "30"),
+                (FIELD_TIME_OTP_ALGORITHM, "HMAC-SHA-256"),
+            ],
+            59,
+        );
+        assert_eq!(sha256, "46119246");
+    }
+
+    #[test]
+    fn test_legacy_fields() {
+        assert_eq!(
+            code(
+                &[
+                    (FIELD_LEGACY_SEED, SECRET_BASE32),
+                    (FIELD_LEGACY_SETTINGS, "30;8")
+                ],
+                59
+            ),
+            "94287082"
+        );
+        assert_eq!(code(&[(FIELD_LEGACY_SEED, SECRET_BASE32)], 59), "287082");
+
+        let db = database_with(&[
+            (FIELD_LEGACY_SEED, SECRET_BASE32),
+            (FIELD_LEGACY_SETTINGS, "30;S"),
+        ]);
+        let config = read_otp(&db, &db.document.root.group.entry[0])
+            .unwrap()
+            .unwrap();
+        assert!(matches!(config, OtpConfig::Steam(_)));
+    }
+
+    #[test]
+    fn test_no_otp_and_invalid_settings() {
+        let db = database_with(&[("Title", "Example")]);
+        let entry = &db.document.root.group.entry[0];
+        assert!(!has_otp(entry));
+        assert!(read_otp(&db, entry).unwrap().is_none());
+
+        let db = database_with(&[
+            (FIELD_TIME_OTP_SECRET, "secret"),
+            (FIELD_TIME_OTP_LENGTH, "12"),
+        ]);
+        assert!(matches!(
+            read_otp(&db, &db.document.root.group.entry[0]),
+            Err(OtpEntryError::InvalidSetting { .. })
+        ));
+
+        let db = database_with(&[(FIELD_TIME_OTP_SECRET_HEX, "xyz")]);
+        assert!(matches!(
+            read_otp(&db, &db.document.root.group.entry[0]),
+            Err(OtpEntryError::InvalidSecret(FIELD_TIME_OTP_SECRET_HEX))
+        ));
+    }
+
+    #[test]
+    fn test_write_otp_round_trip() {
+        let db = database_with(&[
+            (FIELD_LEGACY_SEED, SECRET_BASE32),
+            (FIELD_LEGACY_SETTINGS, "30;6"),
+        ]);
+        let mut entry = db.document.root.group.entry[0].clone();
+
+        for storage in [
+            OtpStorage::KeePassXc,
+            OtpStorage::KeePass,
+            OtpStorage::Legacy,
+        ] {
+            let config = OtpConfig::Totp(Rfc6238Totp {
+                issuer: Some("Example Corp".to_string()),
+                account: Some("bob@example.com".to_string()),
+                algorithm: TotpAlgorithm::Sha1,
+                digits: 8,
+                period: 30,
+                secret: b"12345678901234567890".to_vec(),
+            });
+            write_otp(&mut entry, &config, storage).unwrap();
+
+            let totp = entry_totp(&db, &entry).unwrap().unwrap();
+            assert_eq!(totp.generate(59).unwrap().code, "94287082", "{storage:?}");
+            let fields = entry
+                .string
+                .iter()
+                .filter(|f| OTP_FIELDS.contains(&f.key.as_str()));
+            assert!(fields.count() <= 4);
+        }
+
+        write_otp(&mut entry, &config_steam(), OtpStorage::KeePassXc).unwrap();
+        let url = db
+            .get_value_string(entry.get_value(FIELD_OTP).unwrap())
+            .unwrap();
+        assert!(url.starts_with("otpauth://totp/Steam:none?secret="));
+        assert!(url.ends_with("&encoder=steam"));
+        assert!(matches!(
+            read_otp(&db, &entry).unwrap(),
+            Some(OtpConfig::Steam(_))
+        ));
+
+        assert!(matches!(
+            write_otp(&mut entry, &config_steam(), OtpStorage::KeePass),
+            Err(OtpEntryError::UnsupportedStorage(OtpStorage::KeePass))
+        ));
+        assert!(entry.get_value(FIELD_OTP).is_some());
+
+        assert!(clear_otp(&mut entry));
+        assert!(!has_otp(&entry));
+    }
+
+    fn entry_uuid() -> Uuid {
+        let mut bytes = [0u8; 16];
+        bytes[15] = 1;
+        Uuid::from_bytes(bytes)
+    }
+
+    fn field(db: &KeePassDatabase, key: &str) -> Option<String> {
+        let entry = db.document.root.group.find_entry(&entry_uuid())?;
+        Some(db.get_value_string(entry.get_value(key)?).unwrap())
+    }
+
+    #[test]
+    fn test_pin_based_otp_fields() {
+        let url = "otpauth://yaotp/alice?secret=6SB2IKNM6OBZPAVBVTOHDKS4FAAAAAAADFUTQMBTRY";
+        assert_eq!(
+            code(&[(FIELD_OTP, url), (FIELD_OTP_PIN, "5239")], 1641559648),
+            "umozdicq"
+        );
+        let db = database_with(&[(FIELD_OTP, url)]);
+        let totp = entry_totp(&db, &db.document.root.group.entry[0])
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            totp.generate(0),
+            Err(TotpGenerateError::MissingPin)
+        ));
+
+        let mut entry = db.document.root.group.entry[0].clone();
+        let config = OtpConfig::Motp(MobileOtp {
+            issuer: Some("VPN".to_string()),
+            account: None,
+            secret: hex::decode("e3152afee62599c8").unwrap(),
+            pin: SecureData::new(b"1234"),
+        });
+        write_otp(&mut entry, &config, OtpStorage::KeePassXc).unwrap();
+        assert!(matches!(
+            entry.get_value(FIELD_OTP_PIN),
+            Some(Value::WaitProtect(_))
+        ));
+        let url = db
+            .get_value_string(entry.get_value(FIELD_OTP).unwrap())
+            .unwrap();
+        assert!(url.starts_with("otpauth://motp/VPN:none?secret="));
+        assert!(!url.contains("1234"));
+        let totp = entry_totp(&db, &entry).unwrap().unwrap();
+        assert_eq!(totp.generate(123456789).unwrap().code, "4ebfb2");
+
+        for storage in [OtpStorage::KeePass, OtpStorage::Legacy] {
+            assert!(matches!(
+                write_otp(&mut entry, &config, storage),
+                Err(OtpEntryError::UnsupportedStorage(_))
+            ));
+        }
+        assert!(clear_otp(&mut entry));
+        assert!(entry.get_value(FIELD_OTP_PIN).is_none());
+    }
+
+    #[test]
+    fn test_keepass_hotp_counter_is_persisted() {
+        let mut db = database_with(&[
+            (FIELD_HMAC_OTP_SECRET_HEX, SECRET_HEX),
+            (FIELD_HMAC_OTP_COUNTER, "0"),
+        ]);
+        let entry = &db.document.root.group.entry[0];
+        assert!(has_otp(entry));
+        assert!(read_otp(&db, entry).unwrap().is_none());
+
+        assert_eq!(
+            next_hotp_code(&mut db, &entry_uuid()).unwrap().unwrap(),
+            "755224"
+        );
+        assert_eq!(
+            next_hotp_code(&mut db, &entry_uuid()).unwrap().unwrap(),
+            "287082"
+        );
+        assert_eq!(field(&db, FIELD_HMAC_OTP_COUNTER).as_deref(), Some("2"));
+        // 密钥保留原有的Hex字段
+        assert_eq!(
+            field(&db, FIELD_HMAC_OTP_SECRET_HEX).as_deref(),
+            Some(SECRET_HEX)
+        );
+        assert!(db.document.root.group.entry[0]
+            .times
+            .last_modification_time
+            .value()
+            .is_some());
+
+        assert!(resync_hotp(&mut db, &entry_uuid(), "254676", "287922", 10).unwrap());
+        assert_eq!(field(&db, FIELD_HMAC_OTP_COUNTER).as_deref(), Some("7"));
+        assert!(!resync_hotp(&mut db, &entry_uuid(), "755224", "287082", 10).unwrap());
+        assert_eq!(
+            next_hotp_code(&mut db, &entry_uuid()).unwrap().unwrap(),
+            "162583"
+        );
+    }
+
+    #[test]
+    fn test_otpauth_hotp_counter_is_persisted() {
+        let url = format!("otpauth://hotp/ACME:alice?secret={SECRET_BASE32}&counter=3&issuer=ACME");
+        let mut db = database_with(&[(FIELD_OTP, &url)]);
+        assert!(read_otp(&db, &db.document.root.group.entry[0])
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            next_hotp_code(&mut db, &entry_uuid()).unwrap().unwrap(),
+            "969429"
+        );
+        let url = field(&db, FIELD_OTP).unwrap();
+        assert!(url.starts_with("otpauth://hotp/ACME:alice?"));
+        assert!(url.contains("&counter=4"));
+        let (hotp, storage) = read_hotp(&db, &db.document.root.group.entry[0])
+            .unwrap()
+            .unwrap();
+        assert_eq!((hotp.counter, storage), (4, OtpStorage::KeePassXc));
+
+        // 新的计数器作为待保护值写入, 可以正常保存
+        db.encrypt_database().unwrap();
+    }
+
+    #[test]
+    fn test_write_hotp_storage() {
+        let db = database_with(&[("Title", "VPN")]);
+        let mut entry = db.document.root.group.entry[0].clone();
+        let mut hotp = Hotp {
+            issuer: None,
+            account: None,
+            algorithm: TotpAlgorithm::Sha1,
+            digits: 6,
+            counter: 5,
+            secret: b"12345678901234567890".to_vec(),
+        };
+        write_hotp(&mut entry, &hotp, OtpStorage::KeePass).unwrap();
+        let (read, storage) = read_hotp(&db, &entry).unwrap().unwrap();
+        assert_eq!((read.counter, storage), (5, OtpStorage::KeePass));
+        assert_eq!(read.current().unwrap(), "254676");
+
+        hotp.digits = 8;
+        assert!(matches!(
+            write_hotp(&mut entry, &hotp, OtpStorage::KeePass),
+            Err(OtpEntryError::UnsupportedStorage(OtpStorage::KeePass))
+        ));
+        assert!(matches!(
+            write_hotp(&mut entry, &hotp, OtpStorage::Legacy),
+            Err(OtpEntryError::UnsupportedStorage(OtpStorage::Legacy))
+        ));
+        write_hotp(&mut entry, &hotp, OtpStorage::KeePassXc).unwrap();
+        assert!(entry.get_value(FIELD_HMAC_OTP_COUNTER).is_none());
+        let (read, _) = read_hotp(&db, &entry).unwrap().unwrap();
+        assert_eq!(read.digits, 8);
+    }
+
+    fn config_steam() -> OtpConfig {
+        OtpConfig::Steam(SteamTotp {
+            secret: b"12345678901234567890".to_vec(),
+        })
+    }
+}
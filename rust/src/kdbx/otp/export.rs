@@ -0,0 +1,302 @@
+//! 将OTP配置导出为 `otpauth://` URI, 供其他验证器导入
+
+use data_encoding::BASE32_NOPAD;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use zeroize::Zeroizing;
+
+use crate::kdbx::otp::entry::{
+    read_field, read_hotp, read_otp, OtpConfig, OtpEntryError, OTPAUTH_MOTP, OTPAUTH_YANDEX,
+};
+use crate::kdbx::otp::hotp::Hotp;
+use crate::kdbx::otp::motp::{MobileOtp, MOTP_CODE_LENGTH, MOTP_PERIOD};
+use crate::kdbx::otp::rfc6238::{Rfc6238Totp, TotpAlgorithm};
+use crate::kdbx::otp::steam::{
+    SteamTotp, STEAM_CODE_LENGTH, STEAM_ENCODER, STEAM_ISSUER, STEAM_PERIOD,
+};
+use crate::kdbx::otp::yandex::{YandexTotp, YANDEX_CODE_LENGTH, YANDEX_PERIOD};
+use crate::kdbx::xml::database::KeePassDatabase;
+use crate::kdbx::xml::entities::{Entry, FIELD_TITLE, FIELD_USER_NAME};
+
+/// otpauth URL中需要转义的字符, `:` 为发行方与账户的分隔符
+const LABEL_ESCAPE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'@');
+
+/// 账户为空时使用的占位符, 与KeePassXC一致
+const EMPTY_ACCOUNT: &str = "none";
+
+/// 导出TOTP, 参数顺序固定, 始终包含算法、位数和周期
+pub fn totp_uri(totp: &Rfc6238Totp) -> Zeroizing<String> {
+    build_otpauth_url(
+        "totp",
+        totp.issuer.as_deref(),
+        totp.account.as_deref(),
+        &totp.secret,
+        Some(&totp.algorithm),
+        totp.digits,
+        &[("period", totp.period.to_string())],
+    )
+}
+
+/// 导出HOTP, 计数器为下一次使用的值
+pub fn hotp_uri(hotp: &Hotp) -> Zeroizing<String> {
+    build_otpauth_url(
+        "hotp",
+        hotp.issuer.as_deref(),
+        hotp.account.as_deref(),
+        &hotp.secret,
+        Some(&hotp.algorithm),
+        hotp.digits,
+        &[("counter", hotp.counter.to_string())],
+    )
+}
+
+/// 导出Steam令牌, 使用KeePassXC的 `encoder=steam` 约定
+pub fn steam_uri(steam: &SteamTotp, account: Option<&str>) -> Zeroizing<String> {
+    build_otpauth_url(
+        "totp",
+        Some(STEAM_ISSUER),
+        account,
+        &steam.secret,
+        Some(&TotpAlgorithm::Sha1),
+        STEAM_CODE_LENGTH as u32,
+        &[
+            ("period", STEAM_PERIOD.to_string()),
+            ("encoder", STEAM_ENCODER.to_string()),
+        ],
+    )
+}
+
+/// 导出Yandex Key, 不包含PIN
+pub fn yandex_uri(yandex: &YandexTotp) -> Zeroizing<String> {
+    build_otpauth_url(
+        OTPAUTH_YANDEX,
+        None,
+        yandex.account.as_deref(),
+        &yandex.secret,
+        Some(&TotpAlgorithm::Sha256),
+        YANDEX_CODE_LENGTH,
+        &[("period", YANDEX_PERIOD.to_string())],
+    )
+}
+
+/// 导出mOTP, 不包含PIN, 算法固定为MD5因此省略 `algorithm` 参数
+pub fn motp_uri(motp: &MobileOtp) -> Zeroizing<String> {
+    build_otpauth_url(
+        OTPAUTH_MOTP,
+        motp.issuer.as_deref(),
+        motp.account.as_deref(),
+        &motp.secret,
+        None,
+        MOTP_CODE_LENGTH as u32,
+        &[("period", MOTP_PERIOD.to_string())],
+    )
+}
+
+pub fn config_uri(config: &OtpConfig) -> Zeroizing<String> {
+    match config {
+        OtpConfig::Totp(totp) => totp_uri(totp),
+        OtpConfig::Steam(steam) => steam_uri(steam, None),
+        OtpConfig::Yandex(yandex) => yandex_uri(yandex),
+        OtpConfig::Motp(motp) => motp_uri(motp),
+    }
+}
+
+/// 导出条目的OTP配置, 优先导出TOTP
+///
+/// 配置中没有发行方或账户时分别使用条目的标题和用户名, 便于在其他验证器中辨认
+pub fn entry_otp_uri(
+    db: &KeePassDatabase,
+    entry: &Entry,
+) -> Result<Option<Zeroizing<String>>, OtpEntryError> {
+    let title = read_field(db, entry, FIELD_TITLE)?;
+    let user_name = read_field(db, entry, FIELD_USER_NAME)?;
+    let title = title.as_ref().map(|value| value.trim().to_string());
+    let user_name = user_name.as_ref().map(|value| value.trim().to_string());
+
+    if let Some(config) = read_otp(db, entry)? {
+        return Ok(Some(match config {
+            OtpConfig::Totp(mut totp) => {
+                totp.issuer = totp.issuer.or(title);
+                totp.account = totp.account.or(user_name);
+                totp_uri(&totp)
+            }
+            OtpConfig::Steam(steam) => steam_uri(&steam, user_name.as_deref()),
+            OtpConfig::Yandex(mut yandex) => {
+                yandex.account = yandex.account.or(user_name);
+                yandex_uri(&yandex)
+            }
+            OtpConfig::Motp(mut motp) => {
+                motp.issuer = motp.issuer.or(title);
+                motp.account = motp.account.or(user_name);
+                motp_uri(&motp)
+            }
+        }));
+    }
+    if let Some((mut hotp, _)) = read_hotp(db, entry)? {
+        hotp.issuer = hotp.issuer.or(title);
+        hotp.account = hotp.account.or(user_name);
+        return Ok(Some(hotp_uri(&hotp)));
+    }
+    Ok(None)
+}
+
+fn url_algorithm(algorithm: &TotpAlgorithm) -> &'static str {
+    match algorithm {
+        TotpAlgorithm::Sha1 => "SHA1",
+        TotpAlgorithm::Sha256 => "SHA256",
+        TotpAlgorithm::Sha512 => "SHA512",
+    }
+}
+
+fn build_otpauth_url(
+    otp_type: &str,
+    issuer: Option<&str>,
+    account: Option<&str>,
+    secret: &[u8],
+    algorithm: Option<&TotpAlgorithm>,
+    digits: u32,
+    params: &[(&str, String)],
+) -> Zeroizing<String> {
+    let issuer = issuer.filter(|issuer| !issuer.is_empty());
+    let account = utf8_percent_encode(
+        account
+            .filter(|account| !account.is_empty())
+            .unwrap_or(EMPTY_ACCOUNT),
+        LABEL_ESCAPE_SET,
+    );
+    let mut url = Zeroizing::new(match issuer {
+        Some(issuer) => format!(
+            "otpauth://{otp_type}/{}:{account}",
+            utf8_percent_encode(issuer, LABEL_ESCAPE_SET)
+        ),
+        None => format!("otpauth://{otp_type}/{account}"),
+    });
+    url.push_str("?secret=");
+    url.push_str(&Zeroizing::new(BASE32_NOPAD.encode(secret)));
+    if let Some(issuer) = issuer {
+        url.push_str(&format!(
+            "&issuer={}",
+            utf8_percent_encode(issuer, LABEL_ESCAPE_SET)
+        ));
+    }
+    if let Some(algorithm) = algorithm {
+        url.push_str(&format!("&algorithm={}", url_algorithm(algorithm)));
+    }
+    url.push_str(&format!("&digits={digits}"));
+    for (key, value) in params {
+        url.push_str(&format!("&{key}={value}"));
+    }
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::otp::entry::{parse_hotp_url, parse_otp_field, FIELD_OTP};
+    use crate::kdbx::otp::Totp;
+    use crate::kdbx::xml::fixtures;
+
+    const SECRET: &[u8] = b"12345678901234567890";
+    const SECRET_BASE32: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn test_totp_uri_is_canonical() {
+        let totp = Rfc6238Totp {
+            issuer: Some("ACME Co: Dev".to_string()),
+            account: Some("alice@example.com".to_string()),
+            algorithm: TotpAlgorithm::Sha256,
+            digits: 8,
+            period: 60,
+            secret: SECRET.to_vec(),
+        };
+        let uri = totp_uri(&totp);
+        assert_eq!(
+            uri.as_str(),
+            format!(
+                "otpauth://totp/ACME%20Co%3A%20Dev:alice@example.com?secret={SECRET_BASE32}\
+                 &issuer=ACME%20Co%3A%20Dev&algorithm=SHA256&digits=8&period=60"
+            )
+        );
+
+        let OtpConfig::Totp(parsed) = parse_otp_field(&uri).unwrap() else {
+            panic!("expected TOTP");
+        };
+        assert_eq!(parsed.get_issuer(), Some("ACME Co: Dev"));
+        assert_eq!(parsed.get_account(), Some("alice@example.com"));
+        assert_eq!(parsed.algorithm, TotpAlgorithm::Sha256);
+        assert_eq!((parsed.digits, parsed.period), (8, 60));
+        assert_eq!(parsed.secret, SECRET);
+    }
+
+    #[test]
+    fn test_hotp_and_steam_uri() {
+        let hotp = Hotp {
+            issuer: None,
+            account: Some("bob".to_string()),
+            algorithm: TotpAlgorithm::Sha1,
+            digits: 6,
+            counter: 42,
+            secret: SECRET.to_vec(),
+        };
+        let uri = hotp_uri(&hotp);
+        assert_eq!(
+            uri.as_str(),
+            format!("otpauth://hotp/bob?secret={SECRET_BASE32}&algorithm=SHA1&digits=6&counter=42")
+        );
+        assert_eq!(parse_hotp_url(&uri).unwrap().counter, 42);
+
+        let steam = SteamTotp {
+            secret: SECRET.to_vec(),
+        };
+        let uri = steam_uri(&steam, Some("gamer"));
+        assert!(uri.starts_with("otpauth://totp/Steam:gamer?"));
+        assert!(matches!(
+            parse_otp_field(&uri).unwrap(),
+            OtpConfig::Steam(parsed) if parsed.secret == SECRET
+        ));
+        assert!(steam_uri(&steam, None).starts_with("otpauth://totp/Steam:none?"));
+    }
+
+    #[test]
+    fn test_entry_otp_uri_uses_entry_labels() {
+        let entry = fixtures::entry_xml(
+            &fixtures::uuid(1),
+            &[
+                ("Title", "GitHub"),
+                ("UserName", "octocat"),
+                (FIELD_OTP, SECRET_BASE32),
+            ],
+            "",
+        );
+        let db = fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(10),
+            "Root",
+            "",
+            &entry,
+        ));
+        let entry = &db.document.root.group.entry[0];
+        let uri = entry_otp_uri(&db, entry).unwrap().unwrap();
+        assert_eq!(
+            uri.as_str(),
+            format!(
+                "otpauth://totp/GitHub:octocat?secret={SECRET_BASE32}\
+                 &issuer=GitHub&algorithm=SHA1&digits=6&period=30"
+            )
+        );
+
+        let entry = fixtures::entry_xml(&fixtures::uuid(2), &[("Title", "Empty")], "");
+        let db = fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(10),
+            "Root",
+            "",
+            &entry,
+        ));
+        assert!(entry_otp_uri(&db, &db.document.root.group.entry[0])
+            .unwrap()
+            .is_none());
+    }
+}
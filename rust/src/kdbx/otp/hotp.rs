@@ -0,0 +1,201 @@
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use url::Url;
+
+use crate::kdbx::otp::rfc6238::{OtpAuthUrl, Rfc6238ParseError, TotpAlgorithm};
+use crate::kdbx::otp::TotpGenerateError;
+
+/// RFC 4226: 基于计数器的一次性密码
+#[derive(Debug)]
+pub struct Hotp {
+    pub issuer: Option<String>,
+    pub account: Option<String>,
+    pub algorithm: TotpAlgorithm,
+    pub digits: u32,
+    /// 下一次生成使用的计数器
+    pub counter: u64,
+    pub secret: Vec<u8>,
+}
+
+impl Hotp {
+    /// 解析 `otpauth://hotp/...`, `counter` 参数为必填项
+    pub fn from_url(url: &Url) -> Result<Self, Rfc6238ParseError> {
+        let parsed = OtpAuthUrl::parse(url, "hotp")?;
+        let counter = parsed
+            .params
+            .get("counter")
+            .ok_or(Rfc6238ParseError::MissingParameter("counter"))?
+            .parse()?;
+
+        Ok(Hotp {
+            issuer: parsed.issuer,
+            account: parsed.account,
+            algorithm: parsed.algorithm,
+            digits: parsed.digits,
+            counter,
+            secret: parsed.secret,
+        })
+    }
+
+    /// 生成指定计数器的验证码
+    pub fn generate(&self, counter: u64) -> Result<String, TotpGenerateError> {
+        if self.secret.is_empty() {
+            return Err(TotpGenerateError::InvalidSecretLength);
+        }
+        let modulus = 10u32
+            .checked_pow(self.digits)
+            .ok_or(TotpGenerateError::InvalidDigits(self.digits))?;
+        let code = dynamic_truncation(&self.algorithm, &self.secret, counter)? % modulus;
+        Ok(format!("{:0width$}", code, width = self.digits as usize))
+    }
+
+    /// 生成当前计数器的验证码, 调用方负责递增并保存计数器
+    pub fn current(&self) -> Result<String, TotpGenerateError> {
+        self.generate(self.counter)
+    }
+
+    /// 在 `[counter, counter + look_ahead]` 范围内查找连续的两个验证码,
+    /// 找到时返回第二个验证码之后的计数器
+    pub fn resync(
+        &self,
+        first: &str,
+        second: &str,
+        look_ahead: u64,
+    ) -> Result<Option<u64>, TotpGenerateError> {
+        let end = self.counter.saturating_add(look_ahead);
+        let mut counter = self.counter;
+        while counter <= end && counter < u64::MAX {
+            if self.generate(counter)? == first && self.generate(counter + 1)? == second {
+                return Ok(counter.checked_add(2));
+            }
+            counter += 1;
+        }
+        Ok(None)
+    }
+}
+
+/// RFC 4226 5.3: HMAC后的动态截断, 返回31位整数
+pub fn dynamic_truncation(
+    algorithm: &TotpAlgorithm,
+    secret: &[u8],
+    counter: u64,
+) -> Result<u32, TotpGenerateError> {
+    let counter_bytes = counter.to_be_bytes();
+    let hmac_result = match algorithm {
+        TotpAlgorithm::Sha1 => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(secret)?;
+            mac.update(&counter_bytes);
+            mac.finalize().into_bytes().to_vec()
+        }
+        TotpAlgorithm::Sha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret)?;
+            mac.update(&counter_bytes);
+            mac.finalize().into_bytes().to_vec()
+        }
+        TotpAlgorithm::Sha512 => {
+            let mut mac = Hmac::<Sha512>::new_from_slice(secret)?;
+            mac.update(&counter_bytes);
+            mac.finalize().into_bytes().to_vec()
+        }
+    };
+
+    let offset = (hmac_result[hmac_result.len() - 1] & 0x0f) as usize;
+
+    let code = u32::from_be_bytes([
+        hmac_result[offset],
+        hmac_result[offset + 1],
+        hmac_result[offset + 2],
+        hmac_result[offset + 3],
+    ]);
+
+    Ok(code & 0x7fffffff)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const TEST_SECRET: &[u8] = b"12345678901234567890";
+
+    /// RFC 4226 附录D
+    const TEST_CODES: [&str; 10] = [
+        "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871",
+        "520489",
+    ];
+
+    fn hotp(counter: u64) -> Hotp {
+        Hotp {
+            issuer: None,
+            account: None,
+            algorithm: TotpAlgorithm::Sha1,
+            digits: 6,
+            counter,
+            secret: TEST_SECRET.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_rfc4226_vectors() {
+        let hotp = hotp(0);
+        for (counter, expected) in TEST_CODES.iter().enumerate() {
+            assert_eq!(&hotp.generate(counter as u64).unwrap(), expected);
+        }
+        assert_eq!(
+            dynamic_truncation(&TotpAlgorithm::Sha1, TEST_SECRET, 0).unwrap(),
+            1284755224
+        );
+    }
+
+    #[test]
+    fn test_parse_otpauth_hotp_url() {
+        let url = Url::from_str(
+            "otpauth://hotp/ACME:alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=3&digits=6&issuer=ACME",
+        )
+        .unwrap();
+        let hotp = Hotp::from_url(&url).unwrap();
+        assert_eq!(hotp.issuer.as_deref(), Some("ACME"));
+        assert_eq!(hotp.account.as_deref(), Some("alice"));
+        assert_eq!(hotp.counter, 3);
+        assert_eq!(hotp.current().unwrap(), TEST_CODES[3]);
+
+        let missing = Url::from_str("otpauth://hotp/alice?secret=GEZDGNBV").unwrap();
+        assert!(matches!(
+            Hotp::from_url(&missing),
+            Err(Rfc6238ParseError::MissingParameter("counter"))
+        ));
+        let totp = Url::from_str("otpauth://totp/alice?secret=GEZDGNBV&counter=1").unwrap();
+        assert!(matches!(
+            Hotp::from_url(&totp),
+            Err(Rfc6238ParseError::InvalidOtpAuthType)
+        ));
+    }
+
+    #[test]
+    fn test_resync() {
+        let hotp = hotp(1);
+        assert_eq!(
+            hotp.resync(TEST_CODES[5], TEST_CODES[6], 10).unwrap(),
+            Some(7)
+        );
+        assert_eq!(
+            hotp.resync(TEST_CODES[1], TEST_CODES[2], 0).unwrap(),
+            Some(3)
+        );
+        // 不连续或超出范围
+        assert_eq!(hotp.resync(TEST_CODES[5], TEST_CODES[7], 10).unwrap(), None);
+        assert_eq!(hotp.resync(TEST_CODES[8], TEST_CODES[9], 3).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_digits() {
+        let mut hotp = hotp(0);
+        hotp.digits = 10;
+        assert!(matches!(
+            hotp.current(),
+            Err(TotpGenerateError::InvalidDigits(10))
+        ));
+    }
+}
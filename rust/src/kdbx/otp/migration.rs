@@ -0,0 +1,458 @@
+//! Google Authenticator导出的 `otpauth-migration://offline?data=...`
+//!
+//! `data` 为Base64编码的protobuf `MigrationPayload`:
+//!
+//! ```text
+//! message MigrationPayload {
+//!   message OtpParameters {
+//!     bytes secret = 1;
+//!     string name = 2;
+//!     string issuer = 3;
+//!     Algorithm algorithm = 4;   // 0 未指定, 1 SHA1, 2 SHA256, 3 SHA512, 4 MD5
+//!     DigitCount digits = 5;     // 0 未指定, 1 六位, 2 八位
+//!     OtpType type = 6;          // 0 未指定, 1 HOTP, 2 TOTP
+//!     int64 counter = 7;
+//!   }
+//!   repeated OtpParameters otp_parameters = 1;
+//!   int32 version = 2;
+//!   int32 batch_size = 3;
+//!   int32 batch_index = 4;
+//!   int32 batch_id = 5;
+//! }
+//! ```
+
+use base64::Engine;
+use chrono::Utc;
+use thiserror::Error;
+use url::Url;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::kdbx::otp::entry::{write_hotp, write_otp, OtpConfig, OtpEntryError, OtpStorage};
+use crate::kdbx::otp::hotp::Hotp;
+use crate::kdbx::otp::rfc6238::{Rfc6238Totp, TotpAlgorithm, DEFAULT_DIGITS, DEFAULT_PERIOD};
+use crate::kdbx::xml::database::KeePassDatabase;
+use crate::kdbx::xml::entities::{Entry, Value, FIELD_TITLE, FIELD_USER_NAME};
+
+const MIGRATION_SCHEME: &str = "otpauth-migration";
+const MIGRATION_HOST: &str = "offline";
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    #[error("Not an otpauth-migration URL")]
+    NotMigrationUrl,
+
+    #[error("Missing data parameter")]
+    MissingData,
+
+    #[error("Invalid Base64 data: {0}")]
+    InvalidBase64(#[from] base64::DecodeError),
+
+    #[error("Malformed migration payload")]
+    MalformedPayload,
+
+    #[error("Unsupported OTP algorithm: {0}")]
+    UnsupportedAlgorithm(u64),
+
+    #[error("Unsupported OTP type: {0}")]
+    UnsupportedType(u64),
+
+    #[error("Group not found")]
+    GroupNotFound,
+
+    #[error("Entry error: {0}")]
+    EntryError(#[from] OtpEntryError),
+}
+
+/// 导出数据中的一个令牌
+#[derive(Debug)]
+pub enum MigrationOtp {
+    Totp(Rfc6238Totp),
+    Hotp(Hotp),
+}
+
+impl MigrationOtp {
+    pub fn issuer(&self) -> Option<&str> {
+        match self {
+            MigrationOtp::Totp(totp) => totp.issuer.as_deref(),
+            MigrationOtp::Hotp(hotp) => hotp.issuer.as_deref(),
+        }
+    }
+
+    pub fn account(&self) -> Option<&str> {
+        match self {
+            MigrationOtp::Totp(totp) => totp.account.as_deref(),
+            MigrationOtp::Hotp(hotp) => hotp.account.as_deref(),
+        }
+    }
+}
+
+/// 导出数据较多时会拆分为多个二维码, 每个二维码为一批
+#[derive(Debug)]
+pub struct MigrationBatch {
+    pub otps: Vec<MigrationOtp>,
+    pub version: i32,
+    pub batch_size: i32,
+    pub batch_index: i32,
+    pub batch_id: i32,
+}
+
+/// 解析 `otpauth-migration://offline?data=...`
+pub fn parse_migration_url(url: &str) -> Result<MigrationBatch, MigrationError> {
+    let url = Url::parse(url.trim())?;
+    if url.scheme() != MIGRATION_SCHEME || url.host_str() != Some(MIGRATION_HOST) {
+        return Err(MigrationError::NotMigrationUrl);
+    }
+    let data = url
+        .query_pairs()
+        .find(|(key, _)| key == "data")
+        .map(|(_, value)| Zeroizing::new(value.into_owned()))
+        .ok_or(MigrationError::MissingData)?;
+    // 未转义的 `+` 在查询参数中被解码为空格
+    let data = Zeroizing::new(data.replace(' ', "+"));
+    let payload =
+        Zeroizing::new(base64::engine::general_purpose::STANDARD.decode(data.as_bytes())?);
+    parse_migration_payload(&payload)
+}
+
+/// 解析protobuf编码的 `MigrationPayload`
+pub fn parse_migration_payload(payload: &[u8]) -> Result<MigrationBatch, MigrationError> {
+    let mut batch = MigrationBatch {
+        otps: Vec::new(),
+        version: 0,
+        batch_size: 0,
+        batch_index: 0,
+        batch_id: 0,
+    };
+    let mut reader = Reader::new(payload);
+    while let Some((field, value)) = reader.next_field()? {
+        match (field, value) {
+            (1, Wire::Bytes(parameters)) => batch.otps.push(parse_parameters(parameters)?),
+            (2, Wire::Varint(value)) => batch.version = value as i32,
+            (3, Wire::Varint(value)) => batch.batch_size = value as i32,
+            (4, Wire::Varint(value)) => batch.batch_index = value as i32,
+            (5, Wire::Varint(value)) => batch.batch_id = value as i32,
+            _ => {}
+        }
+    }
+    Ok(batch)
+}
+
+/// 在指定组中为每个令牌创建一个条目, 返回新条目的UUID
+///
+/// 标题使用发行方 (没有时使用账户名), 用户名使用账户名。
+/// 所有条目都创建成功后才会加入组中。
+pub fn import_migration(
+    db: &mut KeePassDatabase,
+    group: &Uuid,
+    batch: &MigrationBatch,
+    storage: OtpStorage,
+) -> Result<Vec<Uuid>, MigrationError> {
+    if db.document.root.group.find_group_path(group).is_none() {
+        return Err(MigrationError::GroupNotFound);
+    }
+
+    let now = Utc::now();
+    let mut entries = Vec::with_capacity(batch.otps.len());
+    for otp in &batch.otps {
+        let mut entry = Entry::new(Uuid::new_v4(), now);
+        let title = otp.issuer().or(otp.account()).unwrap_or_default();
+        entry.set_value(FIELD_TITLE, Value::Unprotected(title.to_string()));
+        entry.set_value(
+            FIELD_USER_NAME,
+            Value::Unprotected(otp.account().unwrap_or_default().to_string()),
+        );
+        match otp {
+            MigrationOtp::Totp(totp) => {
+                let config = OtpConfig::Totp(Rfc6238Totp {
+                    issuer: totp.issuer.clone(),
+                    account: totp.account.clone(),
+                    algorithm: totp.algorithm.clone(),
+                    digits: totp.digits,
+                    period: totp.period,
+                    secret: totp.secret.clone(),
+                });
+                write_otp(&mut entry, &config, storage)?;
+            }
+            MigrationOtp::Hotp(hotp) => write_hotp(&mut entry, hotp, storage)?,
+        }
+        entries.push(entry);
+    }
+
+    let uuids = entries.iter().map(|entry| *entry.uuid.value()).collect();
+    let target = db
+        .document
+        .root
+        .group
+        .find_group_mut(group)
+        .ok_or(MigrationError::GroupNotFound)?;
+    target.entry.extend(entries);
+    Ok(uuids)
+}
+
+fn parse_parameters(data: &[u8]) -> Result<MigrationOtp, MigrationError> {
+    let mut secret = Vec::new();
+    let mut name = String::new();
+    let mut issuer = String::new();
+    let mut algorithm = 0;
+    let mut digits = 0;
+    let mut otp_type = 0;
+    let mut counter = 0;
+
+    let mut reader = Reader::new(data);
+    while let Some((field, value)) = reader.next_field()? {
+        match (field, value) {
+            (1, Wire::Bytes(value)) => secret = value.to_vec(),
+            (2, Wire::Bytes(value)) => name = string(value)?,
+            (3, Wire::Bytes(value)) => issuer = string(value)?,
+            (4, Wire::Varint(value)) => algorithm = value,
+            (5, Wire::Varint(value)) => digits = value,
+            (6, Wire::Varint(value)) => otp_type = value,
+            (7, Wire::Varint(value)) => counter = value,
+            _ => {}
+        }
+    }
+
+    let algorithm = match algorithm {
+        0 | 1 => TotpAlgorithm::Sha1,
+        2 => TotpAlgorithm::Sha256,
+        3 => TotpAlgorithm::Sha512,
+        other => return Err(MigrationError::UnsupportedAlgorithm(other)),
+    };
+    let digits = match digits {
+        2 => 8,
+        _ => DEFAULT_DIGITS,
+    };
+    let issuer = Some(issuer).filter(|issuer| !issuer.is_empty());
+    // name通常为 `发行方:账户`
+    let account = match &issuer {
+        Some(issuer) => name
+            .strip_prefix(issuer.as_str())
+            .and_then(|rest| rest.strip_prefix(':'))
+            .unwrap_or(&name)
+            .trim()
+            .to_string(),
+        None => name,
+    };
+    let account = Some(account).filter(|account| !account.is_empty());
+
+    match otp_type {
+        0 | 2 => Ok(MigrationOtp::Totp(Rfc6238Totp {
+            issuer,
+            account,
+            algorithm,
+            digits,
+            period: DEFAULT_PERIOD,
+            secret,
+        })),
+        1 => Ok(MigrationOtp::Hotp(Hotp {
+            issuer,
+            account,
+            algorithm,
+            digits,
+            counter,
+            secret,
+        })),
+        other => Err(MigrationError::UnsupportedType(other)),
+    }
+}
+
+fn string(value: &[u8]) -> Result<String, MigrationError> {
+    String::from_utf8(value.to_vec()).map_err(|_| MigrationError::MalformedPayload)
+}
+
+/// protobuf字段值, 仅区分本格式用到的类型
+enum Wire<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, MigrationError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or(MigrationError::MalformedPayload)?;
+            self.position += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(MigrationError::MalformedPayload)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MigrationError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(MigrationError::MalformedPayload)?;
+        let value = &self.data[self.position..end];
+        self.position = end;
+        Ok(value)
+    }
+
+    /// 读取下一个字段, 返回 (字段编号, 值)
+    fn next_field(&mut self) -> Result<Option<(u64, Wire<'a>)>, MigrationError> {
+        if self.position >= self.data.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0x07 {
+            0 => Wire::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Wire::Fixed
+            }
+            2 => {
+                let len = usize::try_from(self.varint()?)
+                    .map_err(|_| MigrationError::MalformedPayload)?;
+                Wire::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Wire::Fixed
+            }
+            _ => return Err(MigrationError::MalformedPayload),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::otp::entry::{read_hotp, read_otp};
+    use crate::kdbx::otp::Totp;
+    use crate::kdbx::xml::fixtures;
+
+    /// Google Authenticator导出的单个TOTP, 密钥为 `JBSWY3DPEHPK3PXP`
+    const SINGLE_TOTP: &str = "otpauth-migration://offline?data=CjEKCkhlbGxvId6tvu8SGEV4YW1wbGU6YWxpY2VAZ29vZ2xlLmNvbRoHRXhhbXBsZTAC";
+
+    /// 一个SHA-256/8位的TOTP和一个计数器为300的HOTP, 批次 2/1
+    const TOTP_AND_HOTP: &str = "otpauth-migration://offline?data=Cj4KFDEyMzQ1Njc4OTAxMjM0NTY3ODkwEhdBQ01FIENvOmJvYkBleGFtcGxlLmNvbRoHQUNNRSBDbyACKAIwAgokChQxMjM0NTY3ODkwMTIzNDU2Nzg5MBIDdnBuIAEoATABOKwCEAEYAiABKJWa7zo%3D";
+
+    #[test]
+    fn test_parse_single_totp() {
+        let batch = parse_migration_url(SINGLE_TOTP).unwrap();
+        assert_eq!(batch.otps.len(), 1);
+        let MigrationOtp::Totp(totp) = &batch.otps[0] else {
+            panic!("expected TOTP");
+        };
+        assert_eq!(totp.secret, b"Hello!\xde\xad\xbe\xef");
+        assert_eq!(totp.issuer.as_deref(), Some("Example"));
+        assert_eq!(totp.account.as_deref(), Some("alice@google.com"));
+        assert_eq!(totp.algorithm, TotpAlgorithm::Sha1);
+        assert_eq!((totp.digits, totp.period), (6, 30));
+    }
+
+    #[test]
+    fn test_parse_batch_with_hotp() {
+        let batch = parse_migration_url(TOTP_AND_HOTP).unwrap();
+        assert_eq!(
+            (
+                batch.version,
+                batch.batch_size,
+                batch.batch_index,
+                batch.batch_id
+            ),
+            (1, 2, 1, 123456789)
+        );
+        assert_eq!(batch.otps.len(), 2);
+
+        let MigrationOtp::Totp(totp) = &batch.otps[0] else {
+            panic!("expected TOTP");
+        };
+        assert_eq!(totp.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(totp.account.as_deref(), Some("bob@example.com"));
+        assert_eq!(totp.algorithm, TotpAlgorithm::Sha256);
+        assert_eq!(totp.digits, 8);
+
+        let MigrationOtp::Hotp(hotp) = &batch.otps[1] else {
+            panic!("expected HOTP");
+        };
+        assert_eq!(hotp.issuer, None);
+        assert_eq!(hotp.account.as_deref(), Some("vpn"));
+        assert_eq!(hotp.counter, 300);
+        assert_eq!(hotp.secret, b"12345678901234567890");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            parse_migration_url("otpauth://totp/a?secret=AAAA"),
+            Err(MigrationError::NotMigrationUrl)
+        ));
+        assert!(matches!(
+            parse_migration_url("otpauth-migration://offline?foo=bar"),
+            Err(MigrationError::MissingData)
+        ));
+        // 长度超出数据范围
+        assert!(matches!(
+            parse_migration_payload(&[0x0a, 0x10, 0x0a]),
+            Err(MigrationError::MalformedPayload)
+        ));
+        // MD5
+        assert!(matches!(
+            parse_migration_payload(&[0x0a, 0x02, 0x20, 0x04]),
+            Err(MigrationError::UnsupportedAlgorithm(4))
+        ));
+    }
+
+    #[test]
+    fn test_import_creates_entries() {
+        let mut db = fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(10),
+            "Root",
+            "",
+            &fixtures::group_xml(&fixtures::uuid(11), "Imported", "", ""),
+        ));
+        let mut group_bytes = [0u8; 16];
+        group_bytes[15] = 11;
+        let group = Uuid::from_bytes(group_bytes);
+
+        let batch = parse_migration_url(TOTP_AND_HOTP).unwrap();
+        let uuids = import_migration(&mut db, &group, &batch, OtpStorage::KeePassXc).unwrap();
+        assert_eq!(uuids.len(), 2);
+        assert_eq!(db.document.root.group.group[0].entry.len(), 2);
+
+        let totp_entry = db.document.root.group.find_entry(&uuids[0]).unwrap();
+        let title = db
+            .get_value_string(totp_entry.get_value(FIELD_TITLE).unwrap())
+            .unwrap();
+        assert_eq!(title, "ACME Co");
+        let totp = read_otp(&db, totp_entry).unwrap().unwrap().into_totp();
+        assert_eq!(totp.generate(59).unwrap().code, "32247374");
+
+        let hotp_entry = db.document.root.group.find_entry(&uuids[1]).unwrap();
+        let (hotp, _) = read_hotp(&db, hotp_entry).unwrap().unwrap();
+        assert_eq!(hotp.counter, 300);
+
+        // 旧版格式不能保存SHA-256的TOTP, 导入失败时不会添加任何条目
+        assert!(matches!(
+            import_migration(&mut db, &group, &batch, OtpStorage::Legacy),
+            Err(MigrationError::EntryError(_))
+        ));
+        assert_eq!(db.document.root.group.group[0].entry.len(), 2);
+        assert!(matches!(
+            import_migration(&mut db, &Uuid::nil(), &batch, OtpStorage::KeePassXc),
+            Err(MigrationError::GroupNotFound)
+        ));
+    }
+}
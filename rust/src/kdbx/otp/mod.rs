@@ -1,8 +1,17 @@
+pub mod entry;
+pub mod export;
+pub mod hotp;
+pub mod migration;
+pub mod motp;
+pub mod qr;
 pub mod rfc6238;
 pub mod steam;
+pub mod yandex;
 
 use thiserror::Error;
 
+use crate::crypto::memory_crypt::SecureDataError;
+
 #[derive(Debug, Error)]
 pub enum TotpGenerateError {
     #[error("Invalid secret key length")]
@@ -11,6 +20,18 @@ pub enum TotpGenerateError {
     #[error("HMAC key length invalid: {0}")]
     HmacKeyError(#[from] hmac::digest::InvalidLength),
     
+    #[error("Invalid number of digits: {0}")]
+    InvalidDigits(u32),
+
+    #[error("Invalid secret key checksum")]
+    InvalidChecksum,
+
+    #[error("PIN is required")]
+    MissingPin,
+
+    #[error("Secure data error: {0}")]
+    SecureData(#[from] SecureDataError),
+
     #[error("Invalid timestamp")]
     InvalidTimestamp,
 }
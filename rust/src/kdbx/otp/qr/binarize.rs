@@ -0,0 +1,153 @@
+//! 灰度图像二值化
+
+use crate::kdbx::otp::qr::image::LumaImage;
+
+const BLOCK_SIZE: usize = 8;
+/// 块内亮度差小于该值时视为纯色块
+const MIN_DYNAMIC_RANGE: u8 = 24;
+
+/// 二值图像, `true` 为深色
+#[derive(Debug, Clone)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    bits: Vec<bool>,
+}
+
+impl Bitmap {
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.bits[y * self.width + x]
+    }
+
+    #[cfg(test)]
+    pub fn from_fn(width: usize, height: usize, dark: impl Fn(usize, usize) -> bool) -> Self {
+        let mut bits = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                bits.push(dark(x, y));
+            }
+        }
+        Self {
+            width,
+            height,
+            bits,
+        }
+    }
+}
+
+/// 以Otsu法求得的全局阈值二值化, 适合截图等光照均匀的图像
+pub fn global(image: &LumaImage) -> Bitmap {
+    let mut histogram = [0u64; 256];
+    for &pixel in &image.pixels {
+        histogram[pixel as usize] += 1;
+    }
+    let total = image.pixels.len() as f64;
+    let weighted_sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(value, &count)| value as f64 * count as f64)
+        .sum();
+
+    let mut threshold = 127u8;
+    let mut best_variance = -1.0;
+    let mut background = 0.0;
+    let mut background_sum = 0.0;
+    for (value, &count) in histogram.iter().enumerate() {
+        background += count as f64;
+        if background == 0.0 {
+            continue;
+        }
+        let foreground = total - background;
+        if foreground == 0.0 {
+            break;
+        }
+        background_sum += value as f64 * count as f64;
+        let background_mean = background_sum / background;
+        let foreground_mean = (weighted_sum - background_sum) / foreground;
+        let variance = background * foreground * (background_mean - foreground_mean).powi(2);
+        if variance > best_variance {
+            best_variance = variance;
+            threshold = value as u8;
+        }
+    }
+
+    Bitmap {
+        width: image.width,
+        height: image.height,
+        bits: image.pixels.iter().map(|&p| p <= threshold).collect(),
+    }
+}
+
+/// 以8x8块为单位的局部阈值二值化, 适合照片中光照不均的情况
+///
+/// 算法与ZXing的HybridBinarizer相同, 图像过小时退回全局阈值
+pub fn hybrid(image: &LumaImage) -> Bitmap {
+    let (width, height) = (image.width, image.height);
+    if width < BLOCK_SIZE * 5 || height < BLOCK_SIZE * 5 {
+        return global(image);
+    }
+    let sub_width = width.div_ceil(BLOCK_SIZE);
+    let sub_height = height.div_ceil(BLOCK_SIZE);
+    let max_x = width - BLOCK_SIZE;
+    let max_y = height - BLOCK_SIZE;
+
+    let mut black_points = vec![0u32; sub_width * sub_height];
+    for y in 0..sub_height {
+        let y_offset = (y * BLOCK_SIZE).min(max_y);
+        for x in 0..sub_width {
+            let x_offset = (x * BLOCK_SIZE).min(max_x);
+            let (mut sum, mut min, mut max) = (0u32, u8::MAX, 0u8);
+            for yy in 0..BLOCK_SIZE {
+                for xx in 0..BLOCK_SIZE {
+                    let pixel = image.get(x_offset + xx, y_offset + yy);
+                    sum += pixel as u32;
+                    min = min.min(pixel);
+                    max = max.max(pixel);
+                }
+            }
+            let mut average = sum / (BLOCK_SIZE * BLOCK_SIZE) as u32;
+            if max - min <= MIN_DYNAMIC_RANGE {
+                // 纯色块默认视为背景, 若与周围的深色块相近则沿用其阈值
+                average = min as u32 / 2;
+                if x > 0 && y > 0 {
+                    let neighbors = (black_points[(y - 1) * sub_width + x]
+                        + 2 * black_points[y * sub_width + x - 1]
+                        + black_points[(y - 1) * sub_width + x - 1])
+                        / 4;
+                    if (min as u32) < neighbors {
+                        average = neighbors;
+                    }
+                }
+            }
+            black_points[y * sub_width + x] = average;
+        }
+    }
+
+    let cap = |value: usize, max: usize| value.clamp(2, max);
+    let mut bits = vec![false; width * height];
+    for y in 0..sub_height {
+        let y_offset = (y * BLOCK_SIZE).min(max_y);
+        let top = cap(y, sub_height - 3);
+        for x in 0..sub_width {
+            let x_offset = (x * BLOCK_SIZE).min(max_x);
+            let left = cap(x, sub_width - 3);
+            let mut sum = 0;
+            for yy in top - 2..=top + 2 {
+                for xx in left - 2..=left + 2 {
+                    sum += black_points[yy * sub_width + xx];
+                }
+            }
+            let threshold = sum / 25;
+            for yy in y_offset..y_offset + BLOCK_SIZE {
+                for xx in x_offset..x_offset + BLOCK_SIZE {
+                    bits[yy * width + xx] = (image.get(xx, yy) as u32) <= threshold;
+                }
+            }
+        }
+    }
+    Bitmap {
+        width,
+        height,
+        bits,
+    }
+}
@@ -0,0 +1,599 @@
+//! 从已采样的模块矩阵中读取QR码内容 (ISO/IEC 18004)
+
+use crate::kdbx::otp::qr::reed_solomon;
+use crate::kdbx::otp::qr::QrError;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// 纠错等级, 顺序与下列表格一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcLevel {
+    Low,
+    Medium,
+    Quartile,
+    High,
+}
+
+impl EcLevel {
+    fn ordinal(self) -> usize {
+        self as usize
+    }
+
+    /// 格式信息中的2位编码
+    fn from_format_bits(bits: u32) -> Self {
+        match bits {
+            1 => EcLevel::Low,
+            0 => EcLevel::Medium,
+            3 => EcLevel::Quartile,
+            _ => EcLevel::High,
+        }
+    }
+}
+
+#[rustfmt::skip]
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+
+#[rustfmt::skip]
+const NUM_ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// 正方形的模块矩阵, `true` 为深色
+#[derive(Debug, Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct BitMatrix {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+impl BitMatrix {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            modules: vec![false; size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+    }
+
+    /// 沿主对角线翻转, 用于读取镜像的二维码
+    pub fn transpose(&self) -> Self {
+        let mut result = Self::new(self.size);
+        for y in 0..self.size {
+            for x in 0..self.size {
+                result.set(y, x, self.get(x, y));
+            }
+        }
+        result
+    }
+}
+
+/// 版本对应的边长
+pub fn size_for_version(version: usize) -> usize {
+    version * 4 + 17
+}
+
+/// 对齐图案中心的坐标 (行列相同)
+pub fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let last = size_for_version(version) - 7;
+    let mut positions = vec![6];
+    for i in 0..count - 1 {
+        positions.insert(1, last - i * step);
+    }
+    positions
+}
+
+/// 数据和纠错码可用的模块数
+fn raw_data_modules(version: usize) -> usize {
+    let mut result = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let count = version / 7 + 2;
+        result -= (25 * count - 10) * count - 55;
+        if version >= 7 {
+            result -= 36;
+        }
+    }
+    result
+}
+
+/// 15位格式信息 (BCH(15,5), 与0x5412异或)
+pub fn format_bits(ec_bits: u32, mask: u32) -> u32 {
+    let data = (ec_bits << 3) | mask;
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    ((data << 10) | remainder) ^ 0x5412
+}
+
+/// 18位版本信息 (BCH(18,6)), 版本7及以上使用
+pub fn version_bits(version: usize) -> u32 {
+    let data = version as u32;
+    let mut remainder = data;
+    for _ in 0..12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+    }
+    (data << 12) | remainder
+}
+
+/// 功能图案 (定位、分隔符、格式、时序、对齐、版本信息) 所占的模块
+pub fn function_modules(version: usize) -> BitMatrix {
+    let size = size_for_version(version);
+    let mut matrix = BitMatrix::new(size);
+    let mut fill = |x0: usize, y0: usize, width: usize, height: usize| {
+        for y in y0..y0 + height {
+            for x in x0..x0 + width {
+                matrix.set(x, y, true);
+            }
+        }
+    };
+    // 定位图案、分隔符和格式信息
+    fill(0, 0, 9, 9);
+    fill(size - 8, 0, 8, 9);
+    fill(0, size - 8, 9, 8);
+    // 时序图案
+    fill(6, 0, 1, size);
+    fill(0, 6, size, 1);
+
+    let positions = alignment_positions(version);
+    let last = positions.len().wrapping_sub(1);
+    for (i, &x) in positions.iter().enumerate() {
+        for (j, &y) in positions.iter().enumerate() {
+            if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                continue;
+            }
+            fill(x - 2, y - 2, 5, 5);
+        }
+    }
+
+    if version >= 7 {
+        fill(size - 11, 0, 3, 6);
+        fill(0, size - 11, 6, 3);
+    }
+    matrix
+}
+
+pub fn mask_applies(mask: u32, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// 按数据区的之字形顺序遍历非功能模块
+pub fn data_module_order(version: usize) -> Vec<(usize, usize)> {
+    let size = size_for_version(version);
+    let function = function_modules(version);
+    let mut order = Vec::with_capacity(raw_data_modules(version));
+    let mut right = size as isize - 1;
+    while right >= 1 {
+        if right == 6 {
+            right = 5;
+        }
+        for vertical in 0..size {
+            for j in 0..2 {
+                let x = (right - j) as usize;
+                let upward = (right + 1) & 2 == 0;
+                let y = if upward {
+                    size - 1 - vertical
+                } else {
+                    vertical
+                };
+                if !function.get(x, y) {
+                    order.push((x, y));
+                }
+            }
+        }
+        right -= 2;
+    }
+    order
+}
+
+/// 数据码字与纠错码的分块方式: (块数, 每块纠错码长度, 码字总数)
+pub fn block_layout(version: usize, level: EcLevel) -> (usize, usize, usize) {
+    (
+        NUM_ERROR_CORRECTION_BLOCKS[level.ordinal()][version] as usize,
+        ECC_CODEWORDS_PER_BLOCK[level.ordinal()][version] as usize,
+        raw_data_modules(version) / 8,
+    )
+}
+
+/// 读取模块矩阵中的内容, 返回解码后的原始字节
+pub fn decode_matrix(matrix: &BitMatrix) -> Result<Vec<u8>, QrError> {
+    let size = matrix.size();
+    if size < 21 || !(size - 17).is_multiple_of(4) {
+        return Err(QrError::InvalidVersion);
+    }
+    let mut version = (size - 17) / 4;
+    if version >= 7 {
+        // 尺寸由图像估计而来, 以版本信息为准
+        version = read_version(matrix).ok_or(QrError::InvalidVersion)?;
+        if size_for_version(version) != size {
+            return Err(QrError::InvalidVersion);
+        }
+    }
+    let (level, mask) = read_format(matrix).ok_or(QrError::InvalidFormat)?;
+
+    let order = data_module_order(version);
+    let (blocks, ecc_len, total) = block_layout(version, level);
+    let mut codewords = vec![0u8; total];
+    for (i, &(x, y)) in order.iter().take(total * 8).enumerate() {
+        if matrix.get(x, y) ^ mask_applies(mask, x, y) {
+            codewords[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+
+    let data = deinterleave(&codewords, blocks, ecc_len)?;
+    decode_segments(&data, version)
+}
+
+/// 两份格式信息中与有效值汉明距离最小者, 距离超过3时视为无法读取
+fn read_format(matrix: &BitMatrix) -> Option<(EcLevel, u32)> {
+    let size = matrix.size();
+    let bit = |x: usize, y: usize| matrix.get(x, y) as u32;
+
+    let mut first = 0;
+    for i in 0..=5 {
+        first |= bit(8, i) << i;
+    }
+    first |= bit(8, 7) << 6;
+    first |= bit(8, 8) << 7;
+    first |= bit(7, 8) << 8;
+    for i in 9..15 {
+        first |= bit(14 - i, 8) << i;
+    }
+
+    let mut second = 0;
+    for i in 0..8 {
+        second |= bit(size - 1 - i, 8) << i;
+    }
+    for i in 8..15 {
+        second |= bit(8, size - 15 + i) << i;
+    }
+
+    let mut best = None;
+    let mut best_distance = u32::MAX;
+    for ec_bits in 0..4 {
+        for mask in 0..8 {
+            let expected = format_bits(ec_bits, mask);
+            for actual in [first, second] {
+                let distance = (expected ^ actual).count_ones();
+                if distance < best_distance {
+                    best_distance = distance;
+                    best = Some((EcLevel::from_format_bits(ec_bits), mask));
+                }
+            }
+        }
+    }
+    best.filter(|_| best_distance <= 3)
+}
+
+fn read_version(matrix: &BitMatrix) -> Option<usize> {
+    let size = matrix.size();
+    let mut first = 0;
+    let mut second = 0;
+    for i in 0..18 {
+        let a = size - 11 + i % 3;
+        let b = i / 3;
+        first |= (matrix.get(a, b) as u32) << i;
+        second |= (matrix.get(b, a) as u32) << i;
+    }
+    let mut best = None;
+    let mut best_distance = u32::MAX;
+    for version in 7..=40 {
+        let expected = version_bits(version);
+        for actual in [first, second] {
+            let distance = (expected ^ actual).count_ones();
+            if distance < best_distance {
+                best_distance = distance;
+                best = Some(version);
+            }
+        }
+    }
+    best.filter(|_| best_distance <= 3)
+}
+
+/// 还原交织的码字并逐块纠错, 返回数据码字
+fn deinterleave(codewords: &[u8], blocks: usize, ecc_len: usize) -> Result<Vec<u8>, QrError> {
+    let total = codewords.len();
+    let short_blocks = blocks - total % blocks;
+    let short_len = total / blocks;
+
+    // 短块在数据末尾补一个占位字节, 使所有块等长
+    let mut buffers = vec![Vec::with_capacity(short_len + 1); blocks];
+    let mut next = codewords.iter();
+    for i in 0..=short_len {
+        for (j, buffer) in buffers.iter_mut().enumerate() {
+            if i == short_len - ecc_len && j < short_blocks {
+                buffer.push(0);
+            } else {
+                buffer.push(*next.next().ok_or(QrError::InvalidFormat)?);
+            }
+        }
+    }
+
+    let mut data = Vec::with_capacity(total - blocks * ecc_len);
+    for (j, mut buffer) in buffers.into_iter().enumerate() {
+        if j < short_blocks {
+            buffer.remove(short_len - ecc_len);
+        }
+        reed_solomon::correct(&mut buffer, ecc_len).ok_or(QrError::TooManyErrors)?;
+        data.extend_from_slice(&buffer[..buffer.len() - ecc_len]);
+    }
+    Ok(data)
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.position
+    }
+
+    fn read(&mut self, bits: usize) -> Result<u32, QrError> {
+        if bits > self.remaining() {
+            return Err(QrError::InvalidData);
+        }
+        let mut value = 0;
+        for _ in 0..bits {
+            let bit = (self.data[self.position / 8] >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | bit as u32;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+/// 解析数据段, 支持数字、字母数字和字节模式
+fn decode_segments(data: &[u8], version: usize) -> Result<Vec<u8>, QrError> {
+    let range = match version {
+        1..=9 => 0,
+        10..=26 => 1,
+        _ => 2,
+    };
+    let mut reader = BitReader { data, position: 0 };
+    let mut result = Vec::new();
+    while reader.remaining() >= 4 {
+        match reader.read(4)? {
+            0 => break,
+            // 数字
+            1 => {
+                let mut count = reader.read([10, 12, 14][range])? as usize;
+                while count >= 3 {
+                    let value = reader.read(10)?;
+                    if value >= 1000 {
+                        return Err(QrError::InvalidData);
+                    }
+                    result.extend(format!("{value:03}").bytes());
+                    count -= 3;
+                }
+                if count > 0 {
+                    let bits = if count == 2 { 7 } else { 4 };
+                    let value = reader.read(bits)?;
+                    result.extend(format!("{value:0count$}").bytes());
+                }
+            }
+            // 字母数字
+            2 => {
+                let mut count = reader.read([9, 11, 13][range])? as usize;
+                while count >= 2 {
+                    let value = reader.read(11)? as usize;
+                    let (high, low) = (value / 45, value % 45);
+                    if high >= 45 {
+                        return Err(QrError::InvalidData);
+                    }
+                    result.push(ALPHANUMERIC[high]);
+                    result.push(ALPHANUMERIC[low]);
+                    count -= 2;
+                }
+                if count == 1 {
+                    let value = reader.read(6)? as usize;
+                    result.push(*ALPHANUMERIC.get(value).ok_or(QrError::InvalidData)?);
+                }
+            }
+            // 结构化链接, 忽略序号和校验
+            3 => {
+                reader.read(16)?;
+            }
+            // 字节
+            4 => {
+                let count = reader.read([8, 16, 16][range])?;
+                for _ in 0..count {
+                    result.push(reader.read(8)? as u8);
+                }
+            }
+            // FNC1
+            5 => {}
+            9 => {
+                reader.read(8)?;
+            }
+            // ECI, 内容按UTF-8处理, 仅跳过指示符
+            7 => {
+                let first = reader.read(8)?;
+                if first & 0x80 == 0x80 {
+                    let extra = if first & 0xC0 == 0x80 { 8 } else { 16 };
+                    reader.read(extra)?;
+                }
+            }
+            mode => return Err(QrError::UnsupportedMode(mode)),
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use qrcodegen::{Mask, QrCode, QrCodeEcc, QrSegment, Version};
+
+    #[test]
+    fn test_alignment_positions() {
+        assert!(alignment_positions(1).is_empty());
+        assert_eq!(alignment_positions(2), vec![6, 18]);
+        assert_eq!(alignment_positions(7), vec![6, 22, 38]);
+        assert_eq!(alignment_positions(32), vec![6, 34, 60, 86, 112, 138]);
+        assert_eq!(alignment_positions(39), vec![6, 26, 54, 82, 110, 138, 166]);
+        assert_eq!(alignment_positions(40), vec![6, 30, 58, 86, 114, 142, 170]);
+    }
+
+    #[test]
+    fn test_format_and_version_bits() {
+        // ISO/IEC 18004 附录C的示例: M级, 掩码5
+        assert_eq!(format_bits(0, 5), 0b100000011001110);
+        assert_eq!(version_bits(7), 0x07C94);
+        assert_eq!(version_bits(40), 0x28C69);
+    }
+
+    fn matrix(code: &QrCode) -> BitMatrix {
+        let size = code.size() as usize;
+        let mut matrix = BitMatrix::new(size);
+        for y in 0..size {
+            for x in 0..size {
+                matrix.set(x, y, code.get_module(x as i32, y as i32));
+            }
+        }
+        matrix
+    }
+
+    #[test]
+    fn test_decode_generated_matrices() {
+        let levels = [
+            QrCodeEcc::Low,
+            QrCodeEcc::Medium,
+            QrCodeEcc::Quartile,
+            QrCodeEcc::High,
+        ];
+        for version in [1u8, 2, 5, 7, 10, 20, 27, 40] {
+            for (i, &level) in levels.iter().enumerate() {
+                let text: Vec<u8> = (0..version as usize * 5)
+                    .map(|n| (n * 31 % 251) as u8)
+                    .collect();
+                let segments = [QrSegment::make_bytes(&text)];
+                let code = QrCode::encode_segments_advanced(
+                    &segments,
+                    level,
+                    Version::new(version),
+                    Version::MAX,
+                    Some(Mask::new(i as u8 * 2 + version % 2)),
+                    false,
+                )
+                .unwrap();
+                let mut matrix = matrix(&code);
+                assert_eq!(decode_matrix(&matrix).unwrap(), text, "version {version}");
+
+                // 翻转少量数据模块后仍可纠正
+                for &(x, y) in data_module_order(code.version().value() as usize)
+                    .iter()
+                    .step_by(97)
+                    .take(3)
+                {
+                    matrix.set(x, y, !matrix.get(x, y));
+                }
+                assert_eq!(decode_matrix(&matrix).unwrap(), text, "version {version}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_segment_modes() {
+        let segments = [
+            QrSegment::make_numeric("0123456789"),
+            QrSegment::make_alphanumeric("OTPAUTH://TOTP/"),
+            QrSegment::make_eci(26),
+            QrSegment::make_bytes("密钥".as_bytes()),
+        ];
+        let code = QrCode::encode_segments(&segments, QrCodeEcc::Medium).unwrap();
+        assert_eq!(
+            decode_matrix(&matrix(&code)).unwrap(),
+            "0123456789OTPAUTH://TOTP/密钥".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_raw_data_modules() {
+        assert_eq!(raw_data_modules(1) / 8, 26);
+        assert_eq!(raw_data_modules(7) / 8, 196);
+        assert_eq!(raw_data_modules(40) / 8, 3706);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(128))]
+
+        /// 随机翻转模块(包括格式和版本信息), 解码不应panic
+        #[test]
+        fn test_decode_corrupted_no_panic(
+            version in 1u8..=40,
+            flips in vec(any::<u32>(), 0..400),
+        ) {
+            let text: Vec<u8> = (0..version as usize * 5).map(|n| n as u8).collect();
+            let code = QrCode::encode_segments_advanced(
+                &[QrSegment::make_bytes(&text)],
+                QrCodeEcc::Low,
+                Version::new(version),
+                Version::new(version),
+                None,
+                false,
+            )
+            .unwrap();
+            let mut matrix = matrix(&code);
+            let size = matrix.size();
+            for flip in flips {
+                let (x, y) = (flip as usize % size, flip as usize / size % size);
+                matrix.set(x, y, !matrix.get(x, y));
+            }
+            let _ = decode_matrix(&matrix);
+        }
+
+        #[test]
+        fn test_decode_random_matrix_no_panic(
+            size in 0usize..=181,
+            bits in vec(any::<bool>(), 181 * 181),
+        ) {
+            let mut matrix = BitMatrix::new(size);
+            for y in 0..size {
+                for x in 0..size {
+                    matrix.set(x, y, bits[y * size + x]);
+                }
+            }
+            let _ = decode_matrix(&matrix);
+        }
+    }
+}
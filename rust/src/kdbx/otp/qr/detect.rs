@@ -0,0 +1,774 @@
+//! 在二值图像中定位QR码并采样模块矩阵
+//!
+//! 定位图案的查找与透视变换参照ZXing的实现, 对齐图案改为按模板匹配搜索。
+
+use crate::kdbx::otp::qr::binarize::Bitmap;
+use crate::kdbx::otp::qr::decode::BitMatrix;
+
+/// 最多尝试的定位图案组合数
+const MAX_TRIPLES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+impl Point {
+    fn distance(self, other: Point) -> f32 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FinderPattern {
+    center: Point,
+    module_size: f32,
+    count: u32,
+}
+
+impl FinderPattern {
+    fn about_equals(&self, module_size: f32, x: f32, y: f32) -> bool {
+        if (y - self.center.y).abs() <= module_size && (x - self.center.x).abs() <= module_size {
+            let difference = (module_size - self.module_size).abs();
+            return difference <= 1.0 || difference <= self.module_size;
+        }
+        false
+    }
+
+    /// 按出现次数加权合并
+    fn combine(&self, module_size: f32, x: f32, y: f32) -> Self {
+        let count = self.count as f32;
+        let total = count + 1.0;
+        FinderPattern {
+            center: Point {
+                x: (count * self.center.x + x) / total,
+                y: (count * self.center.y + y) / total,
+            },
+            module_size: (count * self.module_size + module_size) / total,
+            count: self.count + 1,
+        }
+    }
+}
+
+/// 左上、右上、左下三个定位图案
+#[derive(Debug, Clone, Copy)]
+struct FinderTriple {
+    top_left: Point,
+    top_right: Point,
+    bottom_left: Point,
+}
+
+/// 在图像中查找QR码, 返回可能的模块矩阵, 按可信度排序
+///
+/// 由于图像中的尺寸估计可能存在偏差, 同一个码会给出多个候选尺寸
+pub fn detect(image: &Bitmap) -> Vec<BitMatrix> {
+    let finder = FinderPatternFinder::new(image);
+    let mut results = Vec::new();
+    for triple in finder.find() {
+        let module_size = calculate_module_size(image, &triple);
+        if !module_size.is_finite() || module_size < 1.0 {
+            continue;
+        }
+        let Some(estimate) = estimate_dimension(&triple, module_size) else {
+            continue;
+        };
+        for dimension in [estimate, estimate + 4, estimate.wrapping_sub(4)] {
+            if !(21..=177).contains(&dimension) {
+                continue;
+            }
+            if let Some(matrix) = sample(image, &triple, module_size, dimension) {
+                results.push(matrix);
+            }
+        }
+    }
+    results
+}
+
+struct FinderPatternFinder<'a> {
+    image: &'a Bitmap,
+    candidates: Vec<FinderPattern>,
+}
+
+fn found_pattern_cross(state: &[u32; 5]) -> bool {
+    if state.contains(&0) {
+        return false;
+    }
+    let total: u32 = state.iter().sum();
+    if total < 7 {
+        return false;
+    }
+    let module_size = total as f32 / 7.0;
+    let variance = module_size / 2.0;
+    (module_size - state[0] as f32).abs() < variance
+        && (module_size - state[1] as f32).abs() < variance
+        && (3.0 * module_size - state[2] as f32).abs() < 3.0 * variance
+        && (module_size - state[3] as f32).abs() < variance
+        && (module_size - state[4] as f32).abs() < variance
+}
+
+fn center_from_end(state: &[u32; 5], end: usize) -> f32 {
+    end as f32 - state[4] as f32 - state[3] as f32 - state[2] as f32 / 2.0
+}
+
+impl<'a> FinderPatternFinder<'a> {
+    fn new(image: &'a Bitmap) -> Self {
+        Self {
+            image,
+            candidates: Vec::new(),
+        }
+    }
+
+    /// 逐行扫描1:1:3:1:1的深浅比例, 返回最可能的定位图案组合
+    fn find(mut self) -> Vec<FinderTriple> {
+        let (width, height) = (self.image.width, self.image.height);
+        for y in 0..height {
+            let mut state = [0u32; 5];
+            let mut current = 0;
+            for x in 0..width {
+                if self.image.get(x, y) {
+                    if current & 1 == 1 {
+                        current += 1;
+                    }
+                    state[current] += 1;
+                } else if current & 1 == 0 {
+                    if current == 4 {
+                        if found_pattern_cross(&state) && self.handle_possible_center(&state, y, x)
+                        {
+                            state = [0; 5];
+                            current = 0;
+                        } else {
+                            state = [state[2], state[3], state[4], 1, 0];
+                            current = 3;
+                        }
+                    } else {
+                        current += 1;
+                        state[current] += 1;
+                    }
+                } else {
+                    state[current] += 1;
+                }
+            }
+            if found_pattern_cross(&state) {
+                self.handle_possible_center(&state, y, width);
+            }
+        }
+        self.select_triples()
+    }
+
+    fn handle_possible_center(&mut self, state: &[u32; 5], y: usize, end: usize) -> bool {
+        let total: u32 = state.iter().sum();
+        let center_x = center_from_end(state, end);
+        let Some(center_y) = self.cross_check(center_x as usize, y, state[2], total, true) else {
+            return false;
+        };
+        let Some(center_x) =
+            self.cross_check(center_x as usize, center_y as usize, state[2], total, false)
+        else {
+            return false;
+        };
+        let module_size = total as f32 / 7.0;
+        match self
+            .candidates
+            .iter()
+            .position(|c| c.about_equals(module_size, center_x, center_y))
+        {
+            Some(index) => {
+                self.candidates[index] =
+                    self.candidates[index].combine(module_size, center_x, center_y);
+            }
+            None => self.candidates.push(FinderPattern {
+                center: Point {
+                    x: center_x,
+                    y: center_y,
+                },
+                module_size,
+                count: 1,
+            }),
+        }
+        true
+    }
+
+    /// 沿竖直或水平方向确认1:1:3:1:1比例, 返回该方向上的中心坐标
+    fn cross_check(
+        &self,
+        x: usize,
+        y: usize,
+        max_count: u32,
+        original_total: u32,
+        vertical: bool,
+    ) -> Option<f32> {
+        let (start, limit) = if vertical {
+            (y, self.image.height)
+        } else {
+            (x, self.image.width)
+        };
+        let get = |i: usize| {
+            if vertical {
+                self.image.get(x, i)
+            } else {
+                self.image.get(i, y)
+            }
+        };
+
+        let mut state = [0u32; 5];
+        let mut i = start as isize;
+        while i >= 0 && get(i as usize) {
+            state[2] += 1;
+            i -= 1;
+        }
+        if i < 0 {
+            return None;
+        }
+        while i >= 0 && !get(i as usize) && state[1] <= max_count {
+            state[1] += 1;
+            i -= 1;
+        }
+        if i < 0 || state[1] > max_count {
+            return None;
+        }
+        while i >= 0 && get(i as usize) && state[0] <= max_count {
+            state[0] += 1;
+            i -= 1;
+        }
+        if state[0] > max_count {
+            return None;
+        }
+
+        let mut i = start + 1;
+        while i < limit && get(i) {
+            state[2] += 1;
+            i += 1;
+        }
+        if i == limit {
+            return None;
+        }
+        while i < limit && !get(i) && state[3] < max_count {
+            state[3] += 1;
+            i += 1;
+        }
+        if i == limit || state[3] >= max_count {
+            return None;
+        }
+        while i < limit && get(i) && state[4] < max_count {
+            state[4] += 1;
+            i += 1;
+        }
+        if state[4] >= max_count {
+            return None;
+        }
+
+        let total: u32 = state.iter().sum();
+        if 5 * total.abs_diff(original_total) >= 2 * original_total {
+            return None;
+        }
+        found_pattern_cross(&state).then(|| center_from_end(&state, i))
+    }
+
+    /// 选出最接近等腰直角三角形的组合
+    fn select_triples(&self) -> Vec<FinderTriple> {
+        let confirmed: Vec<&FinderPattern> =
+            self.candidates.iter().filter(|c| c.count >= 2).collect();
+        let candidates: Vec<&FinderPattern> = if confirmed.len() >= 3 {
+            confirmed
+        } else {
+            self.candidates.iter().collect()
+        };
+
+        let mut scored = Vec::new();
+        for i in 0..candidates.len() {
+            for j in i + 1..candidates.len() {
+                for k in j + 1..candidates.len() {
+                    let patterns = [candidates[i], candidates[j], candidates[k]];
+                    let min = patterns
+                        .iter()
+                        .map(|p| p.module_size)
+                        .fold(f32::MAX, f32::min);
+                    let max = patterns.iter().map(|p| p.module_size).fold(0.0, f32::max);
+                    if max > min * 1.4 {
+                        continue;
+                    }
+                    let mut sides = [
+                        patterns[0].center.distance(patterns[1].center).powi(2),
+                        patterns[1].center.distance(patterns[2].center).powi(2),
+                        patterns[0].center.distance(patterns[2].center).powi(2),
+                    ];
+                    sides.sort_by(f32::total_cmp);
+                    // 三角形边长至少跨越一个版本1的码
+                    if sides[0] < (14.0 * min).powi(2) {
+                        continue;
+                    }
+                    let score = ((sides[2] - 2.0 * sides[1]).abs()
+                        + (sides[2] - 2.0 * sides[0]).abs())
+                        / sides[2];
+                    scored.push((score, patterns));
+                }
+            }
+        }
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        scored
+            .into_iter()
+            .take(MAX_TRIPLES)
+            .map(|(_, patterns)| order_patterns(patterns.map(|p| p.center)))
+            .collect()
+    }
+}
+
+/// 最长边所对的顶点为左上角, 再按叉积的方向区分右上与左下
+fn order_patterns(points: [Point; 3]) -> FinderTriple {
+    let d01 = points[0].distance(points[1]);
+    let d12 = points[1].distance(points[2]);
+    let d02 = points[0].distance(points[2]);
+    let (mut a, b, mut c) = if d12 >= d01 && d12 >= d02 {
+        (points[1], points[0], points[2])
+    } else if d02 >= d12 && d02 >= d01 {
+        (points[0], points[1], points[2])
+    } else {
+        (points[0], points[2], points[1])
+    };
+    if (c.x - b.x) * (a.y - b.y) - (c.y - b.y) * (a.x - b.x) < 0.0 {
+        std::mem::swap(&mut a, &mut c);
+    }
+    FinderTriple {
+        top_left: b,
+        top_right: c,
+        bottom_left: a,
+    }
+}
+
+/// 沿定位图案连线测量模块尺寸, 对旋转的图像比行扫描的估计更准确
+fn calculate_module_size(image: &Bitmap, triple: &FinderTriple) -> f32 {
+    (module_size_one_way(image, triple.top_left, triple.top_right)
+        + module_size_one_way(image, triple.top_left, triple.bottom_left))
+        / 2.0
+}
+
+fn module_size_one_way(image: &Bitmap, from: Point, to: Point) -> f32 {
+    let first = run_both_ways(image, from, to);
+    let second = run_both_ways(image, to, from);
+    match (first.is_nan(), second.is_nan()) {
+        (true, _) => second / 7.0,
+        (_, true) => first / 7.0,
+        _ => (first + second) / 14.0,
+    }
+}
+
+/// 从定位图案中心向两侧测量 深-浅-深 的长度, 合计约7个模块
+fn run_both_ways(image: &Bitmap, from: Point, to: Point) -> f32 {
+    let (from_x, from_y) = (from.x as isize, from.y as isize);
+    let (to_x, to_y) = (to.x as isize, to.y as isize);
+    let (width, height) = (image.width as isize, image.height as isize);
+    let mut result = black_white_black_run(image, from_x, from_y, to_x, to_y);
+
+    let mut scale = 1.0;
+    let mut other_x = from_x - (to_x - from_x);
+    if other_x < 0 {
+        scale = from_x as f32 / (from_x - other_x) as f32;
+        other_x = 0;
+    } else if other_x >= width {
+        scale = (width - 1 - from_x) as f32 / (other_x - from_x) as f32;
+        other_x = width - 1;
+    }
+    let mut other_y = (from_y as f32 - (to_y - from_y) as f32 * scale) as isize;
+    scale = 1.0;
+    if other_y < 0 {
+        scale = from_y as f32 / (from_y - other_y) as f32;
+        other_y = 0;
+    } else if other_y >= height {
+        scale = (height - 1 - from_y) as f32 / (other_y - from_y) as f32;
+        other_y = height - 1;
+    }
+    other_x = (from_x as f32 + (other_x - from_x) as f32 * scale) as isize;
+
+    result += black_white_black_run(image, from_x, from_y, other_x, other_y);
+    result - 1.0
+}
+
+/// Bresenham直线上从深色起点经过浅色再到深色结束的距离
+fn black_white_black_run(
+    image: &Bitmap,
+    mut from_x: isize,
+    mut from_y: isize,
+    mut to_x: isize,
+    mut to_y: isize,
+) -> f32 {
+    let steep = (to_y - from_y).abs() > (to_x - from_x).abs();
+    if steep {
+        std::mem::swap(&mut from_x, &mut from_y);
+        std::mem::swap(&mut to_x, &mut to_y);
+    }
+    let dx = (to_x - from_x).abs();
+    let dy = (to_y - from_y).abs();
+    let mut error = -dx / 2;
+    let x_step = if from_x < to_x { 1 } else { -1 };
+    let y_step = if from_y < to_y { 1 } else { -1 };
+    let distance = |x: isize, y: isize| (((x - from_x).pow(2) + (y - from_y).pow(2)) as f32).sqrt();
+
+    let mut state = 0;
+    let mut y = from_y;
+    let mut x = from_x;
+    while x != to_x + x_step {
+        let (real_x, real_y) = if steep { (y, x) } else { (x, y) };
+        if real_x < 0
+            || real_y < 0
+            || real_x >= image.width as isize
+            || real_y >= image.height as isize
+        {
+            break;
+        }
+        if (state == 1) == image.get(real_x as usize, real_y as usize) {
+            if state == 2 {
+                return distance(x, y);
+            }
+            state += 1;
+        }
+        error += dy;
+        if error > 0 {
+            if y == to_y {
+                break;
+            }
+            y += y_step;
+            error -= dx;
+        }
+        x += x_step;
+    }
+    if state == 2 {
+        return distance(to_x + x_step, to_y);
+    }
+    f32::NAN
+}
+
+/// 由定位图案间距推算边长, 结果满足 4n+1
+fn estimate_dimension(triple: &FinderTriple, module_size: f32) -> Option<usize> {
+    let top = (triple.top_left.distance(triple.top_right) / module_size).round() as usize;
+    let left = (triple.top_left.distance(triple.bottom_left) / module_size).round() as usize;
+    let dimension = (top + left) / 2 + 7;
+    match dimension & 3 {
+        0 => Some(dimension + 1),
+        1 => Some(dimension),
+        2 => Some(dimension - 1),
+        _ => Some(dimension + 2).filter(|_| dimension >= 21),
+    }
+}
+
+/// 透视变换矩阵, 与ZXing的PerspectiveTransform相同
+#[derive(Debug, Clone, Copy)]
+struct PerspectiveTransform {
+    a11: f32,
+    a21: f32,
+    a31: f32,
+    a12: f32,
+    a22: f32,
+    a32: f32,
+    a13: f32,
+    a23: f32,
+    a33: f32,
+}
+
+impl PerspectiveTransform {
+    /// 将四边形 (左上、右上、右下、左下) 映射到另一个四边形
+    fn quadrilateral_to_quadrilateral(from: [Point; 4], to: [Point; 4]) -> Self {
+        let square_to_from = Self::square_to_quadrilateral(from).adjoint();
+        Self::square_to_quadrilateral(to).times(&square_to_from)
+    }
+
+    fn square_to_quadrilateral(points: [Point; 4]) -> Self {
+        let [p0, p1, p2, p3] = points;
+        let dx3 = p0.x - p1.x + p2.x - p3.x;
+        let dy3 = p0.y - p1.y + p2.y - p3.y;
+        if dx3 == 0.0 && dy3 == 0.0 {
+            return Self {
+                a11: p1.x - p0.x,
+                a21: p2.x - p1.x,
+                a31: p0.x,
+                a12: p1.y - p0.y,
+                a22: p2.y - p1.y,
+                a32: p0.y,
+                a13: 0.0,
+                a23: 0.0,
+                a33: 1.0,
+            };
+        }
+        let dx1 = p1.x - p2.x;
+        let dx2 = p3.x - p2.x;
+        let dy1 = p1.y - p2.y;
+        let dy2 = p3.y - p2.y;
+        let denominator = dx1 * dy2 - dx2 * dy1;
+        let a13 = (dx3 * dy2 - dx2 * dy3) / denominator;
+        let a23 = (dx1 * dy3 - dx3 * dy1) / denominator;
+        Self {
+            a11: p1.x - p0.x + a13 * p1.x,
+            a21: p3.x - p0.x + a23 * p3.x,
+            a31: p0.x,
+            a12: p1.y - p0.y + a13 * p1.y,
+            a22: p3.y - p0.y + a23 * p3.y,
+            a32: p0.y,
+            a13,
+            a23,
+            a33: 1.0,
+        }
+    }
+
+    fn adjoint(&self) -> Self {
+        Self {
+            a11: self.a22 * self.a33 - self.a23 * self.a32,
+            a21: self.a23 * self.a31 - self.a21 * self.a33,
+            a31: self.a21 * self.a32 - self.a22 * self.a31,
+            a12: self.a13 * self.a32 - self.a12 * self.a33,
+            a22: self.a11 * self.a33 - self.a13 * self.a31,
+            a32: self.a12 * self.a31 - self.a11 * self.a32,
+            a13: self.a12 * self.a23 - self.a13 * self.a22,
+            a23: self.a13 * self.a21 - self.a11 * self.a23,
+            a33: self.a11 * self.a22 - self.a12 * self.a21,
+        }
+    }
+
+    fn times(&self, o: &Self) -> Self {
+        Self {
+            a11: self.a11 * o.a11 + self.a21 * o.a12 + self.a31 * o.a13,
+            a21: self.a11 * o.a21 + self.a21 * o.a22 + self.a31 * o.a23,
+            a31: self.a11 * o.a31 + self.a21 * o.a32 + self.a31 * o.a33,
+            a12: self.a12 * o.a11 + self.a22 * o.a12 + self.a32 * o.a13,
+            a22: self.a12 * o.a21 + self.a22 * o.a22 + self.a32 * o.a23,
+            a32: self.a12 * o.a31 + self.a22 * o.a32 + self.a32 * o.a33,
+            a13: self.a13 * o.a11 + self.a23 * o.a12 + self.a33 * o.a13,
+            a23: self.a13 * o.a21 + self.a23 * o.a22 + self.a33 * o.a23,
+            a33: self.a13 * o.a31 + self.a23 * o.a32 + self.a33 * o.a33,
+        }
+    }
+
+    fn transform(&self, x: f32, y: f32) -> Point {
+        let denominator = self.a13 * x + self.a23 * y + self.a33;
+        Point {
+            x: (self.a11 * x + self.a21 * y + self.a31) / denominator,
+            y: (self.a12 * x + self.a22 * y + self.a32) / denominator,
+        }
+    }
+}
+
+/// 在右下角附近搜索对齐图案, 返回其中心
+///
+/// 以定位图案推算出的模块方向采样5x5模板, 取匹配度最高位置的重心
+fn find_alignment(
+    image: &Bitmap,
+    triple: &FinderTriple,
+    dimension: usize,
+    module_size: f32,
+) -> Option<Point> {
+    let modules = (dimension - 7) as f32;
+    let right = Point {
+        x: (triple.top_right.x - triple.top_left.x) / modules,
+        y: (triple.top_right.y - triple.top_left.y) / modules,
+    };
+    let down = Point {
+        x: (triple.bottom_left.x - triple.top_left.x) / modules,
+        y: (triple.bottom_left.y - triple.top_left.y) / modules,
+    };
+    // 对齐图案中心距右下角3个模块
+    let correction = 1.0 - 3.0 / modules;
+    let estimate = Point {
+        x: triple.top_left.x + correction * (right.x + down.x) * modules,
+        y: triple.top_left.y + correction * (right.y + down.y) * modules,
+    };
+
+    let score = |cx: f32, cy: f32| -> Option<u32> {
+        let mut matched = 0;
+        for j in -2i32..=2 {
+            for i in -2i32..=2 {
+                let x = cx + i as f32 * right.x + j as f32 * down.x;
+                let y = cy + i as f32 * right.y + j as f32 * down.y;
+                if x < 0.0 || y < 0.0 || x >= image.width as f32 || y >= image.height as f32 {
+                    return None;
+                }
+                let ring = i.abs().max(j.abs());
+                if image.get(x as usize, y as usize) == (ring != 1) {
+                    matched += 1;
+                }
+            }
+        }
+        Some(matched)
+    };
+
+    for allowance in [4.0, 8.0] {
+        let radius = (allowance * module_size) as isize;
+        let mut best = 0;
+        let mut sum = (0.0, 0.0, 0.0);
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let (x, y) = (estimate.x + dx as f32, estimate.y + dy as f32);
+                let Some(matched) = score(x, y) else {
+                    continue;
+                };
+                if matched > best {
+                    best = matched;
+                    sum = (0.0, 0.0, 0.0);
+                }
+                if matched == best {
+                    sum = (sum.0 + x, sum.1 + y, sum.2 + 1.0);
+                }
+            }
+        }
+        if best >= 23 {
+            return Some(Point {
+                x: sum.0 / sum.2,
+                y: sum.1 / sum.2,
+            });
+        }
+    }
+    None
+}
+
+/// 按透视变换采样每个模块的中心
+fn sample(
+    image: &Bitmap,
+    triple: &FinderTriple,
+    module_size: f32,
+    dimension: usize,
+) -> Option<BitMatrix> {
+    let far = dimension as f32 - 3.5;
+    let alignment = if dimension > 21 {
+        find_alignment(image, triple, dimension, module_size)
+    } else {
+        None
+    };
+    let (bottom_right, source) = match alignment {
+        Some(point) => (point, far - 3.0),
+        None => (
+            Point {
+                x: triple.top_right.x - triple.top_left.x + triple.bottom_left.x,
+                y: triple.top_right.y - triple.top_left.y + triple.bottom_left.y,
+            },
+            far,
+        ),
+    };
+    let transform = PerspectiveTransform::quadrilateral_to_quadrilateral(
+        [
+            Point { x: 3.5, y: 3.5 },
+            Point { x: far, y: 3.5 },
+            Point {
+                x: source,
+                y: source,
+            },
+            Point { x: 3.5, y: far },
+        ],
+        [
+            triple.top_left,
+            triple.top_right,
+            bottom_right,
+            triple.bottom_left,
+        ],
+    );
+
+    let mut matrix = BitMatrix::new(dimension);
+    let (width, height) = (image.width as f32, image.height as f32);
+    for y in 0..dimension {
+        for x in 0..dimension {
+            let point = transform.transform(x as f32 + 0.5, y as f32 + 0.5);
+            if !point.x.is_finite() || !point.y.is_finite() {
+                return None;
+            }
+            // 允许略微越界, 与ZXing一样把边缘的点拉回图像内
+            if point.x < -1.0 || point.y < -1.0 || point.x > width || point.y > height {
+                return None;
+            }
+            let px = point.x.clamp(0.0, width - 1.0) as usize;
+            let py = point.y.clamp(0.0, height - 1.0) as usize;
+            matrix.set(x, y, image.get(px, py));
+        }
+    }
+    Some(matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::otp::qr::decode::decode_matrix;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use qrcodegen::{QrCode, QrCodeEcc};
+
+    const TEXT: &str = "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP";
+    const SCALE: usize = 3;
+    const QUIET_ZONE: usize = 4;
+
+    /// 按模块放大渲染的网格, 四周留出空白
+    fn grid() -> (usize, Vec<bool>) {
+        let code = QrCode::encode_text(TEXT, QrCodeEcc::Medium).unwrap();
+        let size = (code.size() as usize + QUIET_ZONE * 2) * SCALE;
+        let module = |p: usize| (p / SCALE) as i32 - QUIET_ZONE as i32;
+        let mut bits = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                bits.push(code.get_module(module(x), module(y)));
+            }
+        }
+        (size, bits)
+    }
+
+    fn decodes_text(image: &Bitmap) -> bool {
+        detect(image)
+            .iter()
+            .any(|matrix| decode_matrix(matrix).is_ok_and(|data| data == TEXT.as_bytes()))
+    }
+
+    #[test]
+    fn test_detect() {
+        let (size, bits) = grid();
+        assert!(decodes_text(&Bitmap::from_fn(size, size, |x, y| {
+            bits[y * size + x]
+        })));
+    }
+
+    #[test]
+    fn test_degenerate_images() {
+        for (width, height) in [(0, 0), (0, 9), (1, 1), (1, 300), (300, 1), (7, 7)] {
+            for dark in [false, true] {
+                assert!(detect(&Bitmap::from_fn(width, height, |_, _| dark)).is_empty());
+            }
+        }
+        // 只剩一半的二维码
+        let (size, bits) = grid();
+        let half = Bitmap::from_fn(size, size / 2, |x, y| bits[y * size + x]);
+        assert!(!decodes_text(&half));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        /// 随机涂改并裁剪真实的二维码, 检测和解码都不应panic
+        #[test]
+        fn test_detect_corrupted_no_panic(
+            rects in vec((any::<u16>(), any::<u16>(), 1usize..16, 1usize..16), 0..12),
+            crop in (0usize..48, 0usize..48, 0usize..48, 0usize..48),
+        ) {
+            let (size, mut bits) = grid();
+            for (x, y, w, h) in rects {
+                let (x, y) = (x as usize % size, y as usize % size);
+                for yy in y..(y + h).min(size) {
+                    for xx in x..(x + w).min(size) {
+                        bits[yy * size + xx] ^= true;
+                    }
+                }
+            }
+            let (left, top, right, bottom) = crop;
+            let width = size.saturating_sub(left + right);
+            let height = size.saturating_sub(top + bottom);
+            let image = Bitmap::from_fn(width, height, |x, y| bits[(y + top) * size + x + left]);
+            for matrix in detect(&image) {
+                let _ = decode_matrix(&matrix);
+            }
+        }
+
+        #[test]
+        fn test_detect_noise_no_panic(
+            width in 0usize..96,
+            height in 0usize..96,
+            bits in vec(any::<bool>(), 96 * 96),
+        ) {
+            let image = Bitmap::from_fn(width, height, |x, y| bits[y * 96 + x]);
+            for matrix in detect(&image) {
+                let _ = decode_matrix(&matrix);
+            }
+        }
+    }
+}
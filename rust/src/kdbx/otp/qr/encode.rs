@@ -0,0 +1,62 @@
+//! 生成QR码模块矩阵并渲染为PNG
+
+use qrcodegen::{QrCode, QrCodeEcc};
+use std::io::Cursor;
+use zeroize::Zeroizing;
+
+use crate::kdbx::otp::qr::decode::BitMatrix;
+use crate::kdbx::otp::qr::QrError;
+
+/// ISO/IEC 18004 要求的静区宽度, 单位为模块
+const QUIET_ZONE: usize = 4;
+/// 单个模块的最大像素边长
+const MAX_SCALE: usize = 64;
+
+const DARK: u8 = 0;
+const LIGHT: u8 = 255;
+
+/// 以M级纠错编码文本, 容量允许时自动提高纠错等级
+pub fn encode_matrix(text: &str) -> Result<BitMatrix, QrError> {
+    let code = QrCode::encode_text(text, QrCodeEcc::Medium).map_err(|_| QrError::DataTooLong)?;
+    let size = code.size() as usize;
+    let mut matrix = BitMatrix::new(size);
+    for y in 0..size {
+        for x in 0..size {
+            matrix.set(x, y, code.get_module(x as i32, y as i32));
+        }
+    }
+    Ok(matrix)
+}
+
+/// 渲染为8位灰度PNG, 四周保留静区
+pub fn render_png(matrix: &BitMatrix, scale: usize) -> Result<Zeroizing<Vec<u8>>, QrError> {
+    if !(1..=MAX_SCALE).contains(&scale) {
+        return Err(QrError::InvalidScale(scale));
+    }
+    let side = (matrix.size() + QUIET_ZONE * 2) * scale;
+    let mut pixels = Zeroizing::new(vec![LIGHT; side * side]);
+    for y in 0..matrix.size() {
+        for x in 0..matrix.size() {
+            if !matrix.get(x, y) {
+                continue;
+            }
+            let left = (x + QUIET_ZONE) * scale;
+            for row in (y + QUIET_ZONE) * scale..(y + QUIET_ZONE + 1) * scale {
+                pixels[row * side + left..row * side + left + scale].fill(DARK);
+            }
+        }
+    }
+
+    let mut bytes = Zeroizing::new(Vec::new());
+    let mut encoder = png::Encoder::new(Cursor::new(&mut *bytes), side as u32, side as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| {
+            writer.write_image_data(&pixels)?;
+            writer.finish()
+        })
+        .map_err(|e| QrError::ImageEncode(e.to_string()))?;
+    Ok(bytes)
+}
@@ -0,0 +1,251 @@
+//! 将PNG/JPEG图像解码为灰度像素
+
+use crate::kdbx::otp::qr::QrError;
+use std::io::Cursor;
+
+/// 单幅图像允许的最大像素数, 防止恶意附件耗尽内存
+const MAX_IMAGE_PIXELS: usize = 1 << 25;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const JPEG_SIGNATURE: [u8; 3] = [0xFF, 0xD8, 0xFF];
+
+/// 8位灰度图像, 按行存储
+#[derive(Debug, Clone)]
+pub struct LumaImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl LumaImage {
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// 按文件头识别格式并解码
+pub fn decode(bytes: &[u8]) -> Result<LumaImage, QrError> {
+    if bytes.starts_with(&PNG_SIGNATURE) {
+        decode_png(bytes)
+    } else if bytes.starts_with(&JPEG_SIGNATURE) {
+        decode_jpeg(bytes)
+    } else {
+        Err(QrError::UnsupportedImage)
+    }
+}
+
+fn check_size(width: usize, height: usize) -> Result<(), QrError> {
+    if width == 0 || height == 0 || width.saturating_mul(height) > MAX_IMAGE_PIXELS {
+        return Err(QrError::ImageTooLarge);
+    }
+    Ok(())
+}
+
+/// ITU-R BT.601 亮度
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000) as u8
+}
+
+/// 将带透明度的像素合成到白色背景上
+fn over_white(value: u8, alpha: u8) -> u8 {
+    let alpha = alpha as u32;
+    ((value as u32 * alpha + 255 * (255 - alpha) + 127) / 255) as u8
+}
+
+fn decode_png(bytes: &[u8]) -> Result<LumaImage, QrError> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| QrError::ImageDecode(e.to_string()))?;
+    let (width, height) = {
+        let info = reader.info();
+        (info.width as usize, info.height as usize)
+    };
+    check_size(width, height)?;
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buffer)
+        .map_err(|e| QrError::ImageDecode(e.to_string()))?;
+    let channels = match frame.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return Err(QrError::UnsupportedImage),
+    };
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in buffer.chunks(frame.line_size).take(height) {
+        for pixel in row[..width * channels].chunks_exact(channels) {
+            pixels.push(match pixel {
+                [l] => *l,
+                [l, a] => over_white(*l, *a),
+                [r, g, b] => luma(*r, *g, *b),
+                [r, g, b, a] => over_white(luma(*r, *g, *b), *a),
+                _ => unreachable!(),
+            });
+        }
+    }
+    Ok(LumaImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn decode_jpeg(bytes: &[u8]) -> Result<LumaImage, QrError> {
+    let mut decoder = jpeg_decoder::Decoder::new(Cursor::new(bytes));
+    decoder
+        .read_info()
+        .map_err(|e| QrError::ImageDecode(e.to_string()))?;
+    let info = decoder.info().ok_or(QrError::UnsupportedImage)?;
+    let (width, height) = (info.width as usize, info.height as usize);
+    check_size(width, height)?;
+
+    let data = decoder
+        .decode()
+        .map_err(|e| QrError::ImageDecode(e.to_string()))?;
+    let pixels = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => data,
+        // 16位数据为大端序, 取高字节
+        jpeg_decoder::PixelFormat::L16 => data.chunks_exact(2).map(|p| p[0]).collect(),
+        jpeg_decoder::PixelFormat::RGB24 => data
+            .chunks_exact(3)
+            .map(|p| luma(p[0], p[1], p[2]))
+            .collect(),
+        jpeg_decoder::PixelFormat::CMYK32 => data
+            .chunks_exact(4)
+            .map(|p| {
+                let k = 255 - p[3] as u32;
+                let channel = |c: u8| ((255 - c as u32) * k / 255) as u8;
+                luma(channel(p[0]), channel(p[1]), channel(p[2]))
+            })
+            .collect(),
+    };
+    if pixels.len() != width * height {
+        return Err(QrError::ImageDecode("unexpected pixel count".to_string()));
+    }
+    Ok(LumaImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const SIZE: usize = 40;
+
+    fn pixels() -> Vec<u8> {
+        (0..SIZE * SIZE).map(|i| (i * 7 % 256) as u8).collect()
+    }
+
+    fn png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = ::png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(::png::ColorType::Grayscale);
+        encoder.set_depth(::png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(pixels).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    fn jpeg() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        jpeg_encoder::Encoder::new(&mut bytes, 90)
+            .encode(
+                &pixels(),
+                SIZE as u16,
+                SIZE as u16,
+                jpeg_encoder::ColorType::Luma,
+            )
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_decode() {
+        let image = decode(&png(SIZE as u32, SIZE as u32, &pixels())).unwrap();
+        assert_eq!((image.width, image.height), (SIZE, SIZE));
+        assert_eq!(image.pixels, pixels());
+
+        let image = decode(&jpeg()).unwrap();
+        assert_eq!(image.pixels.len(), SIZE * SIZE);
+    }
+
+    /// PNG数据块使用的CRC-32
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    #[test]
+    fn test_truncated_images() {
+        // 只缺少结尾标记或扫描数据末尾时解码器可能仍按已读取的部分解码, 但尺寸必须一致
+        for bytes in [png(SIZE as u32, SIZE as u32, &pixels()), jpeg()] {
+            for len in 0..bytes.len() {
+                if let Ok(image) = decode(&bytes[..len]) {
+                    assert!(len > bytes.len() / 2, "length {len}");
+                    assert_eq!((image.width, image.height), (SIZE, SIZE));
+                    assert_eq!(image.pixels.len(), SIZE * SIZE);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_corrupted_images() {
+        for bytes in [png(SIZE as u32, SIZE as u32, &pixels()), jpeg()] {
+            for i in 0..bytes.len() {
+                let mut corrupted = bytes.clone();
+                corrupted[i] ^= 0x55;
+                if let Ok(image) = decode(&corrupted) {
+                    assert_eq!(image.pixels.len(), image.width * image.height);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_oversized_images() {
+        // 修改IHDR中的宽度和高度并重新计算CRC, 应在分配像素缓冲区之前拒绝
+        let mut bytes = png(SIZE as u32, SIZE as u32, &pixels());
+        bytes[16..24].copy_from_slice(&[0, 1, 0, 0, 0, 1, 0, 0]);
+        let crc = crc32(&bytes[12..29]);
+        bytes[29..33].copy_from_slice(&crc.to_be_bytes());
+        assert!(matches!(decode(&bytes), Err(QrError::ImageTooLarge)));
+
+        // 修改JPEG帧头(SOF0)中的高度和宽度
+        let mut bytes = jpeg();
+        let sof = bytes.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        bytes[sof + 5..sof + 9].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        assert!(matches!(decode(&bytes), Err(QrError::ImageTooLarge)));
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_arbitrary_bytes_no_panic(
+            png_data in proptest::collection::vec(any::<u8>(), 0..512),
+            jpeg_data in proptest::collection::vec(any::<u8>(), 0..512),
+        ) {
+            let _ = decode(&[&PNG_SIGNATURE[..], &png_data].concat());
+            let _ = decode(&[&JPEG_SIGNATURE[..], &jpeg_data].concat());
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//! OTP二维码的识别与生成
+//!
+//! 纯Rust实现, 不依赖网络或系统库。支持PNG和JPEG, 识别结果须为 `otpauth://` URI,
+//! 并经过与条目字段相同的解析和校验后才会返回。
+
+mod binarize;
+mod decode;
+mod detect;
+mod encode;
+mod image;
+mod reed_solomon;
+
+pub use decode::BitMatrix;
+pub use encode::{encode_matrix, render_png};
+
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::kdbx::otp::entry::{
+    is_hotp_url, parse_hotp_url, parse_otp_field, write_hotp, write_otp, OtpConfig, OtpEntryError,
+    OtpStorage,
+};
+use crate::kdbx::otp::hotp::Hotp;
+use crate::kdbx::xml::database::KeePassDatabase;
+use crate::kdbx::xml::entities::Entry;
+
+const OTPAUTH_SCHEME: &str = "otpauth://";
+
+#[derive(Debug, Error)]
+pub enum QrError {
+    #[error("Unsupported image format")]
+    UnsupportedImage,
+
+    #[error("Image is too large")]
+    ImageTooLarge,
+
+    #[error("Image decode error: {0}")]
+    ImageDecode(String),
+
+    #[error("Image encode error: {0}")]
+    ImageEncode(String),
+
+    #[error("Invalid image scale: {0}")]
+    InvalidScale(usize),
+
+    #[error("No QR code found in image")]
+    NotFound,
+
+    #[error("Invalid QR code version")]
+    InvalidVersion,
+
+    #[error("Invalid QR code format information")]
+    InvalidFormat,
+
+    #[error("Too many errors in QR code")]
+    TooManyErrors,
+
+    #[error("Invalid QR code data")]
+    InvalidData,
+
+    #[error("Unsupported QR code mode: {0}")]
+    UnsupportedMode(u32),
+
+    #[error("Data is too long for a QR code")]
+    DataTooLong,
+
+    #[error("Attachment not found")]
+    AttachmentNotFound,
+
+    #[error("QR code does not contain an otpauth URI")]
+    NotOtpAuth,
+
+    #[error("Invalid OTP configuration: {0}")]
+    Otp(#[from] OtpEntryError),
+}
+
+/// 二维码中识别出的OTP配置
+#[derive(Debug)]
+pub enum ScannedOtp {
+    Totp(OtpConfig),
+    Hotp(Hotp),
+}
+
+/// 识别结果, 保留原始URI以便展示或重新编码
+#[derive(Debug)]
+pub struct OtpScan {
+    pub uri: Zeroizing<String>,
+    pub otp: ScannedOtp,
+}
+
+/// 识别图像中的二维码, 返回其中的原始字节
+pub fn decode_image(bytes: &[u8]) -> Result<Zeroizing<Vec<u8>>, QrError> {
+    let image = image::decode(bytes)?;
+    let mut error = QrError::NotFound;
+    // 先用全局阈值处理截图, 失败时再用局部阈值处理照片
+    for bitmap in [binarize::global(&image), binarize::hybrid(&image)] {
+        for matrix in detect::detect(&bitmap) {
+            // 镜像的图像中左下和右上定位图案互换, 采样结果为转置矩阵
+            for candidate in [&matrix, &matrix.transpose()] {
+                match decode::decode_matrix(candidate) {
+                    Ok(data) => return Ok(Zeroizing::new(data)),
+                    Err(e) => error = e,
+                }
+            }
+        }
+    }
+    Err(error)
+}
+
+/// 识别图像中的OTP二维码
+pub fn scan_otp(bytes: &[u8]) -> Result<OtpScan, QrError> {
+    let data = decode_image(bytes)?;
+    let uri = Zeroizing::new(
+        std::str::from_utf8(&data)
+            .map_err(|_| QrError::NotOtpAuth)?
+            .trim()
+            .to_string(),
+    );
+    let is_otpauth = uri
+        .get(..OTPAUTH_SCHEME.len())
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case(OTPAUTH_SCHEME));
+    if !is_otpauth {
+        return Err(QrError::NotOtpAuth);
+    }
+    let otp = if is_hotp_url(&uri) {
+        ScannedOtp::Hotp(parse_hotp_url(&uri)?)
+    } else {
+        ScannedOtp::Totp(parse_otp_field(&uri)?)
+    };
+    Ok(OtpScan { uri, otp })
+}
+
+/// 识别条目附件中的OTP二维码, 附件保存在内部头中
+pub fn scan_attachment(
+    db: &KeePassDatabase,
+    entry: &Entry,
+    name: &str,
+) -> Result<OtpScan, QrError> {
+    let content = db
+        .get_attachment(entry, name)
+        .ok_or(QrError::AttachmentNotFound)?;
+    scan_otp(content)
+}
+
+/// 将识别结果写入条目的OTP字段
+pub fn apply_scan(entry: &mut Entry, scan: &OtpScan, storage: OtpStorage) -> Result<(), QrError> {
+    match &scan.otp {
+        ScannedOtp::Totp(config) => write_otp(entry, config, storage)?,
+        ScannedOtp::Hotp(hotp) => write_hotp(entry, hotp, storage)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::db::kdbx4::header_entity::binary_content::BinaryContent;
+    use crate::kdbx::otp::entry::read_otp;
+    use crate::kdbx::otp::Totp;
+    use crate::kdbx::xml::fixtures;
+    use qrcodegen::{QrCode, QrCodeEcc};
+    use uuid::Uuid;
+
+    const TOTP_URI: &str =
+        "otpauth://totp/Example:alice@example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example&period=30";
+
+    /// 以给定的旋转角度和缩放渲染二维码, 返回灰度像素
+    fn render(code: &QrCode, scale: f32, angle: f32, size: usize) -> Vec<u8> {
+        let modules = code.size() as f32;
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let center = size as f32 / 2.0;
+        let mut pixels = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                let (dx, dy) = (x as f32 + 0.5 - center, y as f32 + 0.5 - center);
+                let mx = (dx * cos + dy * sin) / scale + modules / 2.0;
+                let my = (-dx * sin + dy * cos) / scale + modules / 2.0;
+                let dark =
+                    mx >= 0.0 && my >= 0.0 && code.get_module(mx.floor() as i32, my.floor() as i32);
+                pixels.push(if dark { 30 } else { 230 });
+            }
+        }
+        pixels
+    }
+
+    fn png(pixels: &[u8], size: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = ::png::Encoder::new(&mut bytes, size as u32, size as u32);
+        encoder.set_color(::png::ColorType::Grayscale);
+        encoder.set_depth(::png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(pixels).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    fn code(text: &str, ecc: QrCodeEcc) -> QrCode {
+        QrCode::encode_text(text, ecc).unwrap()
+    }
+
+    #[test]
+    fn test_scan_png() {
+        let qr = code(TOTP_URI, QrCodeEcc::Medium);
+        let size = (qr.size() as usize + 8) * 4;
+        let scan = scan_otp(&png(&render(&qr, 4.0, 0.0, size), size)).unwrap();
+        assert_eq!(scan.uri.as_str(), TOTP_URI);
+        let ScannedOtp::Totp(OtpConfig::Totp(totp)) = scan.otp else {
+            panic!("expected TOTP");
+        };
+        assert_eq!(totp.get_issuer(), Some("Example"));
+        assert_eq!(totp.secret, b"Hello!\xde\xad\xbe\xef");
+    }
+
+    #[test]
+    fn test_scan_rotated_and_mirrored() {
+        let qr = code(TOTP_URI, QrCodeEcc::Quartile);
+        for (angle, scale) in [(90.0, 5.0), (17.0, 4.3), (-35.0, 6.0), (180.0, 3.0)] {
+            let size = ((qr.size() as f32 + 10.0) * scale * 1.5) as usize;
+            let image = png(&render(&qr, scale, angle, size), size);
+            assert_eq!(
+                decode_image(&image).unwrap().as_slice(),
+                TOTP_URI.as_bytes(),
+                "angle {angle}"
+            );
+        }
+
+        let size = (qr.size() as usize + 8) * 4;
+        let pixels = render(&qr, 4.0, 0.0, size);
+        let mirrored: Vec<u8> = (0..size * size)
+            .map(|i| pixels[(i % size) * size + i / size])
+            .collect();
+        assert_eq!(
+            decode_image(&png(&mirrored, size)).unwrap().as_slice(),
+            TOTP_URI.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_scan_large_version() {
+        let text = format!("{TOTP_URI}&padding={}", "A1b2C3d4".repeat(60));
+        let qr = code(&text, QrCodeEcc::Low);
+        assert!(qr.version().value() >= 10);
+        let size = (qr.size() as usize + 8) * 3;
+        let image = png(&render(&qr, 3.0, 0.0, size), size);
+        assert_eq!(decode_image(&image).unwrap().as_slice(), text.as_bytes());
+    }
+
+    #[test]
+    fn test_scan_jpeg_with_uneven_lighting() {
+        let uri =
+            "otpauth://hotp/Bank:bob?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=7&digits=8";
+        let qr = code(uri, QrCodeEcc::High);
+        let size = (qr.size() as usize + 8) * 5;
+        let pixels: Vec<u8> = render(&qr, 5.0, 8.0, size)
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| {
+                // 从左到右逐渐变暗
+                let shade = 1.0 - (i % size) as f32 / size as f32 * 0.5;
+                (p as f32 * shade) as u8
+            })
+            .collect();
+        let mut jpeg = Vec::new();
+        jpeg_encoder::Encoder::new(&mut jpeg, 85)
+            .encode(
+                &pixels,
+                size as u16,
+                size as u16,
+                jpeg_encoder::ColorType::Luma,
+            )
+            .unwrap();
+
+        let scan = scan_otp(&jpeg).unwrap();
+        let ScannedOtp::Hotp(hotp) = scan.otp else {
+            panic!("expected HOTP");
+        };
+        assert_eq!(hotp.counter, 7);
+        assert_eq!(hotp.digits, 8);
+        assert_eq!(hotp.secret, b"12345678901234567890");
+    }
+
+    #[test]
+    fn test_scan_rejects_other_content() {
+        let qr = code("https://example.com", QrCodeEcc::Medium);
+        let size = (qr.size() as usize + 8) * 4;
+        let image = png(&render(&qr, 4.0, 0.0, size), size);
+        assert!(matches!(scan_otp(&image), Err(QrError::NotOtpAuth)));
+
+        let qr = code("otpauth://totp/x?secret=not-base32!", QrCodeEcc::Medium);
+        let size = (qr.size() as usize + 8) * 4;
+        let image = png(&render(&qr, 4.0, 0.0, size), size);
+        assert!(matches!(scan_otp(&image), Err(QrError::Otp(_))));
+
+        let blank = png(&vec![255; 64 * 64], 64);
+        assert!(matches!(scan_otp(&blank), Err(QrError::NotFound)));
+        assert!(matches!(
+            scan_otp(b"GIF89a"),
+            Err(QrError::UnsupportedImage)
+        ));
+    }
+
+    #[test]
+    fn test_scan_attachment() {
+        let entry = fixtures::entry_xml(
+            &fixtures::uuid(1),
+            &[("Title", "GitHub")],
+            r#"<Binary><Key>qr.png</Key><Value Ref="0"/></Binary>"#,
+        );
+        let mut db = fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(10),
+            "Root",
+            "",
+            &entry,
+        ));
+        let qr = code(TOTP_URI, QrCodeEcc::Medium);
+        let size = (qr.size() as usize + 8) * 4;
+        db.inner_header.binary_content.push(BinaryContent {
+            flag: 0,
+            content: png(&render(&qr, 4.0, 0.0, size), size),
+        });
+
+        let mut bytes = [0u8; 16];
+        bytes[15] = 1;
+        let mut entry = db
+            .document
+            .root
+            .group
+            .find_entry(&Uuid::from_bytes(bytes))
+            .unwrap()
+            .clone();
+        assert!(matches!(
+            scan_attachment(&db, &entry, "missing.png"),
+            Err(QrError::AttachmentNotFound)
+        ));
+
+        let scan = scan_attachment(&db, &entry, "qr.png").unwrap();
+        apply_scan(&mut entry, &scan, OtpStorage::KeePassXc).unwrap();
+        let Some(OtpConfig::Totp(totp)) = read_otp(&db, &entry).unwrap() else {
+            panic!("expected TOTP");
+        };
+        assert_eq!(totp.get_account(), Some("alice@example.com"));
+        assert_eq!(totp.secret, b"Hello!\xde\xad\xbe\xef");
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let matrix = encode_matrix(TOTP_URI).unwrap();
+        assert_eq!(decode::decode_matrix(&matrix).unwrap(), TOTP_URI.as_bytes());
+
+        let image = render_png(&matrix, 3).unwrap();
+        let scan = scan_otp(&image).unwrap();
+        assert_eq!(scan.uri.as_str(), TOTP_URI);
+
+        assert!(matches!(
+            render_png(&matrix, 0),
+            Err(QrError::InvalidScale(0))
+        ));
+        assert!(matches!(
+            encode_matrix(&"A".repeat(5000)),
+            Err(QrError::DataTooLong)
+        ));
+    }
+}
@@ -0,0 +1,235 @@
+/// GF(2^8), 本原多项式 x^8 + x^4 + x^3 + x^2 + 1 (0x11D), 生成元为2
+struct Galois {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+impl Galois {
+    const fn new() -> Self {
+        let mut exp = [0u8; 512];
+        let mut log = [0u8; 256];
+        let mut value: u16 = 1;
+        let mut i = 0;
+        while i < 255 {
+            exp[i] = value as u8;
+            log[value as usize] = i as u8;
+            value <<= 1;
+            if value & 0x100 != 0 {
+                value ^= 0x11D;
+            }
+            i += 1;
+        }
+        while i < 512 {
+            exp[i] = exp[i - 255];
+            i += 1;
+        }
+        Self { exp, log }
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            return 0;
+        }
+        self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        debug_assert!(b != 0);
+        if a == 0 {
+            return 0;
+        }
+        self.exp[self.log[a as usize] as usize + 255 - self.log[b as usize] as usize]
+    }
+
+    /// α^power
+    fn pow(&self, power: usize) -> u8 {
+        self.exp[power % 255]
+    }
+
+    fn inverse(&self, a: u8) -> u8 {
+        self.exp[255 - self.log[a as usize] as usize]
+    }
+}
+
+static GF: Galois = Galois::new();
+
+/// 在多项式中按x的升幂求值
+fn eval_ascending(poly: &[u8], x: u8) -> u8 {
+    poly.iter()
+        .rev()
+        .fold(0, |acc, &coefficient| GF.mul(acc, x) ^ coefficient)
+}
+
+/// 就地纠正码字, `codeword` 的第一个字节为最高次项, 末尾 `ecc_len` 个字节为纠错码
+///
+/// 生成多项式的根为 α^0 .. α^(ecc_len - 1), 与QR码一致。
+/// 成功时返回纠正的字节数, 错误超过纠错能力时返回None
+pub fn correct(codeword: &mut [u8], ecc_len: usize) -> Option<usize> {
+    let n = codeword.len();
+    let syndromes: Vec<u8> = (0..ecc_len)
+        .map(|i| {
+            let x = GF.pow(i);
+            codeword.iter().fold(0, |acc, &byte| GF.mul(acc, x) ^ byte)
+        })
+        .collect();
+    if syndromes.iter().all(|&s| s == 0) {
+        return Some(0);
+    }
+
+    // Berlekamp-Massey, 系数按升幂排列
+    let mut locator = vec![1u8];
+    let mut previous = vec![1u8];
+    let mut errors = 0;
+    let mut shift = 1;
+    let mut previous_discrepancy = 1u8;
+    for step in 0..ecc_len {
+        let mut discrepancy = syndromes[step];
+        for i in 1..=errors.min(locator.len() - 1) {
+            discrepancy ^= GF.mul(locator[i], syndromes[step - i]);
+        }
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+        let scale = GF.div(discrepancy, previous_discrepancy);
+        let mut next = locator.clone();
+        if next.len() < previous.len() + shift {
+            next.resize(previous.len() + shift, 0);
+        }
+        for (i, &coefficient) in previous.iter().enumerate() {
+            next[i + shift] ^= GF.mul(scale, coefficient);
+        }
+        if 2 * errors <= step {
+            previous = std::mem::replace(&mut locator, next);
+            errors = step + 1 - errors;
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            locator = next;
+            shift += 1;
+        }
+    }
+    while locator.len() > 1 && locator[locator.len() - 1] == 0 {
+        locator.pop();
+    }
+    if errors * 2 > ecc_len || locator.len() - 1 != errors {
+        return None;
+    }
+
+    // Chien搜索: 第k个字节对应 x^(n-1-k)
+    let positions: Vec<usize> = (0..n)
+        .filter(|&k| {
+            let x_inverse = GF.inverse(GF.pow(n - 1 - k));
+            eval_ascending(&locator, x_inverse) == 0
+        })
+        .collect();
+    if positions.len() != errors {
+        return None;
+    }
+
+    // Forney: Ω(x) = S(x)Λ(x) mod x^ecc_len
+    let mut evaluator = vec![0u8; ecc_len];
+    for (i, &s) in syndromes.iter().enumerate() {
+        for (j, &l) in locator.iter().enumerate() {
+            if i + j < ecc_len {
+                evaluator[i + j] ^= GF.mul(s, l);
+            }
+        }
+    }
+    let derivative: Vec<u8> = locator
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, &l)| if i % 2 == 1 { l } else { 0 })
+        .collect();
+    for &k in &positions {
+        let x = GF.pow(n - 1 - k);
+        let x_inverse = GF.inverse(x);
+        let denominator = eval_ascending(&derivative, x_inverse);
+        if denominator == 0 {
+            return None;
+        }
+        let magnitude = GF.mul(
+            x,
+            GF.div(eval_ascending(&evaluator, x_inverse), denominator),
+        );
+        codeword[k] ^= magnitude;
+    }
+    Some(errors)
+}
+
+/// 计算纠错码, 用于测试和生成
+#[cfg(test)]
+pub fn ecc(data: &[u8], ecc_len: usize) -> Vec<u8> {
+    // 生成多项式 Π(x - α^i), 按降幂排列并省略首项1
+    let mut generator = vec![0u8; ecc_len];
+    generator[ecc_len - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..ecc_len {
+        for j in 0..ecc_len {
+            generator[j] = GF.mul(generator[j], root);
+            if j + 1 < ecc_len {
+                generator[j] ^= generator[j + 1];
+            }
+        }
+        root = GF.mul(root, 2);
+    }
+    let mut remainder = vec![0u8; ecc_len];
+    for &byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (r, &g) in remainder.iter_mut().zip(&generator) {
+            *r ^= GF.mul(g, factor);
+        }
+    }
+    remainder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correct_errors() {
+        let data: Vec<u8> = (0..40).map(|i| (i * 7 + 3) as u8).collect();
+        let mut codeword = data.clone();
+        codeword.extend(ecc(&data, 16));
+        let original = codeword.clone();
+
+        assert_eq!(correct(&mut codeword, 16), Some(0));
+
+        for (position, flip) in [
+            (0, 0xff),
+            (5, 0x01),
+            (20, 0x80),
+            (39, 0x55),
+            (41, 0x12),
+            (55, 0x33),
+        ] {
+            codeword[position] ^= flip;
+        }
+        assert_eq!(correct(&mut codeword, 16), Some(6));
+        assert_eq!(codeword, original);
+
+        // 8个错误为纠错上限
+        for position in (0..56).step_by(7) {
+            codeword[position] ^= 0xa5;
+        }
+        assert_eq!(correct(&mut codeword, 16), Some(8));
+        assert_eq!(codeword, original);
+    }
+
+    #[test]
+    fn test_too_many_errors() {
+        let data = b"keepass one";
+        let mut codeword = data.to_vec();
+        codeword.extend(ecc(data, 4));
+        let original = codeword.clone();
+        for byte in codeword.iter_mut().take(5) {
+            *byte ^= 0x3c;
+        }
+        // 超出纠错能力时要么报告失败, 要么纠正为另一个码字, 不会还原
+        let result = correct(&mut codeword, 4);
+        assert!(result.is_none() || codeword != original);
+    }
+}
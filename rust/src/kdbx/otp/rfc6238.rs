@@ -1,24 +1,24 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use data_encoding::BASE32_NOPAD;
-use hmac::{Hmac, Mac};
-use sha1::Sha1;
-use sha2::{Sha256, Sha512};
+use percent_encoding::percent_decode_str;
 use thiserror::Error;
 use url::Url;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use crate::kdbx::otp::hotp::dynamic_truncation;
 use crate::kdbx::otp::{Totp, TotpCode, TotpGenerateError};
 
-const DEFAULT_DIGITS: u32 = 6;
-const DEFAULT_PERIOD: u32 = 30;
+pub const DEFAULT_DIGITS: u32 = 6;
+pub const DEFAULT_PERIOD: u32 = 30;
 
 #[derive(Debug, Error)]
 pub enum Rfc6238ParseError {
     #[error("Invalid URL: {0}")]
     InvalidUrl(#[from] url::ParseError),
 
-    #[error("Invalid otpauth URL: must use totp type")]
+    #[error("Invalid otpauth URL: unexpected OTP type")]
     InvalidOtpAuthType,
 
     #[error("Missing required parameter: {0}")]
@@ -34,7 +34,7 @@ pub enum Rfc6238ParseError {
     InvalidDigits(#[from] std::num::ParseIntError),
 }
 
-#[derive(Zeroize, ZeroizeOnDrop, PartialEq, Debug)]
+#[derive(Zeroize, ZeroizeOnDrop, PartialEq, Debug, Clone)]
 pub enum TotpAlgorithm {
     Sha1,
     Sha256,
@@ -57,22 +57,40 @@ pub struct Rfc6238Totp {
     pub secret: Vec<u8>,
 }
 
-impl Rfc6238Totp {
-    fn from_url(url: &Url) -> Result<Self, Rfc6238ParseError> {
+/// otpauth URL中TOTP与HOTP共用的部分
+pub(crate) struct OtpAuthUrl {
+    pub issuer: Option<String>,
+    pub account: Option<String>,
+    pub algorithm: TotpAlgorithm,
+    pub digits: u32,
+    pub secret: Vec<u8>,
+    pub params: HashMap<String, String>,
+}
+
+impl OtpAuthUrl {
+    /// 解析 `otpauth://{otp_type}/...`
+    pub(crate) fn parse(url: &Url, otp_type: &str) -> Result<Self, Rfc6238ParseError> {
         if url.scheme() != "otpauth" {
             return Err(Rfc6238ParseError::InvalidUrl(
                 url::ParseError::InvalidDomainCharacter,
             ));
         }
 
-        if url.host_str() != Some("totp") {
+        if url.host_str() != Some(otp_type) {
             return Err(Rfc6238ParseError::InvalidOtpAuthType);
         }
 
-        let path = url.path().trim_start_matches('/');
-        let (issuer_from_path, account) = if let Some(colon_pos) = path.find(':') {
-            let issuer = path[..colon_pos].to_string();
-            let account_str = path[colon_pos + 1..].to_string();
+        // 分隔符可以是字面的 `:` 或转义的 `%3A`, 优先按字面分隔, 以便发行方中包含转义的冒号
+        let raw_path = url.path().trim_start_matches('/');
+        let separator = raw_path
+            .find(':')
+            .map(|pos| (pos, 1))
+            .or_else(|| raw_path.to_ascii_uppercase().find("%3A").map(|pos| (pos, 3)));
+        let decode = |value: &str| percent_decode_str(value).decode_utf8_lossy().to_string();
+        let path = decode(raw_path);
+        let (issuer_from_path, account) = if let Some((pos, len)) = separator {
+            let issuer = decode(&raw_path[..pos]);
+            let account_str = decode(&raw_path[pos + len..]);
             let account = if account_str.is_empty() || account_str == "none" {
                 None
             } else {
@@ -85,19 +103,16 @@ impl Rfc6238Totp {
             (None, None)
         };
 
-        let query_params: std::collections::HashMap<_, _> = url.query_pairs().collect();
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
 
-        let secret_str = query_params
+        let secret_str = params
             .get("secret")
             .ok_or(Rfc6238ParseError::MissingParameter("secret"))?;
         let secret = BASE32_NOPAD.decode(secret_str.to_uppercase().as_bytes())?;
 
-        let issuer = query_params
-            .get("issuer")
-            .map(|s| s.to_string())
-            .or(issuer_from_path);
+        let issuer = params.get("issuer").cloned().or(issuer_from_path);
 
-        let algorithm = if let Some(algo_str) = query_params.get("algorithm") {
+        let algorithm = if let Some(algo_str) = params.get("algorithm") {
             match algo_str.to_uppercase().as_str() {
                 "SHA1" => TotpAlgorithm::Sha1,
                 "SHA256" => TotpAlgorithm::Sha256,
@@ -108,29 +123,44 @@ impl Rfc6238Totp {
             TotpAlgorithm::default()
         };
 
-        let digits = if let Some(digits_str) = query_params.get("digits") {
+        let digits = if let Some(digits_str) = params.get("digits") {
             digits_str.parse()?
         } else {
             DEFAULT_DIGITS
         };
 
-        let period = if let Some(period_str) = query_params.get("period") {
+        Ok(OtpAuthUrl {
+            issuer,
+            account,
+            algorithm,
+            digits,
+            secret,
+            params,
+        })
+    }
+}
+
+impl Rfc6238Totp {
+    pub fn from_url(url: &Url) -> Result<Self, Rfc6238ParseError> {
+        let parsed = OtpAuthUrl::parse(url, "totp")?;
+
+        let period = if let Some(period_str) = parsed.params.get("period") {
             period_str.parse()?
         } else {
             DEFAULT_PERIOD
         };
 
         Ok(Rfc6238Totp {
-            issuer,
-            account,
-            algorithm,
-            digits,
+            issuer: parsed.issuer,
+            account: parsed.account,
+            algorithm: parsed.algorithm,
+            digits: parsed.digits,
             period,
-            secret,
+            secret: parsed.secret,
         })
     }
 
-    fn from_base32(base32_str: &str) -> Result<Self, Rfc6238ParseError> {
+    pub fn from_base32(base32_str: &str) -> Result<Self, Rfc6238ParseError> {
         let secret = BASE32_NOPAD.decode(base32_str.to_uppercase().as_bytes())?;
 
         Ok(Rfc6238Totp {
@@ -163,37 +193,11 @@ impl Totp for Rfc6238Totp {
         let period_start = counter * (self.period as u64);
         let period_end = period_start + (self.period as u64);
         
-        let counter_bytes = counter.to_be_bytes();
-        let hmac_result = match self.algorithm {
-            TotpAlgorithm::Sha1 => {
-                let mut mac = Hmac::<Sha1>::new_from_slice(&self.secret)?;
-                mac.update(&counter_bytes);
-                mac.finalize().into_bytes().to_vec()
-            }
-            TotpAlgorithm::Sha256 => {
-                let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)?;
-                mac.update(&counter_bytes);
-                mac.finalize().into_bytes().to_vec()
-            }
-            TotpAlgorithm::Sha512 => {
-                let mut mac = Hmac::<Sha512>::new_from_slice(&self.secret)?;
-                mac.update(&counter_bytes);
-                mac.finalize().into_bytes().to_vec()
-            }
-        };
-        
-        let offset = (hmac_result[hmac_result.len() - 1] & 0x0f) as usize;
-        
-        let code = u32::from_be_bytes([
-            hmac_result[offset],
-            hmac_result[offset + 1],
-            hmac_result[offset + 2],
-            hmac_result[offset + 3],
-        ]);
-        
-        let code = code & 0x7fffffff;
-        
-        let modulus = 10u32.pow(self.digits);
+        let code = dynamic_truncation(&self.algorithm, &self.secret, counter)?;
+
+        let modulus = 10u32
+            .checked_pow(self.digits)
+            .ok_or(TotpGenerateError::InvalidDigits(self.digits))?;
         let otp = code % modulus;
         
         let code_str = format!("{:0width$}", otp, width = self.digits as usize);
@@ -290,6 +294,20 @@ mod tests {
         assert_eq!(totp.period, DEFAULT_PERIOD);
     }
 
+    #[test]
+    fn test_parse_otpauth_url_label_separator() {
+        let totp = Rfc6238Totp::from_str("otpauth://totp/ACME%3Aalice?secret=JBSWY3DPEHPK3PXP")
+            .unwrap();
+        assert_eq!(totp.issuer.as_deref(), Some("ACME"));
+        assert_eq!(totp.account.as_deref(), Some("alice"));
+
+        let totp =
+            Rfc6238Totp::from_str("otpauth://totp/A%3AB:alice%20smith?secret=JBSWY3DPEHPK3PXP")
+                .unwrap();
+        assert_eq!(totp.issuer.as_deref(), Some("A:B"));
+        assert_eq!(totp.account.as_deref(), Some("alice smith"));
+    }
+
     #[test]
     fn test_parse_base32_secret() {
         let secret = "JBSWY3DPEHPK3PXP";
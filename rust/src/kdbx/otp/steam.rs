@@ -1,12 +1,15 @@
-use hmac::{Hmac, Mac};
-use sha1::Sha1;
-
+use crate::kdbx::otp::hotp::dynamic_truncation;
+use crate::kdbx::otp::rfc6238::TotpAlgorithm;
 use crate::kdbx::otp::{Totp, TotpCode, TotpGenerateError};
 
 const STEAM_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
-const STEAM_CODE_LENGTH: usize = 5;
-const STEAM_PERIOD: u64 = 30;
+pub const STEAM_CODE_LENGTH: usize = 5;
+pub const STEAM_PERIOD: u64 = 30;
+/// otpauth URL中标识Steam令牌的 `encoder` 参数
+pub const STEAM_ENCODER: &str = "steam";
+pub const STEAM_ISSUER: &str = "Steam";
 
+#[derive(Debug)]
 pub struct SteamTotp {
     pub secret: Vec<u8>,
 }
@@ -31,23 +34,8 @@ impl Totp for SteamTotp {
         let period_start = counter * STEAM_PERIOD;
         let period_end = period_start + STEAM_PERIOD;
         
-        let counter_bytes = counter.to_be_bytes();
-        
-        let mut mac = Hmac::<Sha1>::new_from_slice(&self.secret)?;
-        mac.update(&counter_bytes);
-        let hmac_result = mac.finalize().into_bytes();
-        
-        let offset = (hmac_result[hmac_result.len() - 1] & 0x0f) as usize;
-        
-        let mut full_code = u32::from_be_bytes([
-            hmac_result[offset],
-            hmac_result[offset + 1],
-            hmac_result[offset + 2],
-            hmac_result[offset + 3],
-        ]);
-        
-        full_code &= 0x7fffffff;
-        
+        let mut full_code = dynamic_truncation(&TotpAlgorithm::Sha1, &self.secret, counter)?;
+
         let alphabet_len = STEAM_ALPHABET.len() as u32;
         let mut code = String::with_capacity(STEAM_CODE_LENGTH);
         for _ in 0..STEAM_CODE_LENGTH {
@@ -0,0 +1,204 @@
+//! Yandex Key: 以PIN与密钥的SHA-256作为HMAC-SHA256密钥, 验证码为8位小写字母
+//!
+//! 算法与Aegis的实现一致
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::crypto::secure_data::SecureData;
+use crate::kdbx::otp::{Totp, TotpCode, TotpGenerateError};
+
+pub const YANDEX_CODE_LENGTH: u32 = 8;
+pub const YANDEX_PERIOD: u64 = 30;
+pub const YANDEX_ISSUER: &str = "Yandex";
+
+/// 密钥长度, 应用导出的密钥在末尾附加了填充和12位校验和
+const SECRET_LENGTH: usize = 16;
+const SECRET_FULL_LENGTH: usize = 26;
+const CHECKSUM_POLYNOMIAL: u16 = 0b1_1000_1111_0011;
+
+#[derive(Debug)]
+pub struct YandexTotp {
+    pub account: Option<String>,
+    /// 16字节密钥, 不含校验和
+    pub secret: Vec<u8>,
+    pub pin: SecureData,
+}
+
+impl YandexTotp {
+    /// 接受16字节密钥或26字节带校验和的密钥, 后者会校验并去除校验和
+    pub fn new(
+        account: Option<String>,
+        secret: &[u8],
+        pin: SecureData,
+    ) -> Result<Self, TotpGenerateError> {
+        let secret = match secret.len() {
+            SECRET_LENGTH => secret,
+            SECRET_FULL_LENGTH if checksum(secret) => &secret[..SECRET_LENGTH],
+            SECRET_FULL_LENGTH => return Err(TotpGenerateError::InvalidChecksum),
+            _ => return Err(TotpGenerateError::InvalidSecretLength),
+        };
+        Ok(Self {
+            account,
+            secret: secret.to_vec(),
+            pin,
+        })
+    }
+}
+
+/// 校验和为除最后12位外所有位在GF(2)上对多项式取模的结果
+fn checksum(secret: &[u8]) -> bool {
+    let expected =
+        (((secret[secret.len() - 2] & 0x0f) as u16) << 8) | secret[secret.len() - 1] as u16;
+    let mut remaining = secret.len() * 8 - 12;
+    let mut accumulator: u16 = 0;
+    let mut accumulator_bits = 0;
+    let mut index = 0;
+    let mut available = 8;
+    while remaining > 0 {
+        let mut required = (13 - accumulator_bits).min(remaining);
+        while required > 0 {
+            let take = required.min(available);
+            let input = (secret[index] & ((1u16 << available) - 1) as u8) >> (available - take);
+            accumulator = (accumulator << take) | input as u16;
+            remaining -= take;
+            required -= take;
+            available -= take;
+            accumulator_bits += take;
+            if available == 0 {
+                index += 1;
+                available = 8;
+            }
+        }
+        if accumulator_bits == 13 {
+            accumulator ^= CHECKSUM_POLYNOMIAL;
+        }
+        accumulator_bits = (u16::BITS - accumulator.leading_zeros()) as usize;
+    }
+    accumulator == expected
+}
+
+impl Totp for YandexTotp {
+    fn get_issuer(&self) -> Option<&str> {
+        Some(YANDEX_ISSUER)
+    }
+
+    fn get_account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
+    fn generate(&self, timestamp: u64) -> Result<TotpCode, TotpGenerateError> {
+        if self.secret.len() != SECRET_LENGTH {
+            return Err(TotpGenerateError::InvalidSecretLength);
+        }
+        let pin = self.pin.unsecure()?;
+        if pin.is_empty() {
+            return Err(TotpGenerateError::MissingPin);
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(&*pin);
+        hasher.update(&self.secret);
+        let key = Zeroizing::new(hasher.finalize().to_vec());
+        // 与官方应用一致, 哈希首字节为0时去掉该字节
+        let key = if key[0] == 0 { &key[1..] } else { &key[..] };
+
+        let counter = timestamp / YANDEX_PERIOD;
+        let mut mac = Hmac::<Sha256>::new_from_slice(key)?;
+        mac.update(&counter.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let mut value = [0u8; 8];
+        value.copy_from_slice(&hash[offset..offset + 8]);
+        value[0] &= 0x7f;
+
+        let mut number = u64::from_be_bytes(value) % 26u64.pow(YANDEX_CODE_LENGTH);
+        let mut code = vec![b'a'; YANDEX_CODE_LENGTH as usize];
+        for letter in code.iter_mut().rev() {
+            *letter = b'a' + (number % 26) as u8;
+            number /= 26;
+        }
+
+        let period_start = counter * YANDEX_PERIOD;
+        Ok(TotpCode {
+            code: String::from_utf8(code).expect("ASCII letters"),
+            period_start,
+            period_end: period_start + YANDEX_PERIOD,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_encoding::BASE32_NOPAD;
+
+    /// 与Aegis的测试向量一致: (PIN, 密钥, 时间戳, 验证码)
+    const VECTORS: [(&str, &str, u64, &str); 5] = [
+        (
+            "5239",
+            "6SB2IKNM6OBZPAVBVTOHDKS4FAAAAAAADFUTQMBTRY",
+            1641559648,
+            "umozdicq",
+        ),
+        (
+            "7586",
+            "LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSG4HRZPI",
+            1581064020,
+            "oactmacq",
+        ),
+        (
+            "7586",
+            "LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSG4HRZPI",
+            1581090810,
+            "wemdwrix",
+        ),
+        (
+            "5210481216086702",
+            "JBGSAU4G7IEZG6OY4UAXX62JU4AAAAAAHTSG4HXU3M",
+            1581091469,
+            "dfrpywob",
+        ),
+        (
+            "5210481216086702",
+            "JBGSAU4G7IEZG6OY4UAXX62JU4AAAAAAHTSG4HXU3M",
+            1581093059,
+            "vunyprpd",
+        ),
+    ];
+
+    #[test]
+    fn test_yandex_vectors() {
+        for (pin, secret, timestamp, expected) in VECTORS {
+            let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+            let totp = YandexTotp::new(None, &secret, SecureData::new(pin.as_bytes())).unwrap();
+            let code = totp.generate(timestamp).unwrap();
+            assert_eq!(code.code, expected);
+            assert_eq!(code.period_start, timestamp / 30 * 30);
+        }
+    }
+
+    #[test]
+    fn test_yandex_secret_validation() {
+        let mut secret = BASE32_NOPAD.decode(VECTORS[0].1.as_bytes()).unwrap();
+        let totp = YandexTotp::new(None, &secret, SecureData::new(b"5239")).unwrap();
+        assert_eq!(totp.secret, secret[..SECRET_LENGTH]);
+
+        secret[3] ^= 1;
+        assert!(matches!(
+            YandexTotp::new(None, &secret, SecureData::new(b"5239")),
+            Err(TotpGenerateError::InvalidChecksum)
+        ));
+        assert!(matches!(
+            YandexTotp::new(None, &secret[..10], SecureData::new(b"5239")),
+            Err(TotpGenerateError::InvalidSecretLength)
+        ));
+
+        let totp = YandexTotp::new(None, &secret[..16], SecureData::new(b"")).unwrap();
+        assert!(matches!(
+            totp.generate(0),
+            Err(TotpGenerateError::MissingPin)
+        ));
+    }
+}
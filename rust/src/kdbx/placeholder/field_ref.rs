@@ -0,0 +1,141 @@
+use uuid::Uuid;
+
+use crate::kdbx::xml::{
+    database::KeePassDatabase,
+    entities::{
+        Entry, FIELD_NOTES, FIELD_PASSWORD, FIELD_TITLE, FIELD_URL, FIELD_USER_NAME,
+        STANDARD_FIELDS,
+    },
+    errors::KdbxDatabaseError,
+};
+
+/// 字段引用中的字段代码
+///
+/// <https://keepass.info/help/base/fieldrefs.html>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefField {
+    Title,
+    UserName,
+    Password,
+    Url,
+    Notes,
+    Uuid,
+    /// 自定义字符串字段, 只能用于搜索
+    Other,
+}
+
+impl RefField {
+    fn from_code(code: char) -> Option<Self> {
+        match code.to_ascii_uppercase() {
+            'T' => Some(RefField::Title),
+            'U' => Some(RefField::UserName),
+            'P' => Some(RefField::Password),
+            'A' => Some(RefField::Url),
+            'N' => Some(RefField::Notes),
+            'I' => Some(RefField::Uuid),
+            'O' => Some(RefField::Other),
+            _ => None,
+        }
+    }
+
+    /// 标准字段对应的Key
+    pub fn key(&self) -> Option<&'static str> {
+        match self {
+            RefField::Title => Some(FIELD_TITLE),
+            RefField::UserName => Some(FIELD_USER_NAME),
+            RefField::Password => Some(FIELD_PASSWORD),
+            RefField::Url => Some(FIELD_URL),
+            RefField::Notes => Some(FIELD_NOTES),
+            RefField::Uuid | RefField::Other => None,
+        }
+    }
+}
+
+/// 字段引用 `{REF:<Wanted>@<SearchIn>:<Text>}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRef<'a> {
+    pub wanted: RefField,
+    pub search_in: RefField,
+    pub text: &'a str,
+}
+
+impl<'a> FieldRef<'a> {
+    /// 解析 `REF:` 之后的部分, 例如 `P@I:46C9B1FFBD4ABC4BBB260C6190BAD20C`
+    pub fn parse(value: &'a str) -> Option<Self> {
+        let mut chars = value.chars();
+        let wanted = RefField::from_code(chars.next()?)?;
+        if wanted == RefField::Other || chars.next()? != '@' {
+            return None;
+        }
+        let search_in = RefField::from_code(chars.next()?)?;
+        if chars.next()? != ':' {
+            return None;
+        }
+        Some(Self {
+            wanted,
+            search_in,
+            text: chars.as_str(),
+        })
+    }
+
+    /// 在数据库中查找第一个匹配的条目, 文本搜索不区分大小写
+    pub fn find_entry<'d>(
+        &self,
+        database: &'d KeePassDatabase,
+    ) -> Result<Option<&'d Entry>, KdbxDatabaseError> {
+        let entries = database.document.root.group.all_entries();
+
+        if self.search_in == RefField::Uuid {
+            let uuid = match Uuid::parse_str(self.text) {
+                Ok(uuid) => uuid,
+                Err(_) => return Ok(None),
+            };
+            return Ok(entries
+                .into_iter()
+                .find(|entry| entry.uuid.value() == &uuid));
+        }
+
+        let needle = self.text.to_lowercase();
+        for entry in entries {
+            for field in &entry.string {
+                let matches_key = match self.search_in.key() {
+                    Some(key) => field.key == key,
+                    None => !STANDARD_FIELDS.contains(&field.key.as_str()),
+                };
+                if !matches_key {
+                    continue;
+                }
+                let value = database.get_value_string(&field.value)?;
+                if value.to_lowercase().contains(&needle) {
+                    return Ok(Some(entry));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_field_ref() {
+        let field_ref = FieldRef::parse("P@I:46C9B1FFBD4ABC4BBB260C6190BAD20C").unwrap();
+        assert_eq!(field_ref.wanted, RefField::Password);
+        assert_eq!(field_ref.search_in, RefField::Uuid);
+        assert_eq!(field_ref.text, "46C9B1FFBD4ABC4BBB260C6190BAD20C");
+
+        let field_ref = FieldRef::parse("u@t:Example").unwrap();
+        assert_eq!(field_ref.wanted, RefField::UserName);
+        assert_eq!(field_ref.search_in, RefField::Title);
+    }
+
+    #[test]
+    fn test_parse_invalid_field_ref() {
+        assert!(FieldRef::parse("O@I:abc").is_none());
+        assert!(FieldRef::parse("X@I:abc").is_none());
+        assert!(FieldRef::parse("P-I:abc").is_none());
+        assert!(FieldRef::parse("P@I").is_none());
+    }
+}
@@ -0,0 +1,85 @@
+pub mod expander;
+pub mod field_ref;
+pub mod transform;
+
+use chrono::{DateTime, FixedOffset};
+use thiserror::Error;
+
+use crate::kdbx::xml::errors::KdbxDatabaseError;
+
+/// 占位符最大递归深度, 防止字段之间循环引用
+pub const MAX_RECURSION_DEPTH: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum PlaceholderError {
+    #[error("Placeholder recursion limit exceeded")]
+    RecursionLimit,
+
+    #[error("Invalid regular expression: {0}")]
+    InvalidRegex(#[from] regex::Error),
+
+    #[error("Database error")]
+    DatabaseError(#[from] KdbxDatabaseError),
+}
+
+/// 展开结果的用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaceholderMode {
+    /// 直接展开, 用于URL和备注
+    #[default]
+    Plain,
+    /// 用于自动输入序列, 插入的值会转义 `+^%~(){}[]`, 保证按字面输入
+    AutoType,
+}
+
+/// 时钟, 用于 `{DT_*}` 占位符
+pub trait Clock {
+    fn now(&self) -> DateTime<FixedOffset>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        chrono::Local::now().fixed_offset()
+    }
+}
+
+/// 运行环境, 用于 `%VAR%` 和 `{DB_*}` 占位符
+pub trait Environment {
+    fn var(&self, name: &str) -> Option<String>;
+    fn database_path(&self) -> Option<String>;
+}
+
+#[derive(Default)]
+pub struct SystemEnvironment {
+    pub database_path: Option<String>,
+}
+
+impl Environment for SystemEnvironment {
+    fn var(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+
+    fn database_path(&self) -> Option<String> {
+        self.database_path.clone()
+    }
+}
+
+/// 自动输入中具有特殊含义的字符
+const AUTO_TYPE_SPECIAL_CHARS: &[char] = &['+', '^', '%', '@', '~', '(', ')', '{', '}', '[', ']'];
+
+/// 将文本转义为可以在自动输入序列中按字面输入的形式
+pub fn escape_auto_type(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if AUTO_TYPE_SPECIAL_CHARS.contains(&c) {
+            result.push('{');
+            result.push(c);
+            result.push('}');
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
@@ -0,0 +1,122 @@
+use base64::Engine;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use regex::Regex;
+
+use crate::kdbx::placeholder::PlaceholderError;
+
+/// 与 .NET `Uri.EscapeDataString` 一致, 保留RFC 3986中的非保留字符
+const URI_ESCAPE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// 按首字符作为分隔符拆分参数, 例如 `/text/search/replace/` 拆分为3个参数
+///
+/// 参数数量不符时返回None
+pub fn split_args(value: &str, count: usize) -> Option<Vec<&str>> {
+    let separator = value.chars().next()?;
+    let rest = &value[separator.len_utf8()..];
+    let mut args: Vec<&str> = rest.split(separator).collect();
+    // 末尾分隔符之后必须为空
+    if args.len() != count + 1 || !args.pop()?.is_empty() {
+        return None;
+    }
+    Some(args)
+}
+
+/// `{T-REPLACE-RX:/Text/Search/Replace/}`
+pub fn replace_regex(text: &str, search: &str, replace: &str) -> Result<String, PlaceholderError> {
+    let regex = Regex::new(search)?;
+    Ok(regex.replace_all(text, replace).into_owned())
+}
+
+/// `{T-CONV:/Text/Type/}` 的转换类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    Upper,
+    Lower,
+    Base64,
+    Hex,
+    Uri,
+    UriDecode,
+    /// 不做转换, 且在自动输入中不转义
+    Raw,
+}
+
+impl Conversion {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "u" | "upper" => Some(Conversion::Upper),
+            "l" | "lower" => Some(Conversion::Lower),
+            "base64" => Some(Conversion::Base64),
+            "hex" => Some(Conversion::Hex),
+            "uri" => Some(Conversion::Uri),
+            "uri-dec" => Some(Conversion::UriDecode),
+            "raw" => Some(Conversion::Raw),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Conversion::Upper => text.to_uppercase(),
+            Conversion::Lower => text.to_lowercase(),
+            Conversion::Base64 => base64::engine::general_purpose::STANDARD.encode(text),
+            Conversion::Hex => hex::encode_upper(text),
+            Conversion::Uri => utf8_percent_encode(text, URI_ESCAPE_SET).to_string(),
+            Conversion::UriDecode => percent_decode_str(text).decode_utf8_lossy().into_owned(),
+            Conversion::Raw => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args("/text/search/replace/", 3),
+            Some(vec!["text", "search", "replace"])
+        );
+        assert_eq!(split_args("|a/b|u|", 2), Some(vec!["a/b", "u"]));
+        assert_eq!(split_args("/text/search/", 3), None);
+        assert_eq!(split_args("/text/search/replace", 3), None);
+        assert_eq!(split_args("", 2), None);
+    }
+
+    #[test]
+    fn test_replace_regex() {
+        assert_eq!(
+            replace_regex("user@example.com", "@.*$", "").unwrap(),
+            "user"
+        );
+        assert_eq!(
+            replace_regex("2024-01-31", r"(\d+)-(\d+)-(\d+)", "$3.$2.$1").unwrap(),
+            "31.01.2024"
+        );
+        assert!(replace_regex("text", "(", "").is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(Conversion::parse("U").unwrap().apply("aBc"), "ABC");
+        assert_eq!(Conversion::parse("lower").unwrap().apply("aBc"), "abc");
+        assert_eq!(
+            Conversion::parse("base64").unwrap().apply("hello"),
+            "aGVsbG8="
+        );
+        assert_eq!(Conversion::parse("hex").unwrap().apply("hi"), "6869");
+        assert_eq!(
+            Conversion::parse("uri").unwrap().apply("a b&c~"),
+            "a%20b%26c~"
+        );
+        assert_eq!(
+            Conversion::parse("uri-dec").unwrap().apply("a%20b%26c"),
+            "a b&c"
+        );
+        assert_eq!(Conversion::parse("unknown"), None);
+    }
+}
@@ -0,0 +1,582 @@
+//! 快速解锁: 以PIN加密缓存KDF的输出, 之后解锁时跳过耗时的密钥变换
+//!
+//! PIN的熵很低, 缓存一旦泄露便可被离线穷举, 尝试次数只能限制经由本接口的尝试.
+//! 平台应尽量提供保存在系统密钥库中的密钥作为`platform_secret`, 使缓存离开本机后无法破解.
+//! 剩余次数以平台密钥派生的密钥做MAC, 没有平台密钥时只能发现意外的损坏, 无法防止篡改.
+
+use std::io::{Cursor, Read, Write};
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::crypto::hash::{calculate_hmac_multiple, calculate_sha256_multiple};
+use crate::crypto::kdf::{self, Argon2Kdf, Kdf, KdfOptions};
+use crate::crypto::memory_crypt::SecureDataError;
+use crate::crypto::secure_data::SecureData;
+use crate::kdbx::db::kdbx4::config::Kdbx4Config;
+use crate::kdbx::db::kdbx4::header_entity::kdf_config::KdfConfig;
+
+const CACHE_MAGIC: [u8; 4] = *b"KOQU";
+const CACHE_VERSION: u8 = 2;
+const SALT_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+/// 缓存的密文长度上限, 变换后的密钥为32字节, 加上16字节的认证标签
+const MAX_CIPHERTEXT_SIZE: usize = 1024;
+/// PIN派生密钥的内存上限(1 GiB), 防止被篡改的缓存耗尽内存
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 100;
+const ATTEMPTS_MAC_LABEL: &[u8] = b"keepass_one quick unlock attempts";
+
+#[derive(Debug, Error)]
+pub enum QuickUnlockError {
+    #[error("Quick unlock cache has been wiped")]
+    Wiped,
+
+    #[error("Quick unlock cache does not match the database header")]
+    Invalidated,
+
+    #[error("Wrong PIN, {0} attempts remaining")]
+    WrongPin(u8),
+
+    #[error("Too many wrong PIN attempts, quick unlock cache wiped")]
+    TooManyAttempts,
+
+    #[error("Invalid quick unlock cache format")]
+    InvalidFormat,
+
+    #[error("Invalid quick unlock options")]
+    InvalidOptions,
+
+    #[error("PIN must not be empty")]
+    EmptyPin,
+
+    #[error("PIN key derivation error")]
+    KdfError(#[from] kdf::KdfError),
+
+    #[error("Encryption error")]
+    EncryptionError,
+
+    #[error("Random number generator error")]
+    RandomError(#[from] getrandom::Error),
+
+    #[error("Secure data error")]
+    SecureDataError(#[from] SecureDataError),
+}
+
+/// 创建快速解锁缓存的选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuickUnlockOptions {
+    /// 允许连续输错PIN的次数, 用尽后缓存被清除
+    pub max_attempts: u8,
+    /// 由PIN派生密钥时Argon2id使用的内存, 单位KiB
+    pub memory_kib: u32,
+    pub iterations: u32,
+}
+
+impl QuickUnlockOptions {
+    fn is_valid(&self) -> bool {
+        self.max_attempts > 0
+            && (1..=MAX_MEMORY_KIB).contains(&self.memory_kib)
+            && (1..=MAX_ITERATIONS).contains(&self.iterations)
+    }
+}
+
+impl Default for QuickUnlockOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            memory_kib: 32 * 1024,
+            iterations: 3,
+        }
+    }
+}
+
+/// 以PIN加密的变换后密钥, 可通过[`Self::to_bytes`]持久化
+///
+/// 每次解锁后剩余次数都可能变化, 调用方需重新保存
+pub struct QuickUnlockCache {
+    options: QuickUnlockOptions,
+    remaining_attempts: u8,
+    fingerprint: [u8; 32],
+    pin_salt: [u8; SALT_SIZE],
+    nonce: [u8; NONCE_SIZE],
+    ciphertext: Vec<u8>,
+    /// 剩余次数的MAC, 序列化后可被任意修改, 解锁前先校验
+    attempts_mac: [u8; 32],
+}
+
+impl QuickUnlockCache {
+    /// 为`config`对应的header缓存变换后的密钥
+    pub fn create(
+        config: &Kdbx4Config,
+        transformed_key: &SecureData,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        options: QuickUnlockOptions,
+    ) -> Result<Self, QuickUnlockError> {
+        Self::create_with_options(
+            config,
+            transformed_key,
+            pin,
+            platform_secret,
+            options,
+            &KdfOptions::default(),
+        )
+    }
+
+    /// 同[`Self::create`], 由PIN派生密钥时报告进度并可被取消
+    pub fn create_with_options(
+        config: &Kdbx4Config,
+        transformed_key: &SecureData,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        options: QuickUnlockOptions,
+        kdf_options: &KdfOptions,
+    ) -> Result<Self, QuickUnlockError> {
+        if pin.is_empty() {
+            return Err(QuickUnlockError::EmptyPin);
+        }
+        if !options.is_valid() {
+            return Err(QuickUnlockError::InvalidOptions);
+        }
+        let mut pin_salt = [0u8; SALT_SIZE];
+        let mut nonce = [0u8; NONCE_SIZE];
+        getrandom::fill(&mut pin_salt)?;
+        getrandom::fill(&mut nonce)?;
+        let fingerprint = header_fingerprint(config);
+
+        let key = derive_pin_key(pin, platform_secret, &pin_salt, &options, kdf_options)?;
+        let cipher = ChaCha20Poly1305::new_from_slice(key.as_slice())
+            .map_err(|_| QuickUnlockError::EncryptionError)?;
+        let ciphertext = cipher
+            .encrypt(
+                (&nonce).into(),
+                Payload {
+                    msg: &transformed_key.unsecure()?,
+                    aad: &fingerprint,
+                },
+            )
+            .map_err(|_| QuickUnlockError::EncryptionError)?;
+
+        let mut cache = Self {
+            options,
+            remaining_attempts: options.max_attempts,
+            fingerprint,
+            pin_salt,
+            nonce,
+            ciphertext,
+            attempts_mac: [0; 32],
+        };
+        cache.attempts_mac = cache.attempts_mac(platform_secret)?;
+        Ok(cache)
+    }
+
+    /// 缓存是否仍对应`config`的KDF参数与主种子
+    pub fn is_valid_for(&self, config: &Kdbx4Config) -> bool {
+        !self.is_wiped() && self.fingerprint == header_fingerprint(config)
+    }
+
+    pub fn is_wiped(&self) -> bool {
+        self.ciphertext.is_empty()
+    }
+
+    /// 保存的剩余次数, 解锁时才校验其MAC
+    pub fn remaining_attempts(&self) -> u8 {
+        self.remaining_attempts
+    }
+
+    /// 以PIN解出变换后的密钥
+    ///
+    /// header已改变或剩余次数未通过校验时缓存被清除; PIN错误时扣减剩余次数, 用尽后缓存被清除
+    pub fn unlock(
+        &mut self,
+        config: &Kdbx4Config,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+    ) -> Result<SecureData, QuickUnlockError> {
+        self.unlock_with_options(config, pin, platform_secret, &KdfOptions::default())
+    }
+
+    /// 同[`Self::unlock`], 由PIN派生密钥时报告进度并可被取消, 取消不扣减剩余次数
+    pub fn unlock_with_options(
+        &mut self,
+        config: &Kdbx4Config,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        kdf_options: &KdfOptions,
+    ) -> Result<SecureData, QuickUnlockError> {
+        if self.is_wiped() {
+            return Err(QuickUnlockError::Wiped);
+        }
+        if self.fingerprint != header_fingerprint(config) {
+            self.wipe();
+            return Err(QuickUnlockError::Invalidated);
+        }
+        // 剩余次数被篡改或平台密钥不符时视为已用尽
+        if self.attempts_mac != self.attempts_mac(platform_secret)? {
+            self.wipe();
+            return Err(QuickUnlockError::TooManyAttempts);
+        }
+
+        let key = derive_pin_key(
+            pin,
+            platform_secret,
+            &self.pin_salt,
+            &self.options,
+            kdf_options,
+        )?;
+        let cipher = ChaCha20Poly1305::new_from_slice(key.as_slice())
+            .map_err(|_| QuickUnlockError::EncryptionError)?;
+        match cipher.decrypt(
+            (&self.nonce).into(),
+            Payload {
+                msg: &self.ciphertext,
+                aad: &self.fingerprint,
+            },
+        ) {
+            Ok(mut transformed_key) => {
+                self.remaining_attempts = self.options.max_attempts;
+                self.attempts_mac = self.attempts_mac(platform_secret)?;
+                Ok(SecureData::take(&mut transformed_key))
+            }
+            Err(_) => {
+                self.remaining_attempts = self.remaining_attempts.saturating_sub(1);
+                if self.remaining_attempts == 0 {
+                    self.wipe();
+                    Err(QuickUnlockError::TooManyAttempts)
+                } else {
+                    self.attempts_mac = self.attempts_mac(platform_secret)?;
+                    Err(QuickUnlockError::WrongPin(self.remaining_attempts))
+                }
+            }
+        }
+    }
+
+    /// 以平台密钥派生的密钥计算剩余次数及其所属缓存的MAC
+    fn attempts_mac(&self, platform_secret: Option<&[u8]>) -> Result<[u8; 32], QuickUnlockError> {
+        let key = calculate_sha256_multiple(&[
+            ATTEMPTS_MAC_LABEL,
+            platform_secret.unwrap_or_default(),
+            &self.pin_salt,
+        ]);
+        let mac = calculate_hmac_multiple(
+            &[
+                &[self.options.max_attempts, self.remaining_attempts],
+                &self.options.memory_kib.to_le_bytes(),
+                &self.options.iterations.to_le_bytes(),
+                &self.fingerprint,
+                &self.nonce,
+                &self.ciphertext,
+            ],
+            &key,
+        )
+        .map_err(|_| QuickUnlockError::EncryptionError)?;
+        Ok(mac.into())
+    }
+
+    /// 清除缓存的密文, 之后只能完整解锁
+    pub fn wipe(&mut self) {
+        self.ciphertext.zeroize();
+        self.pin_salt.zeroize();
+        self.nonce.zeroize();
+        self.remaining_attempts = 0;
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        // 写入Vec不会失败
+        self.write(&mut buffer).unwrap();
+        buffer
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&CACHE_MAGIC)?;
+        writer.write_u8(CACHE_VERSION)?;
+        writer.write_u8(self.options.max_attempts)?;
+        writer.write_u8(self.remaining_attempts)?;
+        writer.write_u32::<LE>(self.options.memory_kib)?;
+        writer.write_u32::<LE>(self.options.iterations)?;
+        writer.write_all(&self.fingerprint)?;
+        writer.write_all(&self.pin_salt)?;
+        writer.write_all(&self.nonce)?;
+        writer.write_u32::<LE>(self.ciphertext.len() as u32)?;
+        writer.write_all(&self.ciphertext)?;
+        writer.write_all(&self.attempts_mac)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, QuickUnlockError> {
+        Self::read(&mut Cursor::new(data)).map_err(|_| QuickUnlockError::InvalidFormat)
+    }
+
+    fn read<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let invalid = || std::io::Error::from(std::io::ErrorKind::InvalidData);
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != CACHE_MAGIC || reader.read_u8()? != CACHE_VERSION {
+            return Err(invalid());
+        }
+        let max_attempts = reader.read_u8()?;
+        let remaining_attempts = reader.read_u8()?;
+        let options = QuickUnlockOptions {
+            max_attempts,
+            memory_kib: reader.read_u32::<LE>()?,
+            iterations: reader.read_u32::<LE>()?,
+        };
+        let mut fingerprint = [0u8; 32];
+        let mut pin_salt = [0u8; SALT_SIZE];
+        let mut nonce = [0u8; NONCE_SIZE];
+        reader.read_exact(&mut fingerprint)?;
+        reader.read_exact(&mut pin_salt)?;
+        reader.read_exact(&mut nonce)?;
+        let ciphertext_len = reader.read_u32::<LE>()? as usize;
+        if ciphertext_len > MAX_CIPHERTEXT_SIZE
+            || remaining_attempts > options.max_attempts
+            || !options.is_valid()
+        {
+            return Err(invalid());
+        }
+        let mut ciphertext = vec![0u8; ciphertext_len];
+        let mut attempts_mac = [0u8; 32];
+        reader.read_exact(&mut ciphertext)?;
+        reader.read_exact(&mut attempts_mac)?;
+        if reader.read(&mut [0u8])? != 0 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            options,
+            remaining_attempts,
+            fingerprint,
+            pin_salt,
+            nonce,
+            ciphertext,
+            attempts_mac,
+        })
+    }
+}
+
+impl Drop for QuickUnlockCache {
+    fn drop(&mut self) {
+        self.ciphertext.zeroize();
+    }
+}
+
+/// 主种子与KDF参数的摘要, 其中任何一项变化都会使缓存失效
+fn header_fingerprint(config: &Kdbx4Config) -> [u8; 32] {
+    let kdf = match &config.kdf_parameters {
+        KdfConfig::Aes { salt, rounds } => [&[0u8][..], salt, &rounds.to_le_bytes()].concat(),
+        KdfConfig::Argon2 {
+            version,
+            salt,
+            iterations,
+            memory,
+            parallelism,
+            variant,
+        } => [
+            &[1u8][..],
+            &variant.as_u32().to_le_bytes(),
+            &version.to_le_bytes(),
+            &iterations.to_le_bytes(),
+            &memory.to_le_bytes(),
+            &parallelism.to_le_bytes(),
+            salt,
+        ]
+        .concat(),
+    };
+    calculate_sha256_multiple(&[&config.master_salt_seed, &kdf]).into()
+}
+
+fn derive_pin_key(
+    pin: &str,
+    platform_secret: Option<&[u8]>,
+    salt: &[u8],
+    options: &QuickUnlockOptions,
+    kdf_options: &KdfOptions,
+) -> Result<Zeroizing<Vec<u8>>, QuickUnlockError> {
+    let kdf = Argon2Kdf {
+        memory: options.memory_kib as u64 * 1024,
+        salt: salt.to_vec(),
+        iterations: options.iterations as u64,
+        parallelism: 1,
+        version: argon2::Version::Version13,
+        variant: argon2::Variant::Argon2id,
+        secret: Zeroizing::new(platform_secret.unwrap_or_default().to_vec()),
+    };
+    let key = kdf.transform_key_with(&SecureData::new(pin.as_bytes()), kdf_options)?;
+    Ok(key.unsecure()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::db::kdbx4::header_entity::{
+        compression::CompressionConfig, encryption_algorithm::EncryptionAlgorithm,
+    };
+
+    const OPTIONS: QuickUnlockOptions = QuickUnlockOptions {
+        max_attempts: 3,
+        memory_kib: 64,
+        iterations: 1,
+    };
+
+    fn config() -> Kdbx4Config {
+        Kdbx4Config {
+            encryption_algorithm: EncryptionAlgorithm::ChaCha20,
+            compression_config: CompressionConfig::None,
+            master_salt_seed: [1; 32],
+            encryption_iv: vec![0; 12],
+            kdf_parameters: KdfConfig::Argon2 {
+                version: 0x13,
+                salt: vec![2; 32],
+                iterations: 2,
+                memory: 64 * 1024,
+                parallelism: 1,
+                variant: argon2::Variant::Argon2id,
+            },
+        }
+    }
+
+    fn cache(secret: Option<&[u8]>) -> QuickUnlockCache {
+        QuickUnlockCache::create(
+            &config(),
+            &SecureData::new(&[7; 32]),
+            "1234",
+            secret,
+            OPTIONS,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_unlock() {
+        let mut cache = cache(Some(b"platform"));
+        let key = cache.unlock(&config(), "1234", Some(b"platform")).unwrap();
+        assert_eq!(key.unsecure().unwrap().as_slice(), &[7; 32]);
+
+        assert!(matches!(
+            cache.unlock(&config(), "0000", Some(b"platform")),
+            Err(QuickUnlockError::WrongPin(2))
+        ));
+        assert!(cache.unlock(&config(), "1234", Some(b"platform")).is_ok());
+        assert_eq!(cache.remaining_attempts(), 3);
+
+        // 平台密钥不符时无法校验剩余次数, 视为已用尽
+        assert!(matches!(
+            cache.unlock(&config(), "1234", None),
+            Err(QuickUnlockError::TooManyAttempts)
+        ));
+        assert!(cache.is_wiped());
+    }
+
+    #[test]
+    fn test_wiped_after_attempts() {
+        let mut cache = cache(None);
+        assert!(matches!(
+            cache.unlock(&config(), "0000", None),
+            Err(QuickUnlockError::WrongPin(2))
+        ));
+        assert!(matches!(
+            cache.unlock(&config(), "0000", None),
+            Err(QuickUnlockError::WrongPin(1))
+        ));
+        assert!(matches!(
+            cache.unlock(&config(), "0000", None),
+            Err(QuickUnlockError::TooManyAttempts)
+        ));
+        assert!(cache.is_wiped());
+        assert!(matches!(
+            cache.unlock(&config(), "1234", None),
+            Err(QuickUnlockError::Wiped)
+        ));
+    }
+
+    #[test]
+    fn test_invalidated_by_header_change() {
+        let mut changed_seed = config();
+        changed_seed.master_salt_seed = [3; 32];
+        let mut changed_kdf = config();
+        changed_kdf.kdf_parameters = changed_kdf.kdf_parameters.rekey().unwrap();
+        let mut changed_iv = config();
+        changed_iv.encryption_iv = vec![9; 12];
+
+        assert!(cache(None).is_valid_for(&changed_iv));
+        for changed in [changed_seed, changed_kdf] {
+            let mut cache = cache(None);
+            assert!(!cache.is_valid_for(&changed));
+            assert!(matches!(
+                cache.unlock(&changed, "1234", None),
+                Err(QuickUnlockError::Invalidated)
+            ));
+            assert!(cache.is_wiped());
+        }
+    }
+
+    #[test]
+    fn test_serialization() {
+        let mut cache = cache(None);
+        assert!(cache.unlock(&config(), "0000", None).is_err());
+        let bytes = cache.to_bytes();
+
+        let mut restored = QuickUnlockCache::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.remaining_attempts(), 2);
+        assert!(restored.unlock(&config(), "1234", None).is_ok());
+
+        assert!(matches!(
+            QuickUnlockCache::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(QuickUnlockError::InvalidFormat)
+        ));
+        let mut tampered = bytes.clone();
+        tampered[6] = 200;
+        assert!(QuickUnlockCache::from_bytes(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_reset_attempts_rejected() {
+        for secret in [None, Some(&b"platform"[..])] {
+            let mut cache = cache(secret);
+            assert!(cache.unlock(&config(), "0000", secret).is_err());
+            let mut bytes = cache.to_bytes();
+            // remaining_attempts位于偏移6
+            assert_eq!(bytes[6], 2);
+            bytes[6] = 3;
+
+            let mut restored = QuickUnlockCache::from_bytes(&bytes).unwrap();
+            assert_eq!(restored.remaining_attempts(), 3);
+            assert!(matches!(
+                restored.unlock(&config(), "1234", secret),
+                Err(QuickUnlockError::TooManyAttempts)
+            ));
+            assert!(restored.is_wiped());
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_options_rejected() {
+        let bytes = cache(None).to_bytes();
+        // memory_kib位于偏移7, iterations位于偏移11
+        for (offset, value) in [(7, u32::MAX), (7, 0), (11, u32::MAX), (11, 0)] {
+            let mut tampered = bytes.clone();
+            tampered[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            assert!(matches!(
+                QuickUnlockCache::from_bytes(&tampered),
+                Err(QuickUnlockError::InvalidFormat)
+            ));
+        }
+
+        let options = QuickUnlockOptions {
+            memory_kib: MAX_MEMORY_KIB + 1,
+            ..OPTIONS
+        };
+        let key = SecureData::new(&[7; 32]);
+        assert!(matches!(
+            QuickUnlockCache::create(&config(), &key, "1234", None, options),
+            Err(QuickUnlockError::InvalidOptions)
+        ));
+        assert!(matches!(
+            QuickUnlockCache::create(&config(), &key, "", None, OPTIONS),
+            Err(QuickUnlockError::EmptyPin)
+        ));
+    }
+}
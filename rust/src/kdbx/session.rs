@@ -0,0 +1,462 @@
+//! 已打开数据库的会话, 负责空闲超时与事件触发的自动锁定
+//!
+//! 锁定时整个`Kdbx4`被丢弃, 其中的`SecureData`、内层密钥和附件随之清零,
+//! 只保留重新打开所需的文件位置和可选的快速解锁缓存. 重新解锁后会话ID保持不变.
+
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::crypto::kdf::KdfOptions;
+use crate::kdbx::config::MemoryProtectConfig;
+use crate::kdbx::db::kdbx4::errors::Kdbx4Error;
+use crate::kdbx::db::kdbx4::Kdbx4;
+use crate::kdbx::keys::KdbxKey;
+use crate::kdbx::quick_unlock::{QuickUnlockCache, QuickUnlockError, QuickUnlockOptions};
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("Session is locked")]
+    Locked,
+
+    #[error("Session is not locked")]
+    NotLocked,
+
+    #[error("Quick unlock is not enabled for this session")]
+    QuickUnlockUnavailable,
+
+    #[error("Failed to open database")]
+    Kdbx4Error(#[from] Kdbx4Error),
+
+    #[error("Quick unlock error")]
+    QuickUnlockError(#[from] QuickUnlockError),
+
+    #[error("Failed to generate session id")]
+    RandomError(#[from] getrandom::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionId(u64);
+
+impl SessionId {
+    fn generate() -> Result<Self, getrandom::Error> {
+        Ok(Self(getrandom::u64()?))
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+/// 应用转发给会话的生命周期事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// 用户操作, 重置空闲计时
+    UserActivity,
+    AppBackground,
+    AppForeground,
+    ScreenLocked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockReason {
+    Manual,
+    IdleTimeout,
+    AppBackground,
+    ScreenLocked,
+}
+
+/// 自动锁定策略
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockPolicy {
+    /// 无操作超过此时间后锁定, `None`表示不按空闲锁定
+    pub idle_timeout: Option<Duration>,
+    pub lock_on_background: bool,
+    pub lock_on_screen_lock: bool,
+}
+
+impl Default for LockPolicy {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Some(Duration::from_secs(5 * 60)),
+            lock_on_background: true,
+            lock_on_screen_lock: true,
+        }
+    }
+}
+
+enum SessionState {
+    Unlocked(Box<Kdbx4>),
+    Locked(LockReason),
+}
+
+pub struct Session {
+    id: SessionId,
+    /// 数据库文件的位置(路径或平台URI), 由应用解释
+    location: String,
+    pub policy: LockPolicy,
+    state: SessionState,
+    last_activity: Instant,
+    quick_unlock: Option<QuickUnlockCache>,
+}
+
+impl Session {
+    pub fn new(location: &str, kdbx: Kdbx4, policy: LockPolicy) -> Result<Self, SessionError> {
+        Ok(Self {
+            id: SessionId::generate()?,
+            location: location.to_string(),
+            policy,
+            state: SessionState::Unlocked(Box::new(kdbx)),
+            last_activity: Instant::now(),
+            quick_unlock: None,
+        })
+    }
+
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// 已超时但尚未被[`Session::lock_if_idle`]锁定的会话也视为锁定
+    ///
+    /// 空闲超时只在应用调用[`Session::lock_if_idle`]、[`Session::database`]或
+    /// [`Session::handle_event`]时生效, 在此之前解密的数据库仍留在内存中.
+    pub fn is_locked(&self) -> bool {
+        matches!(self.state, SessionState::Locked(_)) || self.check_idle()
+    }
+
+    pub fn lock_reason(&self) -> Option<LockReason> {
+        match self.state {
+            SessionState::Locked(reason) => Some(reason),
+            SessionState::Unlocked(_) => None,
+        }
+    }
+
+    pub fn has_quick_unlock(&self) -> bool {
+        self.quick_unlock.is_some()
+    }
+
+    /// 访问已解锁的数据库并重置空闲计时, 已超时的会话会先被锁定
+    pub fn database(&mut self) -> Result<&mut Kdbx4, SessionError> {
+        self.lock_if_idle();
+        self.last_activity = Instant::now();
+        match &mut self.state {
+            SessionState::Unlocked(kdbx) => Ok(kdbx),
+            SessionState::Locked(_) => Err(SessionError::Locked),
+        }
+    }
+
+    /// 丢弃已解密的数据库, 已锁定时保留最初的锁定原因
+    pub fn lock(&mut self, reason: LockReason) {
+        if let SessionState::Unlocked(_) = self.state {
+            self.state = SessionState::Locked(reason);
+        }
+    }
+
+    /// 供应用定时调用, 空闲超时则锁定, 返回是否处于锁定状态
+    pub fn lock_if_idle(&mut self) -> bool {
+        if self.check_idle() {
+            self.lock(LockReason::IdleTimeout);
+        }
+        matches!(self.state, SessionState::Locked(_))
+    }
+
+    /// 处理生命周期事件, 返回事件后是否处于锁定状态
+    pub fn handle_event(&mut self, event: SessionEvent) -> bool {
+        match event {
+            SessionEvent::UserActivity if !self.lock_if_idle() => {
+                self.last_activity = Instant::now();
+            }
+            SessionEvent::AppBackground if self.policy.lock_on_background => {
+                self.lock(LockReason::AppBackground);
+            }
+            SessionEvent::ScreenLocked if self.policy.lock_on_screen_lock => {
+                self.lock(LockReason::ScreenLocked);
+            }
+            // 回到前台时按离开期间的空闲时间判断
+            _ => {
+                self.lock_if_idle();
+            }
+        }
+        matches!(self.state, SessionState::Locked(_))
+    }
+
+    /// 为当前数据库创建快速解锁缓存, 锁定后可凭PIN重新打开
+    pub fn enable_quick_unlock(
+        &mut self,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        options: QuickUnlockOptions,
+    ) -> Result<(), SessionError> {
+        self.enable_quick_unlock_with_options(pin, platform_secret, options, &KdfOptions::default())
+    }
+
+    /// 同[`Session::enable_quick_unlock`], 由PIN派生密钥时报告进度并可被取消
+    pub fn enable_quick_unlock_with_options(
+        &mut self,
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        options: QuickUnlockOptions,
+        kdf_options: &KdfOptions,
+    ) -> Result<(), SessionError> {
+        let cache = self.database()?.create_quick_unlock_with_options(
+            pin,
+            platform_secret,
+            options,
+            kdf_options,
+        )?;
+        self.quick_unlock = Some(cache);
+        Ok(())
+    }
+
+    pub fn disable_quick_unlock(&mut self) {
+        self.quick_unlock = None;
+    }
+
+    /// 以复合密钥重新打开`location`处读取的数据, 会话未锁定时返回[`SessionError::NotLocked`]
+    pub fn unlock_with_key(
+        &mut self,
+        data: &[u8],
+        key: &KdbxKey,
+        config: &MemoryProtectConfig,
+    ) -> Result<(), SessionError> {
+        self.unlock_with_key_options(data, key, config, &KdfOptions::default())
+    }
+
+    /// 同[`Session::unlock_with_key`], 密钥变换时报告进度并可被取消
+    pub fn unlock_with_key_options(
+        &mut self,
+        data: &[u8],
+        key: &KdbxKey,
+        config: &MemoryProtectConfig,
+        options: &KdfOptions,
+    ) -> Result<(), SessionError> {
+        self.ensure_locked()?;
+        let kdbx = Kdbx4::open_with_key_options(data, key, config, options)?;
+        self.restore(kdbx);
+        Ok(())
+    }
+
+    /// 以快速解锁缓存重新打开, 缓存被清除后会从会话中移除
+    pub fn unlock_with_quick_unlock(
+        &mut self,
+        data: &[u8],
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        config: &MemoryProtectConfig,
+    ) -> Result<(), SessionError> {
+        self.unlock_with_quick_unlock_options(
+            data,
+            pin,
+            platform_secret,
+            config,
+            &KdfOptions::default(),
+        )
+    }
+
+    /// 同[`Session::unlock_with_quick_unlock`], 由PIN派生密钥时报告进度并可被取消
+    pub fn unlock_with_quick_unlock_options(
+        &mut self,
+        data: &[u8],
+        pin: &str,
+        platform_secret: Option<&[u8]>,
+        config: &MemoryProtectConfig,
+        options: &KdfOptions,
+    ) -> Result<(), SessionError> {
+        self.ensure_locked()?;
+        let cache = self
+            .quick_unlock
+            .as_mut()
+            .ok_or(SessionError::QuickUnlockUnavailable)?;
+        let result = Kdbx4::open_with_quick_unlock_options(
+            data,
+            cache,
+            pin,
+            platform_secret,
+            config,
+            options,
+        );
+        if cache.is_wiped() {
+            self.quick_unlock = None;
+        }
+        self.restore(result?);
+        Ok(())
+    }
+
+    /// 避免重新解锁时替换已打开的数据库而丢失未保存的修改
+    fn ensure_locked(&mut self) -> Result<(), SessionError> {
+        if self.lock_if_idle() {
+            Ok(())
+        } else {
+            Err(SessionError::NotLocked)
+        }
+    }
+
+    fn restore(&mut self, kdbx: Kdbx4) {
+        self.state = SessionState::Unlocked(Box::new(kdbx));
+        self.last_activity = Instant::now();
+    }
+
+    fn check_idle(&self) -> bool {
+        match (&self.state, self.policy.idle_timeout) {
+            (SessionState::Unlocked(_), Some(timeout)) => self.last_activity.elapsed() >= timeout,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::kdbx::db::kdbx4::config::Kdbx4Config;
+    use crate::kdbx::db::kdbx4::header_entity::{
+        compression::CompressionConfig, encryption_algorithm::EncryptionAlgorithm,
+        kdf_config::KdfConfig,
+    };
+    use crate::kdbx::xml::fixtures;
+
+    const CONFIG: MemoryProtectConfig = MemoryProtectConfig {
+        enable_memory_crypt: false,
+        enable_mlock: false,
+    };
+
+    fn key(password: &str) -> KdbxKey {
+        let mut key = KdbxKey::new();
+        key.add_master_key(password);
+        key
+    }
+
+    /// 生成一个可以重新打开的数据库文件
+    fn database_file() -> Vec<u8> {
+        let config = Kdbx4Config {
+            encryption_algorithm: EncryptionAlgorithm::ChaCha20,
+            compression_config: CompressionConfig::None,
+            master_salt_seed: [1; 32],
+            encryption_iv: EncryptionAlgorithm::ChaCha20.get_random_iv().unwrap(),
+            kdf_parameters: KdfConfig::Aes {
+                salt: [2; 32],
+                rounds: 10,
+            },
+        };
+        let root = fixtures::group_xml(
+            &fixtures::uuid(1),
+            "Root",
+            "<IsExpanded>True</IsExpanded>\
+             <EnableAutoType>null</EnableAutoType>\
+             <EnableSearching>null</EnableSearching>",
+            "",
+        );
+        let kdbx = Kdbx4::create(
+            config.clone(),
+            fixtures::database(&root),
+            &key("password"),
+            &CONFIG,
+        )
+        .unwrap();
+        let mut buffer = Vec::new();
+        kdbx.save_with_key(&key("password"), config, &mut Cursor::new(&mut buffer))
+            .unwrap();
+        buffer
+    }
+
+    fn open_session(data: &[u8], policy: LockPolicy) -> Session {
+        let kdbx = Kdbx4::open_with_key(data, &key("password"), &CONFIG).unwrap();
+        Session::new("vault.kdbx", kdbx, policy).unwrap()
+    }
+
+    #[test]
+    fn test_lock_and_unlock_keeps_id() {
+        let data = database_file();
+        let mut session = open_session(&data, LockPolicy::default());
+        let id = session.id();
+        assert!(session.database().is_ok());
+        assert!(matches!(
+            session.unlock_with_key(&data, &key("password"), &CONFIG),
+            Err(SessionError::NotLocked)
+        ));
+
+        session.lock(LockReason::Manual);
+        assert!(session.is_locked());
+        assert_eq!(session.lock_reason(), Some(LockReason::Manual));
+        assert!(matches!(session.database(), Err(SessionError::Locked)));
+
+        assert!(session
+            .unlock_with_key(&data, &key("wrong"), &CONFIG)
+            .is_err());
+        assert!(session.is_locked());
+
+        session
+            .unlock_with_key(&data, &key("password"), &CONFIG)
+            .unwrap();
+        assert!(!session.is_locked());
+        assert_eq!(session.id(), id);
+        assert_eq!(session.location(), "vault.kdbx");
+    }
+
+    #[test]
+    fn test_idle_timeout_and_events() {
+        let data = database_file();
+        let policy = LockPolicy {
+            idle_timeout: Some(Duration::from_millis(20)),
+            lock_on_background: false,
+            lock_on_screen_lock: true,
+        };
+        let mut session = open_session(&data, policy);
+
+        assert!(!session.handle_event(SessionEvent::AppBackground));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(session.handle_event(SessionEvent::UserActivity));
+        assert_eq!(session.lock_reason(), Some(LockReason::IdleTimeout));
+
+        session
+            .unlock_with_key(&data, &key("password"), &CONFIG)
+            .unwrap();
+        assert!(session.handle_event(SessionEvent::ScreenLocked));
+        assert_eq!(session.lock_reason(), Some(LockReason::ScreenLocked));
+    }
+
+    #[test]
+    fn test_quick_unlock_session() {
+        let data = database_file();
+        let mut session = open_session(&data, LockPolicy::default());
+        assert!(matches!(
+            session.unlock_with_quick_unlock(&data, "1234", None, &CONFIG),
+            Err(SessionError::NotLocked)
+        ));
+        session.lock(LockReason::Manual);
+        assert!(matches!(
+            session.unlock_with_quick_unlock(&data, "1234", None, &CONFIG),
+            Err(SessionError::QuickUnlockUnavailable)
+        ));
+        session
+            .unlock_with_key(&data, &key("password"), &CONFIG)
+            .unwrap();
+
+        let options = QuickUnlockOptions {
+            max_attempts: 1,
+            memory_kib: 64,
+            iterations: 1,
+        };
+        session.enable_quick_unlock("1234", None, options).unwrap();
+        session.handle_event(SessionEvent::AppBackground);
+        assert!(session.is_locked());
+
+        session
+            .unlock_with_quick_unlock(&data, "1234", None, &CONFIG)
+            .unwrap();
+        assert!(!session.is_locked());
+
+        session.enable_quick_unlock("1234", None, options).unwrap();
+        session.lock(LockReason::Manual);
+        assert!(session
+            .unlock_with_quick_unlock(&data, "0000", None, &CONFIG)
+            .is_err());
+        assert!(!session.has_quick_unlock());
+        assert!(session.is_locked());
+    }
+}
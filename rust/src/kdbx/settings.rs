@@ -0,0 +1,220 @@
+use uuid::Uuid;
+
+use crate::kdbx::auto_type::DEFAULT_SEQUENCE;
+use crate::kdbx::xml::entities::{Entry, Group, TNullableBoolEx};
+
+/// 未设置时的默认值, 与KeePass一致
+const DEFAULT_SEARCHING_ENABLED: bool = true;
+const DEFAULT_AUTO_TYPE_ENABLED: bool = true;
+
+/// 沿父组继承后的有效设置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectiveSettings {
+    pub searching_enabled: bool,
+    pub auto_type_enabled: bool,
+    pub auto_type_sequence: String,
+}
+
+impl EffectiveSettings {
+    /// 计算组的有效设置, `path` 为从根组到该组本身的组链
+    pub fn for_group(path: &[&Group]) -> Self {
+        Self {
+            searching_enabled: inherit_bool(path, |group| group.enable_searching.as_ref())
+                .unwrap_or(DEFAULT_SEARCHING_ENABLED),
+            auto_type_enabled: inherit_bool(path, |group| group.enable_auto_type.as_ref())
+                .unwrap_or(DEFAULT_AUTO_TYPE_ENABLED),
+            auto_type_sequence: inherit_sequence(path)
+                .unwrap_or(DEFAULT_SEQUENCE)
+                .to_string(),
+        }
+    }
+
+    /// 计算条目的有效设置, `path` 为从根组到条目直接父组的组链
+    ///
+    /// 条目自身的自动输入开关只能关闭, 不能覆盖父组的禁用。
+    pub fn for_entry(entry: &Entry, path: &[&Group]) -> Self {
+        let mut settings = Self::for_group(path);
+        if let Some(auto_type) = &entry.auto_type {
+            settings.auto_type_enabled &= auto_type.enabled.value();
+            if let Some(sequence) = auto_type.default_sequence.as_deref() {
+                if !sequence.is_empty() {
+                    settings.auto_type_sequence = sequence.to_string();
+                }
+            }
+        }
+        settings
+    }
+}
+
+/// 从最近的组开始查找第一个非Null的值
+fn inherit_bool<F>(path: &[&Group], value: F) -> Option<bool>
+where
+    F: Fn(&Group) -> Option<&TNullableBoolEx>,
+{
+    path.iter()
+        .rev()
+        .filter_map(|group| value(group))
+        .find_map(|value| Option::<bool>::from(value.clone()))
+}
+
+/// 从最近的组开始查找第一个非空的默认自动输入序列
+pub fn inherit_sequence<'a>(path: &[&'a Group]) -> Option<&'a str> {
+    path.iter()
+        .rev()
+        .filter_map(|group| group.default_auto_type_sequence.as_deref())
+        .find(|sequence| !sequence.is_empty())
+}
+
+/// 在组树中按UUID解析组和条目的有效设置
+pub struct SettingsResolver<'a> {
+    root: &'a Group,
+}
+
+impl<'a> SettingsResolver<'a> {
+    pub fn new(root: &'a Group) -> Self {
+        Self { root }
+    }
+
+    /// 组或条目的父组链, 从根组开始, 不包含自身; 根组的父组链为空
+    pub fn parent_path(&self, uuid: &Uuid) -> Option<Vec<&'a Group>> {
+        if let Some(mut path) = self.root.find_group_path(uuid) {
+            path.pop();
+            return Some(path);
+        }
+        self.root.find_entry_path(uuid)
+    }
+
+    pub fn group(&self, uuid: &Uuid) -> Option<EffectiveSettings> {
+        let path = self.root.find_group_path(uuid)?;
+        Some(EffectiveSettings::for_group(&path))
+    }
+
+    pub fn entry(&self, uuid: &Uuid) -> Option<EffectiveSettings> {
+        let path = self.root.find_entry_path(uuid)?;
+        let entry = path
+            .last()?
+            .entry
+            .iter()
+            .find(|entry| entry.uuid.value() == uuid)?;
+        Some(EffectiveSettings::for_entry(entry, &path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::xml::database::KeePassDatabase;
+    use crate::kdbx::xml::fixtures::{self, uuid_value};
+
+    fn test_database() -> KeePassDatabase {
+        let disabled_entry = fixtures::entry_xml(
+            &fixtures::uuid(1),
+            &[("Title", "Disabled")],
+            r#"<AutoType>
+                <Enabled>False</Enabled>
+                <DataTransferObfuscation>0</DataTransferObfuscation>
+            </AutoType>"#,
+        );
+        let custom_entry = fixtures::entry_xml(
+            &fixtures::uuid(2),
+            &[("Title", "Custom")],
+            r#"<AutoType>
+                <Enabled>True</Enabled>
+                <DataTransferObfuscation>0</DataTransferObfuscation>
+                <DefaultSequence>{PASSWORD}{ENTER}</DefaultSequence>
+            </AutoType>"#,
+        );
+        let hidden_entry = fixtures::entry_xml(&fixtures::uuid(3), &[("Title", "Hidden")], "");
+        let inherit = fixtures::group_xml(
+            &fixtures::uuid(13),
+            "Inherit",
+            r#"<DefaultAutoTypeSequence></DefaultAutoTypeSequence>
+            <EnableAutoType>Null</EnableAutoType>
+            <EnableSearching>null</EnableSearching>"#,
+            &format!("{disabled_entry}{custom_entry}"),
+        );
+        let hidden = fixtures::group_xml(
+            &fixtures::uuid(12),
+            "Hidden",
+            r#"<DefaultAutoTypeSequence>{USERNAME}{TAB}{TAB}{PASSWORD}</DefaultAutoTypeSequence>
+            <EnableAutoType>False</EnableAutoType>
+            <EnableSearching>False</EnableSearching>"#,
+            &format!("{hidden_entry}{inherit}"),
+        );
+        let plain = fixtures::group_xml(&fixtures::uuid(11), "Plain", "", "");
+        fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(10),
+            "Root",
+            "",
+            &format!("{plain}{hidden}"),
+        ))
+    }
+
+    #[test]
+    fn test_group_settings() {
+        let database = test_database();
+        let resolver = SettingsResolver::new(&database.document.root.group);
+
+        let defaults = EffectiveSettings {
+            searching_enabled: true,
+            auto_type_enabled: true,
+            auto_type_sequence: DEFAULT_SEQUENCE.to_string(),
+        };
+        assert_eq!(resolver.group(&uuid_value(10)), Some(defaults.clone()));
+        assert_eq!(resolver.group(&uuid_value(11)), Some(defaults));
+
+        let hidden = EffectiveSettings {
+            searching_enabled: false,
+            auto_type_enabled: false,
+            auto_type_sequence: "{USERNAME}{TAB}{TAB}{PASSWORD}".to_string(),
+        };
+        assert_eq!(resolver.group(&uuid_value(12)), Some(hidden.clone()));
+        // Null和空序列均继承父组
+        assert_eq!(resolver.group(&uuid_value(13)), Some(hidden));
+
+        assert_eq!(resolver.group(&uuid_value(1)), None);
+        assert_eq!(resolver.group(&uuid_value(99)), None);
+    }
+
+    #[test]
+    fn test_entry_settings() {
+        let database = test_database();
+        let resolver = SettingsResolver::new(&database.document.root.group);
+
+        let settings = resolver.entry(&uuid_value(1)).unwrap();
+        assert!(!settings.auto_type_enabled);
+        assert!(!settings.searching_enabled);
+        assert_eq!(
+            settings.auto_type_sequence,
+            "{USERNAME}{TAB}{TAB}{PASSWORD}"
+        );
+
+        let settings = resolver.entry(&uuid_value(2)).unwrap();
+        assert!(!settings.auto_type_enabled);
+        assert_eq!(settings.auto_type_sequence, "{PASSWORD}{ENTER}");
+
+        assert_eq!(resolver.entry(&uuid_value(12)), None);
+    }
+
+    #[test]
+    fn test_parent_path() {
+        let database = test_database();
+        let resolver = SettingsResolver::new(&database.document.root.group);
+
+        let names = |uuid: Uuid| {
+            resolver
+                .parent_path(&uuid)
+                .map(|path| path.iter().map(|g| g.name.clone()).collect::<Vec<_>>())
+        };
+        assert_eq!(names(uuid_value(10)), Some(vec![]));
+        assert_eq!(
+            names(uuid_value(13)),
+            Some(vec!["Root".into(), "Hidden".into()])
+        );
+        assert_eq!(
+            names(uuid_value(1)),
+            Some(vec!["Root".into(), "Hidden".into(), "Inherit".into()])
+        );
+        assert_eq!(names(uuid_value(99)), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! 加密的会话快照, 供应用在进程被系统回收后快速恢复
+//!
+//! 快照保存变换后的密钥和当前(可能未保存的)内层数据, 恢复时跳过KDF以及外层数据的解密.
+//! 快照密钥随机生成, 由应用保存在平台的密钥库中. 快照记录了完整外层header的SHA-256,
+//! 磁盘上的header有任何变化(例如文件被重新保存)时拒绝恢复.
+//!
+//! 格式: magic "KOSS" || 版本(1) || header的SHA-256(32) || nonce(24) || 密文,
+//! 明文为 变换后密钥长度(u32 LE) || 变换后密钥 || 内层header与XML. magic到摘要作为AAD.
+
+use std::io::{Cursor, Read};
+
+use byteorder::{ReadBytesExt, LE};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::crypto::memory_crypt::SecureDataError;
+use crate::crypto::secure_data::SecureData;
+use crate::kdbx::db::kdbx4::errors::Kdbx4Error;
+use crate::kdbx::xml::errors::KdbxSaveError;
+
+pub const SNAPSHOT_KEY_SIZE: usize = 32;
+
+const SNAPSHOT_MAGIC: [u8; 4] = *b"KOSS";
+const SNAPSHOT_VERSION: u8 = 1;
+const NONCE_SIZE: usize = 24;
+const PREFIX_SIZE: usize = SNAPSHOT_MAGIC.len() + 1 + 32;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Invalid snapshot format")]
+    InvalidFormat,
+
+    #[error("Unsupported snapshot version: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Snapshot does not match the database header")]
+    HeaderMismatch,
+
+    #[error("Wrong snapshot key or corrupted snapshot")]
+    DecryptionFailed,
+
+    #[error("Invalid snapshot key length: {0}")]
+    InvalidKeyLength(usize),
+
+    #[error("Failed to generate random data")]
+    RandomError(#[from] getrandom::Error),
+
+    #[error("Secure data error")]
+    SecureDataError(#[from] SecureDataError),
+
+    #[error("Failed to serialize database")]
+    SaveError(#[from] KdbxSaveError),
+
+    #[error("Failed to restore database")]
+    Kdbx4Error(#[from] Kdbx4Error),
+}
+
+/// 生成新的快照密钥, 应交由平台密钥库保存
+pub fn generate_snapshot_key() -> Result<Zeroizing<[u8; SNAPSHOT_KEY_SIZE]>, SnapshotError> {
+    let mut key = Zeroizing::new([0u8; SNAPSHOT_KEY_SIZE]);
+    getrandom::fill(key.as_mut_slice())?;
+    Ok(key)
+}
+
+fn cipher(snapshot_key: &[u8]) -> Result<XChaCha20Poly1305, SnapshotError> {
+    if snapshot_key.len() != SNAPSHOT_KEY_SIZE {
+        return Err(SnapshotError::InvalidKeyLength(snapshot_key.len()));
+    }
+    Ok(XChaCha20Poly1305::new(Key::from_slice(snapshot_key)))
+}
+
+/// 加密变换后的密钥与内层数据
+pub(crate) fn seal(
+    snapshot_key: &[u8],
+    header_sha256: &[u8; 32],
+    transformed_key: &SecureData,
+    payload: &[u8],
+) -> Result<Vec<u8>, SnapshotError> {
+    let cipher = cipher(snapshot_key)?;
+    let transformed_key = transformed_key.unsecure()?;
+
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(
+        4 + transformed_key.len() + payload.len(),
+    ));
+    plaintext.extend_from_slice(&(transformed_key.len() as u32).to_le_bytes());
+    plaintext.extend_from_slice(&transformed_key);
+    plaintext.extend_from_slice(payload);
+
+    let mut snapshot = Vec::with_capacity(PREFIX_SIZE + NONCE_SIZE + plaintext.len() + 16);
+    snapshot.extend_from_slice(&SNAPSHOT_MAGIC);
+    snapshot.push(SNAPSHOT_VERSION);
+    snapshot.extend_from_slice(header_sha256);
+
+    let mut nonce = XNonce::default();
+    getrandom::fill(&mut nonce)?;
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: &plaintext,
+                aad: &snapshot,
+            },
+        )
+        .map_err(|_| SnapshotError::InvalidFormat)?;
+    snapshot.extend_from_slice(&nonce);
+    snapshot.extend_from_slice(&ciphertext);
+    Ok(snapshot)
+}
+
+/// 校验快照与磁盘上的header匹配后解密, 返回变换后的密钥与内层数据
+pub(crate) fn open(
+    snapshot: &[u8],
+    snapshot_key: &[u8],
+    header_sha256: &[u8; 32],
+) -> Result<(SecureData, Zeroizing<Vec<u8>>), SnapshotError> {
+    let cipher = cipher(snapshot_key)?;
+    let mut reader = Cursor::new(snapshot);
+    let mut magic = [0u8; 4];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|_| SnapshotError::InvalidFormat)?;
+    if magic != SNAPSHOT_MAGIC {
+        return Err(SnapshotError::InvalidFormat);
+    }
+    let version = reader.read_u8().map_err(|_| SnapshotError::InvalidFormat)?;
+    if version != SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    let mut fingerprint = [0u8; 32];
+    let mut nonce = XNonce::default();
+    reader
+        .read_exact(&mut fingerprint)
+        .and_then(|_| reader.read_exact(&mut nonce))
+        .map_err(|_| SnapshotError::InvalidFormat)?;
+    if &fingerprint != header_sha256 {
+        return Err(SnapshotError::HeaderMismatch);
+    }
+
+    let plaintext = Zeroizing::new(
+        cipher
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: &snapshot[PREFIX_SIZE + NONCE_SIZE..],
+                    aad: &snapshot[..PREFIX_SIZE],
+                },
+            )
+            .map_err(|_| SnapshotError::DecryptionFailed)?,
+    );
+
+    let mut reader = Cursor::new(plaintext.as_slice());
+    let key_len = reader
+        .read_u32::<LE>()
+        .map_err(|_| SnapshotError::InvalidFormat)? as usize;
+    let rest = &plaintext[4..];
+    if key_len > rest.len() {
+        return Err(SnapshotError::InvalidFormat);
+    }
+    let transformed_key = SecureData::new(&rest[..key_len]);
+    let payload = Zeroizing::new(rest[key_len..].to_vec());
+    Ok((transformed_key, payload))
+}
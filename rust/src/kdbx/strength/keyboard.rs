@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// 键盘布局图, 用于识别相邻按键组成的模式 (例如 `qwerty`, `zxcvbn`, `7896`)
+pub struct Graph {
+    pub name: &'static str,
+    /// 字符所在的按键位置 (行, 列), 上档字符与对应的按键相同
+    keys: HashMap<char, (usize, usize)>,
+    shifted: HashSet<char>,
+    /// 第 `r` 行第 `i` 列的按键与上一行第 `i + offset` 列的按键相邻
+    up_offsets: Vec<Vec<isize>>,
+    row_lengths: Vec<usize>,
+    pub starting_positions: usize,
+    pub average_degree: f64,
+}
+
+/// 按键之间的方向, 用于统计模式中的转向次数
+pub type Direction = (isize, isize);
+
+impl Graph {
+    fn new(name: &'static str, rows: &[(&str, Option<&str>)], up_offsets: Vec<Vec<isize>>) -> Self {
+        let mut keys = HashMap::new();
+        let mut shifted = HashSet::new();
+        let mut row_lengths = Vec::new();
+        for (r, (lower, upper)) in rows.iter().enumerate() {
+            for (i, c) in lower.chars().enumerate() {
+                keys.insert(c, (r, i));
+            }
+            if let Some(upper) = upper {
+                for (i, c) in upper.chars().enumerate() {
+                    keys.insert(c, (r, i));
+                    shifted.insert(c);
+                }
+            }
+            row_lengths.push(lower.chars().count());
+        }
+
+        let mut graph = Self {
+            name,
+            keys,
+            shifted,
+            up_offsets,
+            row_lengths,
+            starting_positions: 0,
+            average_degree: 0.0,
+        };
+
+        let positions: Vec<(usize, usize)> = graph
+            .row_lengths
+            .iter()
+            .enumerate()
+            .flat_map(|(r, len)| (0..*len).map(move |i| (r, i)))
+            .collect();
+        let degrees: usize = positions
+            .iter()
+            .map(|a| {
+                positions
+                    .iter()
+                    .filter(|b| graph.direction(*a, **b).is_some())
+                    .count()
+            })
+            .sum();
+        graph.starting_positions = positions.len();
+        graph.average_degree = degrees as f64 / positions.len() as f64;
+        graph
+    }
+
+    pub fn is_shifted(&self, c: char) -> bool {
+        self.shifted.contains(&c)
+    }
+
+    /// 两个字符所在按键的方向, 不相邻时返回None
+    pub fn adjacent(&self, a: char, b: char) -> Option<Direction> {
+        let a = *self.keys.get(&a)?;
+        let b = *self.keys.get(&b)?;
+        self.direction(a, b)
+    }
+
+    fn direction(&self, a: (usize, usize), b: (usize, usize)) -> Option<Direction> {
+        let (ar, ai) = (a.0 as isize, a.1 as isize);
+        let (br, bi) = (b.0 as isize, b.1 as isize);
+        if ar == br {
+            return ((bi - ai).abs() == 1).then_some((0, bi - ai));
+        }
+        // 统一为从下一行到上一行的关系
+        let (lower, upper, dir) = match br - ar {
+            -1 => ((ar, ai), (br, bi), -1),
+            1 => ((br, bi), (ar, ai), 1),
+            _ => return None,
+        };
+        let offset = upper.1 - lower.1;
+        self.up_offsets[lower.0 as usize]
+            .contains(&offset)
+            .then_some((dir, offset))
+    }
+}
+
+pub fn graphs() -> &'static [Graph] {
+    static GRAPHS: OnceLock<Vec<Graph>> = OnceLock::new();
+    GRAPHS.get_or_init(|| {
+        vec![
+            Graph::new(
+                "qwerty",
+                &[
+                    ("`1234567890-=", Some("~!@#$%^&*()_+")),
+                    ("qwertyuiop[]\\", Some("QWERTYUIOP{}|")),
+                    ("asdfghjkl;'", Some("ASDFGHJKL:\"")),
+                    ("zxcvbnm,./", Some("ZXCVBNM<>?")),
+                ],
+                // 第二行相对第一行右移一个半键, 之后每行右移约半个键
+                vec![vec![], vec![1, 2], vec![0, 1], vec![0, 1]],
+            ),
+            Graph::new(
+                "keypad",
+                &[("789", None), ("456", None), ("123", None), ("0", None)],
+                vec![vec![], vec![-1, 0, 1], vec![-1, 0, 1], vec![-1, 0, 1]],
+            ),
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qwerty_adjacency() {
+        let qwerty = &graphs()[0];
+        assert_eq!(qwerty.starting_positions, 47);
+        assert!(qwerty.adjacent('q', 'w').is_some());
+        assert!(qwerty.adjacent('q', '1').is_some());
+        assert!(qwerty.adjacent('q', '2').is_some());
+        assert!(qwerty.adjacent('q', '3').is_none());
+        assert!(qwerty.adjacent('a', 'q').is_some());
+        assert!(qwerty.adjacent('a', 'z').is_some());
+        assert!(qwerty.adjacent('Z', 'x').is_some());
+        assert!(qwerty.adjacent('a', 'p').is_none());
+        assert!(qwerty.is_shifted('!'));
+        assert_eq!(qwerty.adjacent('q', 'w'), qwerty.adjacent('a', 's'));
+    }
+
+    #[test]
+    fn test_keypad_adjacency() {
+        let keypad = &graphs()[1];
+        assert!(keypad.adjacent('7', '5').is_some());
+        assert!(keypad.adjacent('0', '2').is_some());
+        assert!(keypad.adjacent('0', '3').is_none());
+        assert!(keypad.adjacent('7', '9').is_none());
+    }
+}
@@ -0,0 +1,790 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use zeroize::Zeroizing;
+
+use crate::kdbx::strength::keyboard::{graphs, Direction};
+use crate::kdbx::strength::scoring;
+
+/// 字典中最长单词的长度, 更长的子串不做查找
+const MAX_WORD_LENGTH: usize = 24;
+/// 序列中相邻字符的最大差值
+const MAX_SEQUENCE_DELTA: i64 = 5;
+/// 年份与参考年份的最小差距, 避免近年的日期被低估
+pub const MIN_YEAR_SPACE: f64 = 20.0;
+const DATE_SEPARATORS: &[char] = &[' ', '-', '/', '\\', '_', '.'];
+/// 不在内置列表中的单词按完整频率表的规模估算, 与zxcvbn英文词表的大小相当
+const UNLISTED_WORD_RANK: usize = 30000;
+const MIN_UNLISTED_WORD_LENGTH: usize = 4;
+/// 超过此数量的连续辅音不像英文单词
+const MAX_CONSONANT_RUN: usize = 4;
+
+/// 常见的 l33t 替换
+const L33T_TABLE: &[(char, &[char])] = &[
+    ('4', &['a']),
+    ('@', &['a']),
+    ('8', &['b']),
+    ('(', &['c']),
+    ('{', &['c']),
+    ('[', &['c']),
+    ('<', &['c']),
+    ('3', &['e']),
+    ('6', &['g']),
+    ('9', &['g']),
+    ('1', &['i', 'l']),
+    ('!', &['i']),
+    ('|', &['i', 'l']),
+    ('7', &['l', 't']),
+    ('0', &['o']),
+    ('$', &['s']),
+    ('5', &['s']),
+    ('+', &['t']),
+    ('%', &['x']),
+    ('2', &['z']),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dictionary {
+    /// 常见密码
+    Passwords,
+    /// 常用英文单词和人名
+    English,
+    /// 不在内置列表中但像单词的字母串
+    Unlisted,
+}
+
+/// 内置的频率表, 按常见程度排序, 行号即为排名
+///
+/// 这两个列表只收录了最常见的一小部分词, 不是完整的zxcvbn词库。列表之外像单词的字母串
+/// 由 [`unlisted_word_match`] 按 [`UNLISTED_WORD_RANK`] 估算, 以免被当作随机字符而高估强度。
+fn dictionaries() -> &'static [(Dictionary, HashMap<&'static str, usize>)] {
+    static DICTIONARIES: OnceLock<Vec<(Dictionary, HashMap<&'static str, usize>)>> =
+        OnceLock::new();
+    DICTIONARIES.get_or_init(|| {
+        let ranked = |list: &'static str| {
+            list.lines()
+                .filter(|line| !line.is_empty())
+                .enumerate()
+                .map(|(i, word)| (word, i + 1))
+                .collect()
+        };
+        vec![
+            (
+                Dictionary::Passwords,
+                ranked(include_str!("data/passwords.txt")),
+            ),
+            (
+                Dictionary::English,
+                ranked(include_str!("data/english.txt")),
+            ),
+        ]
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Dictionary {
+        dictionary: Dictionary,
+        rank: usize,
+        reversed: bool,
+        l33t: bool,
+    },
+    Spatial {
+        graph: &'static str,
+        turns: usize,
+        shifted_count: usize,
+    },
+    Repeat {
+        base_guesses: f64,
+        repeat_count: usize,
+    },
+    Sequence {
+        ascending: bool,
+    },
+    Date {
+        year: i32,
+        separator: bool,
+    },
+    Year {
+        year: i32,
+    },
+    BruteForce,
+}
+
+/// 密码中 `[start, end)` 范围内的字符匹配到的模式
+///
+/// 不保存匹配到的原文。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub pattern: Pattern,
+    pub guesses: f64,
+}
+
+impl Match {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// 运行所有匹配器
+pub fn omnimatch(chars: &[char], reference_year: i32) -> Vec<Match> {
+    let mut matches = Vec::new();
+    matches.extend(dictionary_match(chars));
+    matches.extend(reverse_dictionary_match(chars));
+    matches.extend(l33t_match(chars));
+    matches.extend(unlisted_word_match(chars));
+    matches.extend(spatial_match(chars));
+    matches.extend(repeat_match(chars, reference_year));
+    matches.extend(sequence_match(chars));
+    matches.extend(date_match(chars, reference_year));
+    matches.sort_by_key(|m| (m.start, m.end));
+    matches
+}
+
+fn lowercase(chars: &[char]) -> Zeroizing<Vec<char>> {
+    Zeroizing::new(
+        chars
+            .iter()
+            .map(|c| c.to_lowercase().next().unwrap_or(*c))
+            .collect(),
+    )
+}
+
+fn lookup(chars: &[char]) -> Vec<(Dictionary, usize)> {
+    let word = Zeroizing::new(chars.iter().collect::<String>());
+    dictionaries()
+        .iter()
+        .filter_map(|(dictionary, words)| words.get(word.as_str()).map(|rank| (*dictionary, *rank)))
+        .collect()
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (1..=k).fold(1.0, |acc, i| acc * (n - k + i) as f64 / i as f64)
+}
+
+/// 大小写变化带来的猜测倍数
+fn uppercase_variations(chars: &[char]) -> f64 {
+    let upper = chars.iter().filter(|c| c.is_uppercase()).count();
+    let lower = chars.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_or_last = upper == 1
+        && (chars.first().is_some_and(|c| c.is_uppercase())
+            || chars.last().is_some_and(|c| c.is_uppercase()));
+    if lower == 0 || first_or_last {
+        return 2.0;
+    }
+    (1..=upper.min(lower))
+        .map(|i| n_choose_k(upper + lower, i))
+        .sum()
+}
+
+fn dictionary_match(chars: &[char]) -> Vec<Match> {
+    let lower = lowercase(chars);
+    let mut matches = Vec::new();
+    for start in 0..lower.len() {
+        for end in start + 1..=lower.len().min(start + MAX_WORD_LENGTH) {
+            for (dictionary, rank) in lookup(&lower[start..end]) {
+                matches.push(Match {
+                    start,
+                    end,
+                    pattern: Pattern::Dictionary {
+                        dictionary,
+                        rank,
+                        reversed: false,
+                        l33t: false,
+                    },
+                    guesses: rank as f64 * uppercase_variations(&chars[start..end]),
+                });
+            }
+        }
+    }
+    matches
+}
+
+/// 元音比例与辅音连续长度都接近英文单词
+fn is_word_like(word: &[char]) -> bool {
+    let mut vowels = 0;
+    let mut consonant_run = 0;
+    for c in word {
+        if matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y') {
+            vowels += 1;
+            consonant_run = 0;
+        } else {
+            consonant_run += 1;
+            if consonant_run > MAX_CONSONANT_RUN {
+                return false;
+            }
+        }
+    }
+    vowels * 5 >= word.len()
+}
+
+/// 将完整的字母串视为列表之外的单词, 只匹配最长的连续字母, 不匹配其子串
+fn unlisted_word_match(chars: &[char]) -> Vec<Match> {
+    let lower = lowercase(chars);
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start < lower.len() {
+        if !lower[start].is_ascii_alphabetic() {
+            start += 1;
+            continue;
+        }
+        let end = lower[start..]
+            .iter()
+            .position(|c| !c.is_ascii_alphabetic())
+            .map_or(lower.len(), |len| start + len);
+        let word = &lower[start..end];
+        if (MIN_UNLISTED_WORD_LENGTH..=MAX_WORD_LENGTH).contains(&word.len())
+            && lookup(word).is_empty()
+            && is_word_like(word)
+        {
+            matches.push(Match {
+                start,
+                end,
+                pattern: Pattern::Dictionary {
+                    dictionary: Dictionary::Unlisted,
+                    rank: UNLISTED_WORD_RANK,
+                    reversed: false,
+                    l33t: false,
+                },
+                guesses: UNLISTED_WORD_RANK as f64 * uppercase_variations(&chars[start..end]),
+            });
+        }
+        start = end;
+    }
+    matches
+}
+
+fn reverse_dictionary_match(chars: &[char]) -> Vec<Match> {
+    let reversed = Zeroizing::new(chars.iter().rev().copied().collect::<Vec<char>>());
+    let len = chars.len();
+    dictionary_match(&reversed)
+        .into_iter()
+        .filter(|m| m.len() > 1)
+        .map(|mut m| {
+            (m.start, m.end) = (len - m.end, len - m.start);
+            if let Pattern::Dictionary { reversed, .. } = &mut m.pattern {
+                *reversed = true;
+            }
+            m.guesses *= 2.0;
+            m
+        })
+        .collect()
+}
+
+fn l33t_candidates(c: char) -> Option<&'static [char]> {
+    L33T_TABLE
+        .iter()
+        .find(|(sub, _)| *sub == c)
+        .map(|(_, letters)| *letters)
+}
+
+/// l33t替换带来的猜测倍数, 与zxcvbn一致按替换后的每个字母分别计算
+fn l33t_variations(original: &[char], word: &[char]) -> f64 {
+    let mut substituted: HashMap<char, (usize, usize)> = HashMap::new();
+    for (o, w) in original.iter().zip(word) {
+        let o = o.to_lowercase().next().unwrap_or(*o);
+        if o != *w {
+            substituted.entry(*w).or_default().0 += 1;
+        }
+    }
+    for letter in substituted.keys().copied().collect::<Vec<_>>() {
+        let unsubstituted = original
+            .iter()
+            .filter(|c| c.to_lowercase().next() == Some(letter))
+            .count();
+        substituted.entry(letter).or_default().1 = unsubstituted;
+    }
+    substituted
+        .values()
+        .map(|(s, u)| {
+            if *u == 0 {
+                2.0
+            } else {
+                (1..=(*s).min(*u)).map(|i| n_choose_k(s + u, i)).sum()
+            }
+        })
+        .product()
+}
+
+fn l33t_match(chars: &[char]) -> Vec<Match> {
+    /// 每个子串最多尝试的替换组合数量
+    const MAX_VARIANTS: usize = 16;
+
+    let lower = lowercase(chars);
+    let mut matches = Vec::new();
+    for start in 0..lower.len() {
+        for end in start + 1..=lower.len().min(start + MAX_WORD_LENGTH) {
+            let token = &lower[start..end];
+            if !token.iter().any(|c| l33t_candidates(*c).is_some()) {
+                continue;
+            }
+            let mut variants: Vec<Zeroizing<Vec<char>>> = vec![Zeroizing::new(Vec::new())];
+            for c in token {
+                let options = l33t_candidates(*c).unwrap_or(std::slice::from_ref(c));
+                variants = variants
+                    .iter()
+                    .flat_map(|variant| {
+                        options.iter().map(|option| {
+                            let mut next = variant.clone();
+                            next.push(*option);
+                            next
+                        })
+                    })
+                    .take(MAX_VARIANTS)
+                    .collect();
+            }
+            for variant in variants {
+                if variant.as_slice() == token {
+                    continue;
+                }
+                for (dictionary, rank) in lookup(&variant) {
+                    let original = &chars[start..end];
+                    matches.push(Match {
+                        start,
+                        end,
+                        pattern: Pattern::Dictionary {
+                            dictionary,
+                            rank,
+                            reversed: false,
+                            l33t: true,
+                        },
+                        guesses: rank as f64
+                            * uppercase_variations(original)
+                            * l33t_variations(original, &variant),
+                    });
+                }
+            }
+        }
+    }
+    matches
+}
+
+fn spatial_match(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    for graph in graphs() {
+        let mut start = 0;
+        while start + 1 < chars.len() {
+            let mut end = start + 1;
+            let mut turns = 0;
+            let mut last_direction: Option<Direction> = None;
+            let mut shifted_count = usize::from(graph.is_shifted(chars[start]));
+            while end < chars.len() {
+                let Some(direction) = graph.adjacent(chars[end - 1], chars[end]) else {
+                    break;
+                };
+                if last_direction != Some(direction) {
+                    turns += 1;
+                    last_direction = Some(direction);
+                }
+                shifted_count += usize::from(graph.is_shifted(chars[end]));
+                end += 1;
+            }
+            if end - start >= 3 {
+                matches.push(Match {
+                    start,
+                    end,
+                    pattern: Pattern::Spatial {
+                        graph: graph.name,
+                        turns,
+                        shifted_count,
+                    },
+                    guesses: spatial_guesses(
+                        graph.starting_positions as f64,
+                        graph.average_degree,
+                        end - start,
+                        turns,
+                        shifted_count,
+                    ),
+                });
+            }
+            start = (end - 1).max(start + 1);
+        }
+    }
+    matches
+}
+
+fn spatial_guesses(
+    starting_positions: f64,
+    average_degree: f64,
+    length: usize,
+    turns: usize,
+    shifted_count: usize,
+) -> f64 {
+    let mut guesses = 0.0;
+    for i in 2..=length {
+        for j in 1..=turns.min(i - 1) {
+            guesses +=
+                n_choose_k(i - 1, j - 1) * starting_positions * average_degree.powi(j as i32);
+        }
+    }
+    if shifted_count > 0 {
+        let unshifted = length - shifted_count;
+        if unshifted == 0 {
+            guesses *= 2.0;
+        } else {
+            guesses *= (1..=shifted_count.min(unshifted))
+                .map(|i| n_choose_k(shifted_count + unshifted, i))
+                .sum::<f64>();
+        }
+    }
+    guesses
+}
+
+fn repeat_match(chars: &[char], reference_year: i32) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        // 选择覆盖范围最长的重复, 相同时选择较短的基础串
+        let mut best: Option<(usize, usize)> = None;
+        for base_len in 1..=(chars.len() - start) / 2 {
+            let base = &chars[start..start + base_len];
+            let count = chars[start..]
+                .chunks(base_len)
+                .take_while(|chunk| *chunk == base)
+                .count();
+            if count >= 2 && best.is_none_or(|(len, c)| base_len * count > len * c) {
+                best = Some((base_len, count));
+            }
+        }
+        match best {
+            Some((base_len, repeat_count)) => {
+                let base = &chars[start..start + base_len];
+                let base_guesses = scoring::most_guessable(base, reference_year).guesses;
+                let end = start + base_len * repeat_count;
+                matches.push(Match {
+                    start,
+                    end,
+                    pattern: Pattern::Repeat {
+                        base_guesses,
+                        repeat_count,
+                    },
+                    guesses: base_guesses * repeat_count as f64,
+                });
+                start = end;
+            }
+            None => start += 1,
+        }
+    }
+    matches
+}
+
+fn sequence_match(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    if chars.len() < 2 {
+        return matches;
+    }
+    let delta = |i: usize| chars[i + 1] as i64 - chars[i] as i64;
+
+    let mut push = |start: usize, end: usize, delta: i64| {
+        let len = end - start;
+        let abs = delta.abs();
+        if abs == 0 || abs > MAX_SEQUENCE_DELTA || (len < 3 && abs != 1) {
+            return;
+        }
+        let first = chars[start];
+        let mut base = if matches!(first, 'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9') {
+            4.0
+        } else if first.is_ascii_digit() {
+            10.0
+        } else {
+            26.0
+        };
+        if delta < 0 {
+            base *= 2.0;
+        }
+        matches.push(Match {
+            start,
+            end,
+            pattern: Pattern::Sequence {
+                ascending: delta > 0,
+            },
+            guesses: base * len as f64,
+        });
+    };
+
+    let mut start = 0;
+    let mut last_delta = delta(0);
+    for i in 1..chars.len() - 1 {
+        let current = delta(i);
+        if current != last_delta {
+            push(start, i + 1, last_delta);
+            start = i;
+            last_delta = current;
+        }
+    }
+    push(start, chars.len(), last_delta);
+    matches
+}
+
+fn year_space(year: i32, reference_year: i32) -> f64 {
+    ((year - reference_year).abs() as f64).max(MIN_YEAR_SPACE)
+}
+
+/// 将三个数字解析为 (日, 月, 年), 返回年份
+fn map_ints_to_year(ints: [u32; 3]) -> Option<i32> {
+    let [a, b, c] = ints;
+    if b > 31 || b == 0 {
+        return None;
+    }
+    let candidates = [(c, [a, b]), (a, [b, c])];
+    // 四位年份
+    for (year, rest) in candidates {
+        if (1000..=2050).contains(&year) && is_day_month(rest) {
+            return Some(year as i32);
+        }
+    }
+    // 两位年份
+    for (year, rest) in candidates {
+        if year < 100 && is_day_month(rest) {
+            return Some(if year > 50 { 1900 + year } else { 2000 + year } as i32);
+        }
+    }
+    None
+}
+
+fn is_day_month([a, b]: [u32; 2]) -> bool {
+    let valid = |day: u32, month: u32| (1..=31).contains(&day) && (1..=12).contains(&month);
+    valid(a, b) || valid(b, a)
+}
+
+fn parse_digits(chars: &[char]) -> Option<u32> {
+    if chars.is_empty() || chars.len() > 4 || !chars.iter().all(char::is_ascii_digit) {
+        return None;
+    }
+    chars.iter().collect::<String>().parse().ok()
+}
+
+fn date_match(chars: &[char], reference_year: i32) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut push = |start: usize, end: usize, year: i32, separator: bool| {
+        let mut guesses = year_space(year, reference_year) * 365.0;
+        if separator {
+            guesses *= 4.0;
+        }
+        matches.push(Match {
+            start,
+            end,
+            pattern: Pattern::Date { year, separator },
+            guesses,
+        });
+    };
+
+    for start in 0..chars.len() {
+        // 无分隔符, 例如 `13101987`
+        for end in start + 4..=chars.len().min(start + 8) {
+            let token = &chars[start..end];
+            if !token.iter().all(char::is_ascii_digit) {
+                break;
+            }
+            let best = (1..token.len() - 1)
+                .flat_map(|i| (i + 1..token.len()).map(move |j| (i, j)))
+                .filter_map(|(i, j)| {
+                    map_ints_to_year([
+                        parse_digits(&token[..i])?,
+                        parse_digits(&token[i..j])?,
+                        parse_digits(&token[j..])?,
+                    ])
+                })
+                .min_by_key(|year| (year - reference_year).abs());
+            if let Some(year) = best {
+                push(start, end, year, false);
+            }
+        }
+
+        // 带分隔符, 例如 `13.10.1987`
+        for end in start + 6..=chars.len().min(start + 10) {
+            let token = &chars[start..end];
+            let Some(first) = token.iter().position(|c| DATE_SEPARATORS.contains(c)) else {
+                continue;
+            };
+            let separator = token[first];
+            let Some(second) = token[first + 1..]
+                .iter()
+                .position(|c| *c == separator)
+                .map(|i| i + first + 1)
+            else {
+                continue;
+            };
+            let ints = (|| {
+                Some([
+                    parse_digits(&token[..first])?,
+                    parse_digits(&token[first + 1..second])?,
+                    parse_digits(&token[second + 1..])?,
+                ])
+            })();
+            if let Some(year) = ints.and_then(map_ints_to_year) {
+                push(start, end, year, true);
+            }
+        }
+    }
+
+    // 单独的近期年份, 例如 `1987`
+    for start in 0..chars.len().saturating_sub(3) {
+        let Some(year) = parse_digits(&chars[start..start + 4]) else {
+            continue;
+        };
+        if (1900..=2099).contains(&year) {
+            let year = year as i32;
+            matches.push(Match {
+                start,
+                end: start + 4,
+                pattern: Pattern::Year { year },
+                guesses: year_space(year, reference_year),
+            });
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YEAR: i32 = 2024;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test_dictionary_match() {
+        let matches = dictionary_match(&chars("xPassword"));
+        let m = matches.iter().find(|m| m.start == 1 && m.end == 9).unwrap();
+        assert!(matches!(
+            m.pattern,
+            Pattern::Dictionary {
+                dictionary: Dictionary::Passwords,
+                rank: 2,
+                ..
+            }
+        ));
+        assert_eq!(m.guesses, 4.0);
+
+        let reversed = reverse_dictionary_match(&chars("drowssap"));
+        assert!(reversed.iter().any(|m| m.start == 0
+            && m.end == 8
+            && matches!(m.pattern, Pattern::Dictionary { reversed: true, .. })));
+    }
+
+    #[test]
+    fn test_unlisted_word_match() {
+        let matches = unlisted_word_match(&chars("Maverick1!midnight"));
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].start, matches[0].end), (0, 8));
+        assert_eq!(matches[0].guesses, UNLISTED_WORD_RANK as f64 * 2.0);
+        assert_eq!((matches[1].start, matches[1].end), (10, 18));
+
+        // 列表中的单词、过短或不像单词的字母串不匹配
+        assert!(unlisted_word_match(&chars("password")).is_empty());
+        assert!(unlisted_word_match(&chars("x1abc")).is_empty());
+        assert!(unlisted_word_match(&chars("rWibMFACxAUGZmxhVncy")).is_empty());
+    }
+
+    #[test]
+    fn test_l33t_match() {
+        let matches = l33t_match(&chars("p@55w0rd"));
+        assert!(matches.iter().any(|m| m.start == 0
+            && m.end == 8
+            && matches!(
+                m.pattern,
+                Pattern::Dictionary {
+                    rank: 2,
+                    l33t: true,
+                    ..
+                }
+            )));
+    }
+
+    #[test]
+    fn test_spatial_match() {
+        let matches = spatial_match(&chars("xqwertyx"));
+        assert!(matches.iter().any(|m| m.start == 1
+            && m.end == 7
+            && m.pattern
+                == Pattern::Spatial {
+                    graph: "qwerty",
+                    turns: 1,
+                    shifted_count: 0
+                }));
+
+        let matches = spatial_match(&chars("zaQ!"));
+        assert!(matches.iter().any(|m| m.start == 0
+            && m.end == 4
+            && matches!(
+                m.pattern,
+                Pattern::Spatial {
+                    shifted_count: 2,
+                    ..
+                }
+            )));
+    }
+
+    #[test]
+    fn test_repeat_match() {
+        let matches = repeat_match(&chars("xabcabcabc"), YEAR);
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].start, matches[0].end), (1, 10));
+        assert!(matches!(
+            matches[0].pattern,
+            Pattern::Repeat {
+                repeat_count: 3,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_sequence_match() {
+        let matches = sequence_match(&chars("abcdef97531"));
+        assert!(matches
+            .iter()
+            .any(|m| (m.start, m.end) == (0, 6)
+                && m.pattern == Pattern::Sequence { ascending: true }));
+        assert!(matches
+            .iter()
+            .any(|m| (m.start, m.end) == (6, 11)
+                && m.pattern == Pattern::Sequence { ascending: false }));
+    }
+
+    #[test]
+    fn test_date_match() {
+        let matches = date_match(&chars("x13.10.1987"), YEAR);
+        assert!(matches.iter().any(|m| (m.start, m.end) == (1, 11)
+            && m.pattern
+                == Pattern::Date {
+                    year: 1987,
+                    separator: true
+                }));
+
+        let matches = date_match(&chars("131087"), YEAR);
+        assert!(matches.iter().any(|m| (m.start, m.end) == (0, 6)
+            && m.pattern
+                == Pattern::Date {
+                    year: 1987,
+                    separator: false
+                }));
+
+        let matches = date_match(&chars("2019"), YEAR);
+        assert!(matches
+            .iter()
+            .any(|m| m.pattern == Pattern::Year { year: 2019 } && m.guesses == MIN_YEAR_SPACE));
+    }
+
+    #[test]
+    fn test_variations() {
+        assert_eq!(uppercase_variations(&chars("password")), 1.0);
+        assert_eq!(uppercase_variations(&chars("Password")), 2.0);
+        assert_eq!(uppercase_variations(&chars("PASSWORD")), 2.0);
+        assert_eq!(uppercase_variations(&chars("PaSsword")), 8.0 + 28.0);
+        assert_eq!(l33t_variations(&chars("p4ss"), &chars("pass")), 2.0);
+        assert_eq!(l33t_variations(&chars("a4"), &chars("aa")), 2.0);
+    }
+}
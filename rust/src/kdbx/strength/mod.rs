@@ -0,0 +1,209 @@
+pub mod keyboard;
+pub mod matching;
+pub mod scoring;
+
+use chrono::{Datelike, Utc};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::kdbx::strength::matching::Match;
+use crate::kdbx::xml::database::KeePassDatabase;
+use crate::kdbx::xml::entities::{Entry, Value, FIELD_PASSWORD};
+use crate::kdbx::xml::errors::KdbxDatabaseError;
+
+/// 超过该长度的部分按暴力破解计算, 避免匹配耗时过长
+const MAX_ANALYZED_LENGTH: usize = 100;
+
+/// 不同攻击场景下的破解时间 (秒)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrackTimes {
+    /// 有速率限制的在线攻击, 每小时100次
+    pub online_throttled: f64,
+    /// 无速率限制的在线攻击, 每秒10次
+    pub online_unthrottled: f64,
+    /// 离线攻击慢哈希 (例如Argon2), 每秒1万次
+    pub offline_slow_hashing: f64,
+    /// 离线攻击快哈希 (例如SHA-1), 每秒100亿次
+    pub offline_fast_hashing: f64,
+}
+
+impl CrackTimes {
+    fn from_guesses(guesses: f64) -> Self {
+        Self {
+            online_throttled: guesses / (100.0 / 3600.0),
+            online_unthrottled: guesses / 10.0,
+            offline_slow_hashing: guesses / 1e4,
+            offline_fast_hashing: guesses / 1e10,
+        }
+    }
+}
+
+/// 密码强度估算结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strength {
+    pub guesses: f64,
+    pub guesses_log10: f64,
+    /// 等效熵 (比特), 即 `log2(guesses)`
+    pub entropy_bits: f64,
+    pub crack_times: CrackTimes,
+    /// 0 (极弱) - 4 (很强), 与zxcvbn的评分一致
+    pub score: u8,
+    /// 猜测次数最少的模式组合
+    pub sequence: Vec<Match>,
+}
+
+/// zxcvbn风格的密码强度估算
+pub fn estimate(password: &str) -> Strength {
+    estimate_with_year(password, Utc::now().year())
+}
+
+fn estimate_with_year(password: &str, reference_year: i32) -> Strength {
+    let chars = Zeroizing::new(password.chars().collect::<Vec<char>>());
+    let analyzed = chars.len().min(MAX_ANALYZED_LENGTH);
+    let result = scoring::most_guessable(&chars[..analyzed], reference_year);
+    let rest = (chars.len() - analyzed) as i32;
+    let guesses = (result.guesses * 10f64.powi(rest)).min(f64::MAX);
+
+    let score = match guesses {
+        g if g < 1e3 + 5.0 => 0,
+        g if g < 1e6 + 5.0 => 1,
+        g if g < 1e8 + 5.0 => 2,
+        g if g < 1e10 + 5.0 => 3,
+        _ => 4,
+    };
+
+    Strength {
+        guesses,
+        guesses_log10: guesses.log10(),
+        entropy_bits: guesses.log2(),
+        crack_times: CrackTimes::from_guesses(guesses),
+        score,
+        sequence: result.sequence,
+    }
+}
+
+/// 估算字段值的强度, 支持受保护的值
+pub fn estimate_value(
+    database: &KeePassDatabase,
+    value: &Value,
+) -> Result<Strength, KdbxDatabaseError> {
+    let password = Zeroizing::new(database.get_value_string(value)?);
+    Ok(estimate(&password))
+}
+
+/// 条目是否参与密码质量检查
+///
+/// <https://keepass.info/help/kb/pw_quality_est.html>
+pub fn is_quality_checked(entry: &Entry) -> bool {
+    entry
+        .quality_check
+        .as_ref()
+        .is_none_or(|quality_check| quality_check.value())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryStrength {
+    pub uuid: Uuid,
+    pub strength: Strength,
+}
+
+/// 数据库中所有设置了密码的条目的强度, 不包含历史记录和 `QualityCheck=False` 的条目
+pub fn quality_report(database: &KeePassDatabase) -> Result<Vec<EntryStrength>, KdbxDatabaseError> {
+    let mut report = Vec::new();
+    for entry in database.document.root.group.all_entries() {
+        if !is_quality_checked(entry) {
+            continue;
+        }
+        let Some(value) = entry.get_value(FIELD_PASSWORD) else {
+            continue;
+        };
+        let password = Zeroizing::new(database.get_value_string(value)?);
+        if password.is_empty() {
+            continue;
+        }
+        report.push(EntryStrength {
+            uuid: *entry.uuid.value(),
+            strength: estimate(&password),
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::xml::fixtures;
+
+    #[test]
+    fn test_scores() {
+        assert_eq!(estimate_with_year("", 2024).score, 0);
+        assert_eq!(estimate_with_year("password", 2024).score, 0);
+        assert_eq!(estimate_with_year("qwerty123", 2024).score, 0);
+        assert!(estimate_with_year("Tr0ub4dour&3", 2024).score >= 2);
+        assert_eq!(estimate_with_year("rWibMFACxAUGZmxhVncy", 2024).score, 4);
+    }
+
+    #[test]
+    fn test_common_passwords_outside_lists() {
+        for password in [
+            "maverick",
+            "samantha",
+            "midnight",
+            "pumpkin",
+            "Snoopy",
+            "maverick1",
+            "peanut1",
+        ] {
+            assert!(
+                estimate_with_year(password, 2024).score <= 1,
+                "{password} is overrated"
+            );
+        }
+    }
+
+    #[test]
+    fn test_crack_times() {
+        let strength = estimate_with_year("password", 2024);
+        assert_eq!(strength.guesses, 3.0);
+        assert_eq!(strength.crack_times.online_unthrottled, 0.3);
+        assert_eq!(strength.crack_times.online_throttled, 108.0);
+        assert!((strength.entropy_bits - 3f64.log2()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_long_password() {
+        let password = "x".repeat(150);
+        let strength = estimate_with_year(&password, 2024);
+        assert!(strength.guesses_log10 > 50.0);
+        assert_eq!(strength.score, 4);
+    }
+
+    #[test]
+    fn test_quality_report() {
+        let weak = fixtures::entry_xml(&fixtures::uuid(1), &[("Password", "password")], "");
+        let excluded = fixtures::entry_xml(
+            &fixtures::uuid(2),
+            &[("Password", "123456")],
+            "<QualityCheck>False</QualityCheck>",
+        );
+        let empty = fixtures::entry_xml(&fixtures::uuid(3), &[("Password", "")], "");
+        let strong = fixtures::entry_xml(
+            &fixtures::uuid(4),
+            &[("Password", "rWibMFACxAUGZmxhVncy")],
+            "<QualityCheck>True</QualityCheck>",
+        );
+        let database = fixtures::database(&fixtures::group_xml(
+            &fixtures::uuid(10),
+            "Root",
+            "",
+            &format!("{weak}{excluded}{empty}{strong}"),
+        ));
+
+        let report = quality_report(&database).unwrap();
+        let scores: Vec<(u8, u8)> = report
+            .iter()
+            .map(|item| (item.uuid.as_bytes()[15], item.strength.score))
+            .collect();
+        assert_eq!(scores, vec![(1, 0), (4, 4)]);
+    }
+}
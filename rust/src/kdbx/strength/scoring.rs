@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+
+use crate::kdbx::strength::matching::{omnimatch, Match, Pattern};
+
+/// 每个字符的暴力破解基数
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+/// 攻击者尝试较长模式组合之前的基础猜测次数
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10000.0;
+
+/// 猜测次数最少的模式组合
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuessResult {
+    pub guesses: f64,
+    pub sequence: Vec<Match>,
+}
+
+/// 计算匹配的猜测次数, 部分匹配有最低猜测次数, 避免过度低估
+fn match_guesses(m: &Match, password_len: usize) -> f64 {
+    let min_guesses = if m.len() == password_len {
+        1.0
+    } else if m.len() == 1 {
+        MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+    } else {
+        MIN_SUBMATCH_GUESSES_MULTI_CHAR
+    };
+    m.guesses.max(min_guesses)
+}
+
+fn bruteforce(start: usize, end: usize) -> Match {
+    let guesses = BRUTEFORCE_CARDINALITY.powi((end - start) as i32);
+    let min_guesses = if end - start == 1 {
+        MIN_SUBMATCH_GUESSES_SINGLE_CHAR + 1.0
+    } else {
+        MIN_SUBMATCH_GUESSES_MULTI_CHAR + 1.0
+    };
+    Match {
+        start,
+        end,
+        pattern: Pattern::BruteForce,
+        guesses: guesses.max(min_guesses).min(f64::MAX),
+    }
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).map(|i| i as f64).product()
+}
+
+#[derive(Clone)]
+struct Candidate {
+    /// 组合的总猜测次数
+    guesses: f64,
+    /// 各匹配猜测次数的乘积
+    product: f64,
+    last: Match,
+}
+
+/// 动态规划求猜测次数最少的匹配序列
+///
+/// 与zxcvbn的 `most_guessable_match_sequence` 一致: 长度为 `l` 的序列的猜测次数为
+/// `l! * ∏guesses + D^(l-1)`。
+pub fn most_guessable(chars: &[char], reference_year: i32) -> GuessResult {
+    let n = chars.len();
+    if n == 0 {
+        return GuessResult {
+            guesses: 1.0,
+            sequence: Vec::new(),
+        };
+    }
+    let matches = omnimatch(chars, reference_year);
+
+    // optimal[k][l]: 覆盖 `[0, k]` 且由 `l` 个匹配组成的最优组合
+    let mut optimal: Vec<BTreeMap<usize, Candidate>> = vec![BTreeMap::new(); n];
+
+    let update =
+        |optimal: &mut Vec<BTreeMap<usize, Candidate>>, m: Match, l: usize, prefix: f64| {
+            let k = m.end - 1;
+            let product = prefix * match_guesses(&m, n);
+            let guesses =
+                factorial(l) * product + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(l as i32 - 1);
+            // 已有更短且不更差的组合时跳过
+            if optimal[k]
+                .range(..=l)
+                .any(|(_, candidate)| candidate.guesses <= guesses)
+            {
+                return;
+            }
+            optimal[k].insert(
+                l,
+                Candidate {
+                    guesses,
+                    product,
+                    last: m,
+                },
+            );
+        };
+
+    for k in 0..n {
+        for m in matches.iter().filter(|m| m.end - 1 == k) {
+            if m.start == 0 {
+                update(&mut optimal, m.clone(), 1, 1.0);
+            } else {
+                let previous: Vec<(usize, f64)> = optimal[m.start - 1]
+                    .iter()
+                    .map(|(l, candidate)| (*l, candidate.product))
+                    .collect();
+                for (l, product) in previous {
+                    update(&mut optimal, m.clone(), l + 1, product);
+                }
+            }
+        }
+
+        update(&mut optimal, bruteforce(0, k + 1), 1, 1.0);
+        for start in 1..=k {
+            // 连续的暴力匹配合并为一个
+            let previous: Vec<(usize, f64)> = optimal[start - 1]
+                .iter()
+                .filter(|(_, candidate)| candidate.last.pattern != Pattern::BruteForce)
+                .map(|(l, candidate)| (*l, candidate.product))
+                .collect();
+            for (l, product) in previous {
+                update(&mut optimal, bruteforce(start, k + 1), l + 1, product);
+            }
+        }
+    }
+
+    let (mut l, best) = optimal[n - 1]
+        .iter()
+        .min_by(|a, b| a.1.guesses.total_cmp(&b.1.guesses))
+        .map(|(l, candidate)| (*l, candidate.clone()))
+        .expect("bruteforce always covers the password");
+
+    let mut sequence = Vec::with_capacity(l);
+    let mut k = n;
+    while k > 0 {
+        let candidate = &optimal[k - 1][&l];
+        sequence.push(candidate.last.clone());
+        k = candidate.last.start;
+        l -= 1;
+    }
+    sequence.reverse();
+
+    GuessResult {
+        guesses: best.guesses,
+        sequence,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guess(password: &str) -> GuessResult {
+        let chars: Vec<char> = password.chars().collect();
+        most_guessable(&chars, 2024)
+    }
+
+    #[test]
+    fn test_single_dictionary_word() {
+        let result = guess("password");
+        assert_eq!(result.sequence.len(), 1);
+        assert_eq!(result.guesses, 2.0 + 1.0);
+    }
+
+    #[test]
+    fn test_combined_sequence() {
+        let result = guess("Dragon1987qwerty");
+        let patterns: Vec<&Pattern> = result.sequence.iter().map(|m| &m.pattern).collect();
+        assert_eq!(patterns.len(), 3);
+        assert!(matches!(patterns[0], Pattern::Dictionary { .. }));
+        assert!(matches!(patterns[1], Pattern::Year { year: 1987 }));
+        assert!(matches!(
+            patterns[2],
+            Pattern::Dictionary { .. } | Pattern::Spatial { .. }
+        ));
+    }
+
+    #[test]
+    fn test_random_password_uses_bruteforce() {
+        let result = guess("xK#9vQ");
+        assert!(result.guesses >= 1e5);
+        assert!(result
+            .sequence
+            .iter()
+            .any(|m| m.pattern == Pattern::BruteForce));
+    }
+
+    #[test]
+    fn test_sequence_covers_password() {
+        for password in [
+            "a",
+            "aaaaaaa",
+            "abc123!",
+            "correcthorsebatterystaple",
+            "13.10.1987",
+        ] {
+            let result = guess(password);
+            let mut position = 0;
+            for m in &result.sequence {
+                assert_eq!(m.start, position);
+                position = m.end;
+            }
+            assert_eq!(position, password.chars().count());
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     kdbx::{
         config::MemoryProtectConfig, db::kdbx4::inner_header::{Kdbx4InnerEncryption, Kdbx4InnerHeader}, xml::{
-            entities::{KeePassFile, Value},
+            entities::{Entry, KeePassFile, Value},
             errors::{KdbxDatabaseError, KdbxSaveError},
             protected_value,
         }
@@ -34,7 +34,7 @@ impl KeePassDatabase {
     pub fn get_value_string(&self, entry: &Value) -> Result<String, KdbxDatabaseError> {
         match entry {
             Value::Unprotected(ref value) => Ok(value.to_string()),
-            Value::WaitProtect(ref value) => Ok(value.to_string()),
+            Value::WaitProtect(ref value) => Ok(String::from_utf8_lossy(&value.unsecure()?).to_string()),
             Value::Protected { value, offset } => {
                 if let Some(offset) = offset {
                     let mut cipher = self.inner_header.encryption.get_stream_cipher();
@@ -49,6 +49,15 @@ impl KeePassDatabase {
         }
     }
 
+    /// 获取条目中指定名称的附件内容
+    pub fn get_attachment(&self, entry: &Entry, name: &str) -> Option<&[u8]> {
+        let binary = entry.binary.iter().find(|binary| binary.key == name)?;
+        self.inner_header
+            .binary_content
+            .get(binary.value.reference as usize)
+            .map(|content| content.content.as_slice())
+    }
+
     pub fn encrypt_database(&self) -> Result<KeePassDatabase, KdbxSaveError> {
         let new_inner_header = self.inner_header.copy_with(Kdbx4InnerEncryption::new()?);
         let mut old_cipher = self.inner_header.encryption.get_stream_cipher();
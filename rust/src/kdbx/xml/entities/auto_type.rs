@@ -1,13 +1,40 @@
 use serde::{Deserialize, Serialize};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use crate::kdbx::xml::entities::TBool;
+
 /// https://keepass.info/help/base/autotype.html
-#[derive(Debug, Serialize, Deserialize, PartialEq, Default, Clone, Zeroize, ZeroizeOnDrop)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Zeroize, ZeroizeOnDrop)]
 pub struct AutoType {
     #[serde(rename = "Enabled")]
-    pub enabled: String,
+    pub enabled: TBool,
     #[serde(rename = "DataTransferObfuscation")]
     pub data_transfer_obfuscation: u32,
+    /// 为空或不存在时从父组继承
     #[serde(rename = "DefaultSequence")]
     pub default_sequence: Option<String>,
+    #[serde(rename = "Association", default)]
+    pub association: Vec<Association>,
+}
+
+impl Default for AutoType {
+    fn default() -> Self {
+        Self {
+            enabled: true.into(),
+            data_transfer_obfuscation: 0,
+            default_sequence: None,
+            association: Vec::new(),
+        }
+    }
+}
+
+/// 特定窗口的自动输入序列, https://keepass.info/help/base/autotype.html#atwindows
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default, Clone, Zeroize, ZeroizeOnDrop)]
+pub struct Association {
+    /// 窗口标题过滤, 支持`*`通配符或以`//`包围的正则表达式
+    #[serde(rename = "Window")]
+    pub window: String,
+    /// 为空时使用条目的默认序列
+    #[serde(rename = "KeystrokeSequence", default)]
+    pub keystroke_sequence: String,
 }
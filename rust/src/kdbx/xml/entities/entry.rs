@@ -1,12 +1,31 @@
 use crate::kdbx::xml::entities::auto_type::AutoType;
+use crate::kdbx::xml::entities::custom_data::CustomData;
 use crate::kdbx::xml::entities::history::History;
 use crate::kdbx::xml::entities::protected_binary::ProtectedBinary;
 use crate::kdbx::xml::entities::string_field::ProtectedString;
 use crate::kdbx::xml::entities::times::Times;
+use crate::kdbx::xml::entities::value::Value;
 use crate::kdbx::xml::entities::{TBool, TColor, TOptionUuid, TUuid};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+/// 标准字段的Key
+pub const FIELD_TITLE: &str = "Title";
+pub const FIELD_USER_NAME: &str = "UserName";
+pub const FIELD_PASSWORD: &str = "Password";
+pub const FIELD_URL: &str = "URL";
+pub const FIELD_NOTES: &str = "Notes";
+
+pub const STANDARD_FIELDS: [&str; 5] = [
+    FIELD_TITLE,
+    FIELD_USER_NAME,
+    FIELD_PASSWORD,
+    FIELD_URL,
+    FIELD_NOTES,
+];
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Zeroize, ZeroizeOnDrop)]
 pub struct Entry {
     #[serde(rename = "UUID")]
@@ -56,6 +75,10 @@ pub struct Entry {
     #[serde(rename = "AutoType")]
     #[serde(default)]
     pub auto_type: Option<AutoType>,
+    /// KDBX 4
+    #[serde(rename = "CustomData")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_data: Option<CustomData>,
     /// <https://keepass.info/help/v2/entry.html#hst>
     #[serde(
         rename = "History",
@@ -65,6 +88,65 @@ pub struct Entry {
     pub history: Option<History>,
 }
 
+impl Entry {
+    /// 创建不含任何字段的新条目
+    pub fn new(uuid: Uuid, now: DateTime<Utc>) -> Self {
+        Self {
+            uuid: uuid.into(),
+            icon_id: 0,
+            custom_icon_uuid: TOptionUuid::default(),
+            foreground_color: None,
+            background_color: None,
+            override_url: None,
+            quality_check: None,
+            tags: String::new(),
+            previous_parent_group: TOptionUuid::default(),
+            times: Times::new(now),
+            string: Vec::new(),
+            binary: Vec::new(),
+            auto_type: Some(AutoType::default()),
+            custom_data: None,
+            history: None,
+        }
+    }
+
+    /// 按Key查找字段值, Key区分大小写
+    pub fn get_value(&self, key: &str) -> Option<&Value> {
+        self.string
+            .iter()
+            .find(|field| field.key == key)
+            .map(|field| &field.value)
+    }
+
+    /// 设置字段值, Key不存在时追加到末尾
+    pub fn set_value(&mut self, key: &str, value: Value) {
+        match self.string.iter_mut().find(|field| field.key == key) {
+            Some(field) => field.value = value,
+            None => self.string.push(ProtectedString {
+                key: key.to_string(),
+                value,
+            }),
+        }
+    }
+
+    /// 删除字段, 返回字段是否存在
+    pub fn remove_value(&mut self, key: &str) -> bool {
+        let len = self.string.len();
+        self.string.retain(|field| field.key != key);
+        self.string.len() != len
+    }
+
+    /// 按Key查找条目的自定义数据
+    pub fn get_custom_data(&self, key: &str) -> Option<&str> {
+        self.custom_data
+            .as_ref()?
+            .item
+            .iter()
+            .find(|item| item.key == key)
+            .map(|item| item.value.as_str())
+    }
+}
+
 fn should_skip_history(history: &Option<History>) -> bool {
     match history {
         None => true,
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use crate::kdbx::xml::entities::times::Times;
 use crate::kdbx::xml::entities::entry::Entry;
 use crate::kdbx::xml::entities::{TBool, TNullableBoolEx, TOptionUuid, TUuid};
+use uuid::Uuid;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Zeroize, ZeroizeOnDrop)]
@@ -49,3 +50,70 @@ pub struct Group {
     #[serde(rename = "Group", default)]
     pub group: Vec<Group>,
 }
+
+impl Group {
+    /// 查找包含指定条目的组链, 从当前组开始, 到条目的直接父组结束
+    pub fn find_entry_path(&self, uuid: &Uuid) -> Option<Vec<&Group>> {
+        if self.entry.iter().any(|entry| entry.uuid.value() == uuid) {
+            return Some(vec![self]);
+        }
+        for child in &self.group {
+            if let Some(mut path) = child.find_entry_path(uuid) {
+                path.insert(0, self);
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    /// 查找指定组的组链, 从当前组开始, 到该组本身结束
+    pub fn find_group_path(&self, uuid: &Uuid) -> Option<Vec<&Group>> {
+        if self.uuid.value() == uuid {
+            return Some(vec![self]);
+        }
+        for child in &self.group {
+            if let Some(mut path) = child.find_group_path(uuid) {
+                path.insert(0, self);
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    /// 在当前组及其子组中查找组, 包含当前组本身
+    pub fn find_group_mut(&mut self, uuid: &Uuid) -> Option<&mut Group> {
+        if self.uuid.value() == uuid {
+            return Some(self);
+        }
+        self.group
+            .iter_mut()
+            .find_map(|child| child.find_group_mut(uuid))
+    }
+
+    /// 在当前组及其子组中查找条目, 不包含历史记录
+    pub fn find_entry(&self, uuid: &Uuid) -> Option<&Entry> {
+        self.entry
+            .iter()
+            .find(|entry| entry.uuid.value() == uuid)
+            .or_else(|| self.group.iter().find_map(|child| child.find_entry(uuid)))
+    }
+
+    /// 与 `find_entry` 相同, 返回可变引用
+    pub fn find_entry_mut(&mut self, uuid: &Uuid) -> Option<&mut Entry> {
+        if let Some(index) = self.entry.iter().position(|entry| entry.uuid.value() == uuid) {
+            return self.entry.get_mut(index);
+        }
+        self.group
+            .iter_mut()
+            .find_map(|child| child.find_entry_mut(uuid))
+    }
+
+    /// 深度优先遍历当前组及其子组中的所有条目, 不包含历史记录
+    pub fn all_entries(&self) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self.entry.iter().collect();
+        for child in &self.group {
+            entries.extend(child.all_entries());
+        }
+        entries
+    }
+}
@@ -1,6 +1,7 @@
 use crate::kdbx::xml::entities::custom_data::CustomData;
 use crate::kdbx::xml::entities::memory_protection::MemoryProtection;
 use crate::kdbx::xml::entities::{CustomIcon, TBool, TColor, TDateTime, TOptionUuid};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -94,3 +95,31 @@ pub struct Meta {
     #[serde(default)]
     pub custom_data: Option<CustomData>,
 }
+
+/// 按MasterKeyChangeRec/MasterKeyChangeForce策略得出的主密钥状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MasterKeyStatus {
+    Current,
+    ChangeRecommended,
+    ChangeForced,
+}
+
+impl Meta {
+    /// 未记录更改时间时视为已超期, 与KeePass一致
+    pub fn master_key_status(&self, now: DateTime<Utc>) -> MasterKeyStatus {
+        let overdue = |days: i32| {
+            days >= 0
+                && self
+                    .master_key_changed
+                    .value()
+                    .is_none_or(|changed| now - changed >= Duration::days(days as i64))
+        };
+        if overdue(self.master_key_change_force) {
+            MasterKeyStatus::ChangeForced
+        } else if overdue(self.master_key_change_rec) {
+            MasterKeyStatus::ChangeRecommended
+        } else {
+            MasterKeyStatus::Current
+        }
+    }
+}
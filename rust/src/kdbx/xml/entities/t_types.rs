@@ -11,6 +11,18 @@ impl Zeroize for TUuid {
     fn zeroize(&mut self) {}
 }
 
+impl From<Uuid> for TUuid {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl TUuid {
+    pub fn value(&self) -> &Uuid {
+        &self.0
+    }
+}
+
 impl Serialize for TUuid {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -52,6 +64,18 @@ impl Default for TOptionUuid {
     }
 }
 
+impl From<Option<Uuid>> for TOptionUuid {
+    fn from(value: Option<Uuid>) -> Self {
+        Self(value)
+    }
+}
+
+impl TOptionUuid {
+    pub fn value(&self) -> Option<&Uuid> {
+        self.0.as_ref()
+    }
+}
+
 impl Serialize for TOptionUuid {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -279,6 +303,18 @@ impl Default for TDateTime {
     }
 }
 
+impl From<DateTime<Utc>> for TDateTime {
+    fn from(value: DateTime<Utc>) -> Self {
+        Self(Some(value))
+    }
+}
+
+impl TDateTime {
+    pub fn value(&self) -> Option<DateTime<Utc>> {
+        self.0
+    }
+}
+
 impl Serialize for TDateTime {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
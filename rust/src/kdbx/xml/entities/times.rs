@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use chrono::{DateTime, Utc};
+
 use crate::kdbx::xml::entities::{TBool, TDateTime};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Zeroize, ZeroizeOnDrop)]
@@ -33,3 +35,18 @@ pub struct Times {
     #[serde(rename = "LocationChanged")]
     pub location_changed: TDateTime,
 }
+
+impl Times {
+    /// 新建对象的时间, 全部设为 `now` 且不过期
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            creation_time: now.into(),
+            last_modification_time: now.into(),
+            last_access_time: now.into(),
+            expiry_time: now.into(),
+            expires: false.into(),
+            usage_count: 0,
+            location_changed: now.into(),
+        }
+    }
+}
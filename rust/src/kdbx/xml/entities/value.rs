@@ -13,7 +13,8 @@ pub enum Value {
         offset: Option<usize>,
     },
     Unprotected(String),
-    WaitProtect(String),
+    /// 新建的明文值, 保存时使用内部流加密
+    WaitProtect(SecureData),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -44,8 +45,8 @@ impl Serialize for Value {
                 protected: None,
                 value: value.clone(),
             },
-            Self::WaitProtect(s) => {
-                return Err(serde::ser::Error::custom(format!("WaitProtect: {}", s)))
+            Self::WaitProtect(_) => {
+                return Err(serde::ser::Error::custom("Value is waiting for protection"))
             }
         }
         .serialize(serializer)
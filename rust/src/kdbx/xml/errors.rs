@@ -2,6 +2,8 @@ use thiserror::Error;
 
 use crate::crypto::errors::CryptoError;
 use crate::crypto::kdf::KdfError;
+use crate::kdbx::db::kdbx4::errors::Kdbx4ConfigError;
+use crate::kdbx::keys::KdbxKeyError;
 use crate::crypto::memory_crypt::SecureDataError;
 
 #[derive(Debug, Error)]
@@ -33,4 +35,10 @@ pub enum KdbxSaveError {
 
     #[error("KDF error")]
     KdfError(#[from] KdfError),
+
+    #[error("Key error")]
+    KeyError(#[from] KdbxKeyError),
+
+    #[error("Invalid database config")]
+    InvalidConfig(#[from] Kdbx4ConfigError),
 }
@@ -0,0 +1,154 @@
+use uuid::Uuid;
+
+use crate::kdbx::{
+    config::MemoryProtectConfig,
+    db::kdbx4::inner_header::{Kdbx4InnerEncryption, Kdbx4InnerHeader},
+    xml::database::KeePassDatabase,
+};
+
+pub const EMPTY_TIMES: &str = r#"<Times>
+    <CreationTime></CreationTime>
+    <LastModificationTime></LastModificationTime>
+    <LastAccessTime></LastAccessTime>
+    <ExpiryTime></ExpiryTime>
+    <Expires>False</Expires>
+    <UsageCount>0</UsageCount>
+    <LocationChanged></LocationChanged>
+</Times>"#;
+
+/// 生成一个带有最小Meta的KeePass XML文档, `root_group`为根组的XML
+pub fn document_xml(root_group: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<KeePassFile>
+    <Meta>
+        <Generator>KeePassOne</Generator>
+        <SettingsChanged></SettingsChanged>
+        <DatabaseName>Test</DatabaseName>
+        <DatabaseNameChanged></DatabaseNameChanged>
+        <DatabaseDescriptionChanged></DatabaseDescriptionChanged>
+        <DefaultUserNameChanged></DefaultUserNameChanged>
+        <MaintenanceHistoryDays>365</MaintenanceHistoryDays>
+        <MasterKeyChanged></MasterKeyChanged>
+        <MasterKeyChangeRec>-1</MasterKeyChangeRec>
+        <MasterKeyChangeForce>-1</MasterKeyChangeForce>
+        <MemoryProtection>
+            <ProtectTitle>False</ProtectTitle>
+            <ProtectUserName>False</ProtectUserName>
+            <ProtectPassword>True</ProtectPassword>
+            <ProtectURL>False</ProtectURL>
+            <ProtectNotes>False</ProtectNotes>
+        </MemoryProtection>
+        <CustomIcons></CustomIcons>
+        <RecycleBinEnabled>True</RecycleBinEnabled>
+        <RecycleBinUUID></RecycleBinUUID>
+        <RecycleBinChanged></RecycleBinChanged>
+        <EntryTemplatesGroup></EntryTemplatesGroup>
+        <EntryTemplatesGroupChanged></EntryTemplatesGroupChanged>
+        <HistoryMaxItems>10</HistoryMaxItems>
+        <HistoryMaxSize>6291456</HistoryMaxSize>
+    </Meta>
+    <Root>
+        {root_group}
+    </Root>
+</KeePassFile>"#
+    )
+}
+
+/// 生成一个组的XML
+pub fn group_xml(uuid: &str, name: &str, extra: &str, children: &str) -> String {
+    format!(
+        r#"<Group>
+    <UUID>{uuid}</UUID>
+    <Name>{name}</Name>
+    <IconID>48</IconID>
+    {EMPTY_TIMES}
+    {extra}
+    {children}
+</Group>"#
+    )
+}
+
+/// 生成Times的XML, 时间为RFC 3339格式
+pub fn times_xml(creation: &str, last_modification: &str, expiry: Option<&str>) -> String {
+    format!(
+        r#"<Times>
+    <CreationTime>{creation}</CreationTime>
+    <LastModificationTime>{last_modification}</LastModificationTime>
+    <LastAccessTime></LastAccessTime>
+    <ExpiryTime>{}</ExpiryTime>
+    <Expires>{}</Expires>
+    <UsageCount>0</UsageCount>
+    <LocationChanged></LocationChanged>
+</Times>"#,
+        expiry.unwrap_or_default(),
+        if expiry.is_some() { "True" } else { "False" }
+    )
+}
+
+/// 生成一个条目的XML, `fields`为(Key, Value)列表, 全部为非保护字段
+pub fn entry_xml(uuid: &str, fields: &[(&str, &str)], extra: &str) -> String {
+    entry_xml_with_times(uuid, EMPTY_TIMES, fields, extra)
+}
+
+/// 与 `entry_xml` 相同, 但使用指定的Times
+pub fn entry_xml_with_times(
+    uuid: &str,
+    times: &str,
+    fields: &[(&str, &str)],
+    extra: &str,
+) -> String {
+    let strings = fields
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "<String><Key>{key}</Key><Value>{}</Value></String>",
+                escape(value)
+            )
+        })
+        .collect::<String>();
+    format!(
+        r#"<Entry>
+    <UUID>{uuid}</UUID>
+    <IconID>0</IconID>
+    {times}
+    {strings}
+    {extra}
+</Entry>"#
+    )
+}
+
+/// 第`n`个测试UUID
+pub fn uuid_value(n: u8) -> Uuid {
+    let mut bytes = [0u8; 16];
+    bytes[15] = n;
+    Uuid::from_bytes(bytes)
+}
+
+/// 第`n`个测试UUID的Base64编码
+pub fn uuid(n: u8) -> String {
+    base64::Engine::encode(
+        &base64::engine::general_purpose::STANDARD,
+        uuid_value(n).as_bytes(),
+    )
+}
+
+pub fn database(root_group: &str) -> KeePassDatabase {
+    let xml = document_xml(root_group);
+    let inner_header = Kdbx4InnerHeader {
+        encryption: Kdbx4InnerEncryption::new().unwrap(),
+        binary_content: Vec::new(),
+    };
+    let config = MemoryProtectConfig {
+        enable_memory_crypt: false,
+        enable_mlock: false,
+    };
+    KeePassDatabase::try_from(xml.as_bytes(), inner_header, &config).unwrap()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
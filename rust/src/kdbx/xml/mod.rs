@@ -3,6 +3,9 @@ pub mod entities;
 pub mod errors;
 pub mod protected_value;
 
+#[cfg(test)]
+pub mod fixtures;
+
 #[cfg(test)]
 mod tests {
     use crate::kdbx::xml::entities::KeePassFile;
@@ -62,12 +62,7 @@ fn process_protected_values(
         {
             *offset = Some(stream_offset);
             stream_offset += value.len();
-            if config.enable_memory_crypt {
-                value.crypt()?;
-            }
-            if config.enable_mlock {
-                value.mlock()?;
-            }
+            config.protect(value)?;
         }
     }
     Ok(stream_offset)
@@ -115,7 +110,7 @@ fn encrypt_protected_values_entry(
             }
             Value::WaitProtect(ref value) => {
                 let offset = new_cipher.current_pos();
-                let new_data = new_cipher.encrypt(value.as_bytes())?;
+                let new_data = new_cipher.encrypt(&value.unsecure()?)?;
                 Some(Value::Protected {
                     value: SecureData::new(&new_data),
                     offset: Some(offset),